@@ -0,0 +1,134 @@
+use crate::cli::CasePolicy;
+use crate::error::Result;
+use chrono::{DateTime, Local};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+/// Nombres de un mismo directorio que solo difieren en mayúsculas/minúsculas:
+/// en un montaje que no las distingue (pCloud, macOS) acabarían pisándose
+#[derive(Debug, Clone, PartialEq)]
+pub struct Collision {
+    pub dir: PathBuf,
+    /// Ordenados; al menos dos
+    pub names: Vec<String>,
+}
+
+/// Colisiones bajo `root` (sin entrar en el directorio de transferencias
+/// parciales), de la más profunda a la menos: así se pueden renombrar en
+/// orden sin invalidar las rutas de las siguientes
+pub fn find(root: &Path, partial_dir: &str) -> Result<Vec<Collision>> {
+    let mut collisions = Vec::new();
+
+    for entry in WalkDir::new(root)
+        .follow_links(false)
+        .into_iter()
+        .filter_entry(|entry| entry.depth() == 0 || entry.file_name() != partial_dir)
+    {
+        let entry = entry.map_err(std::io::Error::from)?;
+        if !entry.file_type().is_dir() {
+            continue;
+        }
+
+        let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for child in std::fs::read_dir(entry.path())? {
+            let name = child?.file_name().to_string_lossy().into_owned();
+            groups.entry(name.to_lowercase()).or_default().push(name);
+        }
+        for (_, mut names) in groups.into_iter().filter(|(_, names)| names.len() > 1) {
+            names.sort();
+            collisions.push(Collision { dir: entry.path().to_path_buf(), names });
+        }
+    }
+
+    collisions.sort_by_key(|collision| std::cmp::Reverse(collision.dir.components().count()));
+    Ok(collisions)
+}
+
+/// Renombrados que resuelven una colisión según `policy`: el más reciente
+/// conserva su nombre y los demás reciben uno que no choca con nada del
+/// directorio (ni siquiera sin distinguir mayúsculas)
+pub fn renames(collision: &Collision, policy: CasePolicy) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut taken: HashSet<String> = HashSet::new();
+    for child in std::fs::read_dir(&collision.dir)? {
+        taken.insert(child?.file_name().to_string_lossy().to_lowercase());
+    }
+
+    let mut entries = Vec::new();
+    for name in &collision.names {
+        let path = collision.dir.join(name);
+        let modified = std::fs::symlink_metadata(&path)?.modified()?;
+        entries.push((name, path, modified));
+    }
+    // Más reciente primero; a igual fecha, por nombre
+    entries.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(b.0)));
+
+    let mut renames = Vec::new();
+    for (name, path, modified) in entries.into_iter().skip(1) {
+        let new_name = (1..)
+            .map(|n| renamed(name, policy, modified, n))
+            .find(|candidate| !taken.contains(&candidate.to_lowercase()))
+            .unwrap_or_default();
+        taken.insert(new_name.to_lowercase());
+        renames.push((path, collision.dir.join(new_name)));
+    }
+    Ok(renames)
+}
+
+/// `informe.txt` -> `informe (2).txt` o `informe (20240102-030405).txt`;
+/// `n` desambigua si el nombre ya existe
+fn renamed(name: &str, policy: CasePolicy, modified: SystemTime, n: u32) -> String {
+    let (stem, extension) = match name.rfind('.') {
+        Some(dot) if dot > 0 => name.split_at(dot),
+        _ => (name, ""),
+    };
+
+    let tag = match policy {
+        CasePolicy::Suffix => (n + 1).to_string(),
+        CasePolicy::Timestamp => {
+            let date = DateTime::<Local>::from(modified).format("%Y%m%d-%H%M%S");
+            if n == 1 { date.to_string() } else { format!("{}-{}", date, n) }
+        }
+    };
+    format!("{} ({}){}", stem, tag, extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_and_rename_case_collisions() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("Docs/.rsync-partial")).unwrap();
+        for name in ["Informe.txt", "informe.txt", "informe (2).txt", "otro.txt", "Docs/a", "Docs/A", "docs"] {
+            std::fs::write(root.join(name), b"x").unwrap();
+        }
+        for name in ["Docs/.rsync-partial/b", "Docs/.rsync-partial/B"] {
+            std::fs::write(root.join(name), b"x").unwrap();
+        }
+
+        let collisions = find(root, ".rsync-partial").unwrap();
+        assert_eq!(collisions.len(), 3);
+        // La más profunda primero
+        assert_eq!(collisions[0], Collision { dir: root.join("Docs"), names: vec!["A".into(), "a".into()] });
+        assert!(collisions.iter().any(|c| c.names == ["Docs", "docs"]));
+
+        // El más reciente conserva el nombre; el resto evita los ya usados
+        let old = SystemTime::now() - Duration::from_secs(3600);
+        std::fs::File::options().write(true).open(root.join("Informe.txt")).unwrap().set_modified(old).unwrap();
+        let informe = collisions.iter().find(|c| c.names[0] == "Informe.txt").unwrap();
+        assert_eq!(
+            renames(informe, CasePolicy::Suffix).unwrap(),
+            [(root.join("Informe.txt"), root.join("Informe (3).txt"))]
+        );
+
+        let date = DateTime::<Local>::from(old).format("%Y%m%d-%H%M%S").to_string();
+        assert_eq!(renamed("Informe.txt", CasePolicy::Timestamp, old, 1), format!("Informe ({}).txt", date));
+        assert_eq!(renamed(".bashrc", CasePolicy::Suffix, old, 1), ".bashrc (2)");
+    }
+}
@@ -0,0 +1,214 @@
+use crate::config::AppConfig;
+use crate::error::{AppError, Result};
+use std::path::PathBuf;
+
+/// Resultado de --check-config: rutas resueltas, elementos del host y los
+/// problemas encontrados (los errores hacen que termine con código distinto de 0)
+#[derive(Debug, Default)]
+pub struct ConfigReport {
+    pub host_section: Option<String>,
+    pub paths: Vec<(&'static str, PathBuf, bool)>,
+    pub items: Vec<(String, bool)>,
+    pub warnings: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+impl ConfigReport {
+    /// Revisa una configuración ya cargada (y por tanto expandida y validada)
+    pub fn build(config: &AppConfig) -> Self {
+        let mut report = Self::default();
+        let general = &config.general;
+        let crypto = &general.crypto;
+        let hostname = AppConfig::get_hostname();
+
+        report.host_section = [hostname.as_str(), "default"]
+            .into_iter()
+            .find(|name| config.hosts.contains_key(*name))
+            .map(str::to_string);
+        if report.host_section.is_none() {
+            report.errors.push(format!(
+                "No hay sección [hosts.{}] ni [hosts.default]",
+                hostname
+            ));
+        }
+
+        let mut paths: Vec<(&'static str, PathBuf)> = vec![("local_dir", config.local_dir())];
+        // Las rutas de un remoto SSH son del otro equipo
+        if config.ssh.is_none() {
+            paths.extend([
+                ("pcloud_mount_point", general.pcloud_mount_point.clone()),
+                ("pcloud_backup_comun", general.pcloud_backup_comun.clone()),
+                ("pcloud_backup_readonly", general.pcloud_backup_readonly.clone()),
+            ]);
+        }
+        paths.extend([
+            ("log_file", general.log_file.clone()),
+            ("history_file", general.history_file.clone()),
+            ("lock_file", general.lock_file.clone()),
+            ("two_way_state_file", general.two_way_state_file.clone()),
+            ("resume_state_file", general.resume_state_file.clone()),
+            ("crypto.local_crypto_dir", crypto.local_crypto_dir.clone()),
+            ("crypto.remote_crypto_dir", crypto.remote_crypto_dir.clone()),
+            ("crypto.local_keepass_dir", crypto.local_keepass_dir.clone()),
+            ("crypto.remote_keepass_dir", crypto.remote_keepass_dir.clone()),
+        ]);
+        if config.uses_alternate_crypto_paths() {
+            paths.push(("crypto.local_crypto_hostname_rtva_dir", crypto.local_crypto_hostname_rtva_dir.clone()));
+            paths.push(("crypto.remote_crypto_hostname_rtva_dir", crypto.remote_crypto_hostname_rtva_dir.clone()));
+        }
+        if let Some(exclude_from) = &general.exclude_from {
+            paths.push(("exclude_from", exclude_from.clone()));
+        }
+        if let Some(checksum_cache_dir) = &general.checksum_cache_dir {
+            paths.push(("checksum_cache_dir", checksum_cache_dir.clone()));
+        }
+        report.paths = paths
+            .into_iter()
+            .map(|(name, path)| {
+                let exists = path.exists();
+                (name, path, exists)
+            })
+            .collect();
+
+        // Los archivos de estado se crean al usarlos; lo que debe existir ya:
+        for (name, path, exists) in &report.paths {
+            let required = matches!(*name, "local_dir" | "pcloud_mount_point") || name.starts_with("crypto.");
+            if required && !exists {
+                report.warnings.push(format!("{} no existe: {}", name, path.display()));
+            }
+        }
+        for backup_dir in [&general.pcloud_backup_comun, &general.pcloud_backup_readonly] {
+            if config.ssh.is_none() && !backup_dir.starts_with(&general.pcloud_mount_point) {
+                report.warnings.push(format!(
+                    "{} no está dentro del punto de montaje {}",
+                    backup_dir.display(),
+                    general.pcloud_mount_point.display()
+                ));
+            }
+        }
+
+        if let Ok(host_config) = config.get_current_host_config() {
+            let local_dir = config.local_dir();
+            report.items = host_config
+                .sync_items
+                .iter()
+                .map(|item| (item.clone(), local_dir.join(item).exists()))
+                .collect();
+
+            let missing: Vec<&str> = report
+                .items
+                .iter()
+                .filter(|(_, exists)| !exists)
+                .map(|(item, _)| item.as_str())
+                .collect();
+            if !missing.is_empty() {
+                let message = format!("Elementos que no existen localmente: {}", missing.join(", "));
+                if general.strict_items {
+                    report.errors.push(format!("{} (strict_items)", message));
+                } else {
+                    report.warnings.push(message);
+                }
+            }
+        }
+
+        report
+    }
+
+    pub fn print(&self, config: &AppConfig) {
+        let mark = |exists: bool| if exists { "[OK]   " } else { "[FALTA]" };
+
+        println!("==========================================");
+        println!("COMPROBACIÓN DE LA CONFIGURACIÓN");
+        println!("==========================================");
+        println!("Archivo: {}", config.config_path.display());
+        println!(
+            "Host: {} (sección {})",
+            AppConfig::get_hostname(),
+            self.host_section
+                .as_deref()
+                .map(|name| format!("[hosts.{}]", name))
+                .unwrap_or_else(|| "ninguna".to_string())
+        );
+        if let Some(remote) = &config.remote_name {
+            match &config.ssh {
+                Some(ssh) => println!("Remoto: {} (SSH, {})", remote, ssh.destination()),
+                None => println!("Remoto: {}", remote),
+            }
+        }
+
+        println!("Rutas:");
+        for (name, path, exists) in &self.paths {
+            println!("  {} {}: {}", mark(*exists), name, path.display());
+        }
+
+        println!("Elementos ({}):", self.items.len());
+        for (item, exists) in &self.items {
+            println!("  {} {}", mark(*exists), item);
+        }
+
+        if !self.warnings.is_empty() {
+            println!("Advertencias:");
+            for warning in &self.warnings {
+                println!("  - {}", warning);
+            }
+        }
+        if !self.errors.is_empty() {
+            println!("Errores:");
+            for error in &self.errors {
+                println!("  - {}", error);
+            }
+        }
+        println!("==========================================");
+    }
+}
+
+/// --check-config: muestra el informe y falla si hay errores. Los errores de
+/// sintaxis o validación ya han hecho fallar la carga antes de llegar aquí.
+pub fn check_config(config: &AppConfig) -> Result<()> {
+    let report = ConfigReport::build(config);
+    report.print(config);
+
+    if report.errors.is_empty() {
+        println!("Configuración válida");
+        Ok(())
+    } else {
+        Err(AppError::Config(format!(
+            "{} errores en la configuración",
+            report.errors.len()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_config_with;
+    use tempfile::TempDir;
+
+    fn test_config(root: &std::path::Path, hosts: &str) -> AppConfig {
+        let mut config = test_config_with(root, hosts);
+        config.general.pcloud_backup_readonly = "/otro/sitio".into();
+        config
+    }
+
+    #[test]
+    fn test_report_flags_missing_paths_and_hosts() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::create_dir(temp_dir.path().join("Documentos")).unwrap();
+
+        let config = test_config(temp_dir.path(), "[hosts.default]\nsync_items = [\"Documentos\", \"Falta\"]\nexclusions = []");
+        let report = ConfigReport::build(&config);
+        assert_eq!(report.host_section.as_deref(), Some("default"));
+        assert!(report.errors.is_empty());
+        assert_eq!(report.items, vec![("Documentos".to_string(), true), ("Falta".to_string(), false)]);
+        assert!(report.paths.iter().any(|(name, _, exists)| *name == "pcloud_mount_point" && !exists));
+        assert!(report.warnings.iter().any(|w| w.contains("Falta")));
+        assert!(report.warnings.iter().any(|w| w.contains("/otro/sitio")));
+
+        let config = test_config(temp_dir.path(), "[hosts.otro_equipo]\nsync_items = []\nexclusions = []");
+        let report = ConfigReport::build(&config);
+        assert_eq!(report.host_section, None);
+        assert_eq!(report.errors.len(), 1);
+        assert!(check_config(&config).is_err());
+    }
+}
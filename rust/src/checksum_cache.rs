@@ -0,0 +1,211 @@
+use crate::cli::SyncMode;
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use walkdir::WalkDir;
+
+/// Huella de un archivo cuando se calculó su checksum
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CacheEntry {
+    size: u64,
+    mtime_secs: u64,
+    mtime_nanos: u32,
+    sha256: String,
+}
+
+/// Cache de checksums de un elemento en un modo concreto: un archivo JSON por
+/// (modo, elemento), de modo que invalidar un elemento es borrar su archivo.
+/// Las claves son "origen/<ruta>" y "destino/<ruta>".
+#[derive(Debug, Default)]
+pub struct ChecksumCache {
+    file: PathBuf,
+    entries: HashMap<String, CacheEntry>,
+}
+
+impl ChecksumCache {
+    /// Carga la cache; si falta o está corrupta se empieza vacía
+    pub fn load(cache_dir: &Path, mode: SyncMode, item: &str) -> Self {
+        let file = cache_file(cache_dir, mode, item);
+        let entries = std::fs::read_to_string(&file)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+
+        Self { file, entries }
+    }
+
+    pub fn invalidate(cache_dir: &Path, mode: SyncMode, item: &str) -> Result<()> {
+        match std::fs::remove_file(cache_file(cache_dir, mode, item)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Archivo temporal para la lista --files-from de este elemento
+    pub fn files_from_path(&self) -> PathBuf {
+        self.file.with_extension("files")
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.file.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let temp = self.file.with_extension("json.tmp");
+        std::fs::write(&temp, serde_json::to_vec(&self.entries).map_err(std::io::Error::from)?)?;
+        std::fs::rename(&temp, &self.file)?;
+        Ok(())
+    }
+
+    /// Checksum de `path`; solo se recalcula si cambió el tamaño o la fecha
+    pub fn checksum(&mut self, key: &str, path: &Path) -> Result<String> {
+        let (size, mtime_secs, mtime_nanos) = signature(path)?;
+
+        if let Some(entry) = self.entries.get(key) {
+            if (entry.size, entry.mtime_secs, entry.mtime_nanos) == (size, mtime_secs, mtime_nanos) {
+                return Ok(entry.sha256.clone());
+            }
+        }
+
+        let sha256 = sha256_file(path)?;
+        self.record(key, path, sha256.clone())?;
+        Ok(sha256)
+    }
+
+    /// Registra un checksum ya conocido (p. ej. el destino tras copiar el origen)
+    pub fn record(&mut self, key: &str, path: &Path, sha256: String) -> Result<()> {
+        let (size, mtime_secs, mtime_nanos) = signature(path)?;
+        self.entries.insert(
+            key.to_string(),
+            CacheEntry {
+                size,
+                mtime_secs,
+                mtime_nanos,
+                sha256,
+            },
+        );
+        Ok(())
+    }
+
+    /// Archivos de `source` (relativos) cuyo contenido difiere del de
+    /// `destination`. Solo se calcula checksum cuando tamaño y existencia no
+    /// bastan para decidir; en ese caso se devuelve también el del origen.
+    pub fn changed_files(&mut self, source: &Path, destination: &Path) -> Result<Vec<(PathBuf, Option<String>)>> {
+        let mut changed = Vec::new();
+
+        for entry in WalkDir::new(source).follow_links(false) {
+            let entry = entry.map_err(std::io::Error::from)?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let relative = entry.path().strip_prefix(source).unwrap_or(entry.path()).to_path_buf();
+            let target = destination.join(&relative);
+            let key = relative.to_string_lossy();
+
+            let source_size = entry.metadata().map_err(std::io::Error::from)?.len();
+            match std::fs::metadata(&target) {
+                Ok(metadata) if metadata.len() == source_size => {
+                    let source_sum = self.checksum(&format!("origen/{}", key), entry.path())?;
+                    if self.checksum(&format!("destino/{}", key), &target)? != source_sum {
+                        changed.push((relative, Some(source_sum)));
+                    }
+                }
+                _ => changed.push((relative, None)),
+            }
+        }
+
+        Ok(changed)
+    }
+}
+
+fn cache_file(cache_dir: &Path, mode: SyncMode, item: &str) -> PathBuf {
+    let mode = match mode {
+        SyncMode::Upload => "subir",
+        SyncMode::Download => "bajar",
+        SyncMode::TwoWay => "dos-vias",
+    };
+    let item: String = item
+        .trim_end_matches('/')
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+        .collect();
+
+    cache_dir.join(format!("{}-{}.json", mode, item))
+}
+
+fn signature(path: &Path) -> Result<(u64, u64, u32)> {
+    let metadata = std::fs::metadata(path)?;
+    let mtime = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok((metadata.len(), mtime.as_secs(), mtime.subsec_nanos()))
+}
+
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_changed_files_uses_and_persists_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let (source, destination, cache_dir) = (
+            temp_dir.path().join("origen"),
+            temp_dir.path().join("destino"),
+            temp_dir.path().join("cache"),
+        );
+        std::fs::create_dir_all(source.join("sub")).unwrap();
+        std::fs::create_dir_all(destination.join("sub")).unwrap();
+
+        std::fs::write(source.join("igual.txt"), "abc").unwrap();
+        std::fs::write(destination.join("igual.txt"), "abc").unwrap();
+        std::fs::write(source.join("sub/distinto.txt"), "abc").unwrap();
+        std::fs::write(destination.join("sub/distinto.txt"), "xyz").unwrap();
+        std::fs::write(source.join("nuevo.txt"), "nuevo").unwrap();
+
+        let mut cache = ChecksumCache::load(&cache_dir, SyncMode::Upload, "Docs");
+        let mut changed: Vec<PathBuf> = cache
+            .changed_files(&source, &destination)
+            .unwrap()
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        changed.sort();
+        assert_eq!(changed, vec![PathBuf::from("nuevo.txt"), PathBuf::from("sub/distinto.txt")]);
+        cache.save().unwrap();
+
+        let reloaded = ChecksumCache::load(&cache_dir, SyncMode::Upload, "Docs");
+        assert!(reloaded.entries.contains_key("destino/igual.txt"));
+        assert!(ChecksumCache::load(&cache_dir, SyncMode::Download, "Docs").entries.is_empty());
+
+        ChecksumCache::invalidate(&cache_dir, SyncMode::Upload, "Docs").unwrap();
+        assert!(ChecksumCache::load(&cache_dir, SyncMode::Upload, "Docs").entries.is_empty());
+    }
+}
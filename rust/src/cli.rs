@@ -1,4 +1,8 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Parser, Debug, Clone, Default)] // Añadido Clone y Default
 #[command(
@@ -11,27 +15,107 @@ Script avanzado de sincronización bidireccional entre directorio local y pCloud
 con capacidades de backup, manejo de enlaces simbólicos y sistema de logging.
 
 Ejemplos de uso:
+  syncb --mode upload  # Equivale a --subir (también download y two-way)
   syncb --subir
   syncb --bajar --dry-run
   syncb --subir --delete --yes
+  syncb --subir --delete-excluded  # Borra también en pCloud lo que ahora se excluye
   syncb --subir --item documentos/
+  syncb --subir --items-from elementos.txt  # Un elemento por línea
+  syncb --subir --no-hidden --include .config  # Sin ocultos salvo .config
+  syncb --subir --yes --resume-items  # Tras una caída, no repite los elementos ya terminados
+  syncb --prune-older-than 90d --dry-run  # Qué se borraría del backup de solo lectura
+  syncb --subir --safe-links  # Sin enlaces que salgan del elemento (--copy-unsafe-links los copia)
+  syncb --subir --exclude-vcs  # Sin .git, .svn, .hg, CVS... (se conserva .gitignore)
+  syncb --check-config  # Validar la configuración sin sincronizar
+  syncb --repair-symlinks --dry-run  # Qué enlaces simbólicos se recrearían, sin transferir nada
+  syncb --bajar --compare-dest ~/pCloudDrive/pCloud\ Backup/equipo  # No bajar lo que ya está en la referencia
   syncb --bajar --item configuracion.ini --item .local/bin --dry-run
   syncb --bajar --backup-dir --item documentos/ --yes
   syncb --subir --exclude '*.tmp' --exclude 'temp/'
+  syncb --subir --exclude-from ~/.rsync-filter
+  syncb --subir --verify        # Verificar por checksum tras sincronizar
+  syncb --subir --manifest      # Guardar SHA256SUMS de cada elemento en pCloud (y verificar al bajar)
   syncb --subir --overwrite     # Sobrescribe todos los archivos
   syncb --subir --bwlimit 1000  # Sincronizar subiendo con límite de 1MB/s
+  syncb --subir --bwlimit 2M    # Igual, con sufijo de unidad (K/M/G)
+  syncb --subir --verify-mount-writable  # Fallar pronto si pCloud no admite escritura
+  syncb --subir --ignore-file .gitignore  # Respetar los .gitignore del origen
+  syncb --subir --force-checksum-on '*.kdbx' --force-checksum-on '*.sqlite'
+  syncb --subir --bwlimit-schedule  # Límite según la hora (bwlimit_schedule)
+  syncb --subir --progress-total  # Porcentaje global (requiere una pasada previa)
+  syncb --subir --confirm-each  # Revisar y decidir elemento a elemento
+  syncb --subir --plan-out plan.json  # Revisar qué se ejecutaría
+  syncb --plan-in plan.json --yes     # Ejecutar exactamente ese plan
+  syncb --subir --remote trabajo  # Usar otra cuenta de pCloud de [[remotes]]
+  syncb --subir --remote nas      # Remoto type = "ssh": rsync por SSH, sin montaje
+  syncb --subir --filter '+ /Fotos/favoritas/' --filter '- /Fotos/*/'  # Reglas de rsync tal cual
+  syncb --bajar --dry-run-delete-only  # Qué borraría --delete, sin hacer nada más
+  syncb --subir --compress --compress-level 6 --skip-compress jpg/mp4/zip
+  syncb --subir --max-filesize 100M  # No subir archivos de más de 100MB
+  syncb --subir --backup-dir --symlink-policy follow  # Copiar el destino de los enlaces
+  syncb --subir --resolve-case  # Renombrar "a.txt"/"A.txt" antes de que choquen en pCloud
   syncb --subir --verbose       # Sincronizar con output verboso
   syncb --bajar --item Documentos/ --timeout 10  # Timeout corto de 10 minutos
+  syncb --profile nightly --bwlimit 500  # Opciones de [profiles.nightly]; la línea de comandos manda
+  syncb --subir --yes --max-runtime 2h  # Cortar a las 2 horas aunque queden elementos
   syncb --force-unlock   # Forzar desbloqueo si hay un lock obsoleto
-  syncb --crypto         # Incluir directorio Crypto de la sincronización
-"#
+  syncb --init-config    # Crear una configuración inicial (primer uso)
+  syncb --bajar --crypto  # Incluir directorio Crypto de la sincronización
+  syncb --subir --crypto --no-crypto-mount-check  # Primera sincronización de Crypto
+  syncb --subir --parallel 4  # Sincronizar hasta 4 elementos a la vez
+  syncb --subir --config ~/syncb/trabajo.toml  # Usar un archivo de configuración concreto
+  syncb --subir --resume  # Continuar transferencias interrumpidas
+  syncb --status         # Estado de montaje, lock y última ejecución
+  syncb --history 20     # Mostrar las últimas 20 ejecuciones
+  syncb --subir --checksum --rebuild-cache  # Recalcular la cache de checksums
+  syncb --subir --backup-dir --snapshot  # Instantánea fechada en el backup de solo lectura
+  syncb --two-way --dry-run  # Bidireccional: ver qué viajaría en cada sentido
+  syncb --two-way --conflict-strategy newer  # Conflictos: gana la copia más reciente
+  syncb --subir --skip-item Videos  # Todo excepto Videos
+  syncb --subir --list-only  # Mostrar qué se sincronizaría sin ejecutar rsync
+  syncb --subir --dry-run --verbose  # Informe de cambios por elemento con cada ruta
+  syncb --subir --yes --json  # Resumen final en JSON
+  syncb --subir --yes --watch 30m  # Demonio: sincroniza cada 30 minutos hasta Ctrl+C
+  syncb --subir --yes --watch-fs   # Sube 5s después de que se calmen los cambios locales
+  syncb --subir --yes --stats-file /var/lib/node_exporter/syncb.prom  # Métricas para monitorización
+  syncb --subir --yes --log-level warn  # Solo avisos y errores (cron)
+
+Códigos de salida:
+  0    Éxito
+  1    Error genérico
+  2    Error de configuración
+  10   Ya hay otra ejecución en curso (lock)
+  11   pCloud no montado
+  12   Tiempo límite excedido
+  13   Espacio en disco insuficiente
+  14   Errores de transferencia o verificación
+  15   Falta una dependencia (rsync, curl)
+  16   Destino sin permiso de escritura
+  130  Cancelado por el usuario
+"#,
+    // Con --profile, las opciones explícitas repiten las del perfil y deben prevalecer
+    args_override_self = true
 )]
 pub struct Cli {
-    /// Modo principal: subir desde local a pCloud
+    /// Archivo de configuración a usar (en lugar de buscarlo)
+    #[arg(long, value_name = "RUTA")]
+    pub config: Option<PathBuf>,
+
+    /// Aplica las opciones de `[profiles.NOMBRE]` de la configuración; las
+    /// indicadas en la línea de comandos prevalecen
+    #[arg(long, value_name = "NOMBRE")]
+    pub profile: Option<String>,
+
+    /// Modo de sincronización (forma preferida de --subir, --bajar y --two-way)
+    #[arg(long, value_enum, value_name = "MODO")]
+    pub mode: Option<SyncMode>,
+
+    /// Subir desde local a pCloud (obsoleto: equivale a --mode upload)
     #[arg(long)]
     pub subir: bool,
 
-    /// Modo principal: bajar desde pCloud a local
+    /// Bajar desde pCloud a local (obsoleto: equivale a --mode download)
     #[arg(long)]
     pub bajar: bool,
 
@@ -39,7 +123,21 @@ pub struct Cli {
     #[arg(long)]
     pub delete: bool,
 
-    /// Simula la operación sin hacer cambios reales
+    /// Implica --delete y además borra en destino lo que ahora está excluido
+    /// (rsync --delete-excluded); pasa por la misma confirmación de borrados
+    #[arg(long, conflicts_with = "two_way")]
+    pub delete_excluded: bool,
+
+    /// Cuándo borra --delete (por defecto, `delete_policy` de la configuración):
+    /// delay (al final, decidido durante la copia), after (al final; lo más
+    /// seguro si se interrumpe, pero necesita más espacio), before (antes de
+    /// copiar; libera espacio primero, pero un corte deja el destino sin esos
+    /// archivos y sin los nuevos) o during (sobre la marcha)
+    #[arg(long, value_enum, value_name = "MOMENTO", requires = "delete")]
+    pub delete_policy: Option<DeletePolicy>,
+
+    /// Simula la operación sin hacer cambios reales. Si pCloud no está montado
+    /// o falta espacio solo se advierte: una simulación correcta no garantiza la ejecución real
     #[arg(long)]
     pub dry_run: bool,
 
@@ -47,6 +145,14 @@ pub struct Cli {
     #[arg(long, value_name = "ELEMENTO")]
     pub item: Option<Vec<String>>,
 
+    /// Lee elementos de un archivo (uno por línea; se ignoran vacías y comentarios #)
+    #[arg(long, value_name = "ARCHIVO", value_parser = parse_existing_file)]
+    pub items_from: Option<PathBuf>,
+
+    /// Omite un elemento de la lista a sincronizar (repetible)
+    #[arg(long, value_name = "ELEMENTO")]
+    pub skip_item: Vec<String>,
+
     /// No pregunta confirmación, ejecuta directamente
     #[arg(long)]
     pub yes: bool,
@@ -55,10 +161,47 @@ pub struct Cli {
     #[arg(long)]
     pub backup_dir: bool,
 
+    /// Con --subir --backup-dir crea una instantánea fechada enlazando (--link-dest) con la anterior
+    #[arg(long)]
+    pub snapshot: bool,
+
+    /// Con --bajar, no transfiere lo que ya está igual en este árbol de referencia
+    /// (rsync --compare-dest), p. ej. el backup de solo lectura al preparar un equipo nuevo
+    #[arg(long, value_name = "RUTA", value_parser = parse_existing_dir)]
+    pub compare_dest: Option<PathBuf>,
+
     /// Excluye archivos que coincidan con el patrón
     #[arg(long, value_name = "PATRON")]
     pub exclude: Vec<String>,
 
+    /// Incluye archivos que coincidan con el patrón aunque otra regla los excluya
+    /// (p. ej. `--no-hidden --include .config`)
+    #[arg(long, value_name = "PATRON")]
+    pub include: Vec<String>,
+
+    /// Regla de filtro de rsync tal cual (`+ /conservar`, `- *.tmp`,
+    /// `merge ~/.rsync-rules`), repetible. Van en orden y antes que las
+    /// `filters` del host y que --include/--exclude: gana la primera que coincide
+    #[arg(long, value_name = "REGLA", value_parser = parse_filter_rule)]
+    pub filter: Vec<String>,
+
+    /// Excluye los metadatos de control de versiones (.git, .svn, .hg, CVS...);
+    /// `.gitignore` y demás archivos de configuración del proyecto se conservan
+    #[arg(long)]
+    pub exclude_vcs: bool,
+
+    /// Excluye todos los archivos y directorios ocultos (que empiezan por '.')
+    #[arg(long, conflicts_with = "only_hidden")]
+    pub no_hidden: bool,
+
+    /// Sincroniza solo los archivos y directorios ocultos (copia de configuración)
+    #[arg(long)]
+    pub only_hidden: bool,
+
+    /// Lee patrones de exclusión de un archivo (rsync --exclude-from)
+    #[arg(long, value_name = "ARCHIVO")]
+    pub exclude_from: Option<PathBuf>,
+
     /// Sobrescribe todos los archivos en destino (no usa --update)
     #[arg(long)]
     pub overwrite: bool,
@@ -67,14 +210,132 @@ pub struct Cli {
     #[arg(long)]
     pub checksum: bool,
 
-    /// Limita la velocidad de transferencia (ej: 1000 para 1MB/s)
-    #[arg(long, value_name = "KB/s")]
+    /// Algoritmo de las comparaciones por checksum (rsync --checksum-choice,
+    /// p. ej. xxh64, que es mucho más rápido que md5). Si el rsync instalado
+    /// no lo admite se avisa y se usa el suyo por defecto
+    #[arg(long, value_name = "ALGORITMO")]
+    pub checksum_choice: Option<String>,
+
+    /// Pasa --prune-empty-dirs a rsync y elimina después los directorios del
+    /// destino que su borrado dejó vacíos (no con un remoto SSH)
+    #[arg(long)]
+    pub prune_empty_dirs: bool,
+
+    /// Estima el total a transferir con una pasada previa y registra el porcentaje global
+    #[arg(long)]
+    pub progress_total: bool,
+
+    /// Descarta la cache de checksums de los elementos y recalcula todo
+    #[arg(long)]
+    pub rebuild_cache: bool,
+
+    /// Al subir, guarda en pCloud un manifiesto SHA256SUMS de cada elemento
+    /// (en `.syncb_manifests/`); al bajar, verifica con él los archivos recibidos
+    #[arg(long)]
+    pub manifest: bool,
+
+    /// Tras cada elemento, verifica por checksum que no quedan diferencias
+    /// (distinto de --checksum, que afecta a la propia transferencia)
+    #[arg(long, alias = "checksum-after")]
+    pub verify: bool,
+
+    /// Respeta los archivos con este nombre en cada directorio del origen, con sintaxis .gitignore (repetible)
+    #[arg(long, value_name = "NOMBRE")]
+    pub ignore_file: Vec<String>,
+
+    /// Compara por checksum solo los archivos que coinciden con el patrón (segunda pasada; repetible)
+    #[arg(long, value_name = "PATRÓN")]
+    pub force_checksum_on: Vec<String>,
+
+    /// Limita la velocidad de transferencia en KB/s (elementos y KeePass); admite sufijos K/M/G (ej: 500K, 2M, 1.5G)
+    #[arg(long, value_name = "KB/s", value_parser = parse_bwlimit)]
     pub bwlimit: Option<u32>,
 
+    /// Guarda en JSON el plan resuelto (rutas y comandos rsync exactos) sin ejecutar
+    #[arg(long, value_name = "ARCHIVO", conflicts_with = "two_way")]
+    pub plan_out: Option<PathBuf>,
+
+    /// Ejecuta tal cual un plan guardado con --plan-out (sin resolver elementos ni opciones)
+    #[arg(long, value_name = "ARCHIVO", conflicts_with_all = ["plan_out", "two_way"])]
+    pub plan_in: Option<PathBuf>,
+
+    /// Si la última ejecución con los mismos parámetros se interrumpió hace
+    /// poco (`resume_max_age_hours`), omite los elementos que ya completó
+    #[arg(long)]
+    pub resume_items: bool,
+
+    /// Muestra la simulación de cada elemento y pregunta si sincronizarlo, omitirlo o abortar
+    #[arg(long, conflicts_with_all = ["yes", "json"])]
+    pub confirm_each: bool,
+
+    /// Remoto de `[[remotes]]` a usar (por defecto, `default_remote` o el primero)
+    #[arg(long, value_name = "NOMBRE")]
+    pub remote: Option<String>,
+
+    /// Informe de solo lectura de lo que difiere (checksum, tamaño o fecha) o
+    /// solo existe en un lado, por elemento; sale con error si hay diferencias
+    #[arg(long, conflicts_with_all = ["two_way", "delete", "snapshot", "plan_out", "plan_in", "dry_run_delete_only"])]
+    pub compare_checksums: bool,
+
+    /// Solo lista lo que borraría --delete (simulación, sin transferir); sale con error si hay borrados
+    #[arg(long, conflicts_with = "two_way")]
+    pub dry_run_delete_only: bool,
+
+    /// Antes de sincronizar, muestra lo que borraría --delete con una pasada
+    /// de simulación aparte; no activa --delete en esta ejecución
+    #[arg(long, conflicts_with_all = ["two_way", "dry_run_delete_only"])]
+    pub preview_deletions: bool,
+
+    /// Comprime los datos durante la transferencia (rsync -z)
+    #[arg(long)]
+    pub compress: bool,
+
+    /// Nivel de compresión 0-9 (rsync --compress-level); implica --compress
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(0..=9))]
+    pub compress_level: Option<u32>,
+
+    /// Sufijos que no se comprimen, separados por '/' (ej: jpg/mp4/zip; rsync --skip-compress)
+    #[arg(long, value_name = "LISTA")]
+    pub skip_compress: Option<String>,
+
+    /// Toma --bwlimit de la franja de `bwlimit_schedule` vigente al empezar
+    #[arg(long, conflicts_with = "bwlimit")]
+    pub bwlimit_schedule: bool,
+
+    /// No transfiere archivos mayores que este tamaño (bytes o K/M/G; rsync --max-size)
+    #[arg(long, value_name = "TAMAÑO", value_parser = parse_size)]
+    pub max_filesize: Option<u64>,
+
+    /// No transfiere archivos menores que este tamaño (bytes o K/M/G; rsync --min-size)
+    #[arg(long, value_name = "TAMAÑO", value_parser = parse_size)]
+    pub min_filesize: Option<u64>,
+
+    /// Si no cabe todo, en vez de abortar omite los archivos mayores que el
+    /// espacio libre del destino (menos la reserva) y sigue con el resto; al
+    /// final se listan los omitidos
+    #[arg(long)]
+    pub exclude_larger_than_free: bool,
+
     /// Límite de tiempo por operación (default: 30 minutos)
     #[arg(long, value_name = "MINUTOS")]
     pub timeout: Option<u32>,
 
+    /// Tiempo máximo de toda la sincronización ("2h", "45m"; sin unidad,
+    /// minutos). Al agotarse detiene el rsync en curso, muestra el resumen
+    /// parcial y termina con el código de tiempo límite excedido
+    #[arg(long, value_name = "DURACIÓN", value_parser = parse_interval, conflicts_with_all = ["watch", "watch_fs"])]
+    pub max_runtime: Option<Duration>,
+
+    /// Conserva las transferencias interrumpidas para continuarlas en la siguiente
+    /// ejecución (rsync --partial --partial-dir). Si rsync termina con un error de
+    /// transferencia (códigos 20, 23, 30...) basta relanzar con --resume
+    #[arg(long)]
+    pub resume: bool,
+
+    /// Número de elementos a sincronizar en paralelo (--bwlimit se reparte entre ellos)
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub parallel: usize,
+
     /// Forzar eliminación de lock
     #[arg(long)]
     pub force_unlock: bool,
@@ -83,30 +344,290 @@ pub struct Cli {
     #[arg(long)]
     pub crypto: bool,
 
+    /// Solo sincroniza Crypto (y KeePass) en el sentido elegido, sin los
+    /// elementos ni el manifiesto de enlaces simbólicos
+    #[arg(
+        long,
+        conflicts_with_all = ["two_way", "snapshot", "delete", "confirm_each", "plan_out", "plan_in", "dry_run_delete_only"]
+    )]
+    pub crypto_only: bool,
+
+    /// Sincroniza Crypto aunque falte el archivo de verificación de montaje (p. ej. la primera vez)
+    #[arg(long)]
+    pub no_crypto_mount_check: bool,
+
+    /// Antes de subir, comprueba que se puede escribir en pCloud (montaje de solo lectura, sesión caducada)
+    #[arg(long)]
+    pub verify_mount_writable: bool,
+
+    /// Omite la comprobación de conectividad (útil sin red)
+    #[arg(long)]
+    pub skip_connectivity_check: bool,
+
+    /// Convierte en advertencias los errores de validación de rutas de la configuración
+    #[arg(long)]
+    pub lenient: bool,
+
+    /// Cómo trata rsync los enlaces simbólicos (por defecto, `symlink_policy` de la configuración)
+    #[arg(long, value_enum, value_name = "POLÍTICA")]
+    pub symlink_policy: Option<SymlinkPolicy>,
+
+    /// Ignora los enlaces que apuntan fuera del elemento sincronizado (rsync --safe-links)
+    #[arg(long, conflicts_with = "copy_unsafe_links")]
+    pub safe_links: bool,
+
+    /// Copia el contenido de los enlaces que apuntan fuera del elemento
+    /// sincronizado en lugar del enlace (rsync --copy-unsafe-links)
+    #[arg(long)]
+    pub copy_unsafe_links: bool,
+
+    /// Avisa de nombres que solo difieren en mayúsculas/minúsculas en el
+    /// origen (se pisarían en pCloud o macOS); se cuentan en el resumen
+    #[arg(long)]
+    pub check_case: bool,
+
+    /// Como --check-case, pero además renombra las colisiones según
+    /// `case_policy` de la configuración (el más reciente conserva su nombre)
+    #[arg(long)]
+    pub resolve_case: bool,
+
+    /// Muestra el resumen final en JSON (incluye el desglose por elemento)
+    #[arg(long)]
+    pub json: bool,
+
+    /// Modo demonio: sincroniza cada INTERVALO ("30m", "1h"; un número solo son minutos)
+    /// hasta recibir SIGINT/SIGTERM, recargando la configuración si cambia
+    #[arg(
+        long,
+        value_name = "INTERVALO",
+        value_parser = parse_interval,
+        requires = "yes",
+        conflicts_with_all = ["plan_out", "plan_in", "list_only", "confirm_each", "dry_run_delete_only"]
+    )]
+    pub watch: Option<Duration>,
+
+    /// Sube automáticamente cuando los cambios locales se calman durante ESPERA
+    /// (por defecto 5s); vigila con inotify los elementos y respeta las exclusiones
+    #[arg(
+        long,
+        value_name = "ESPERA",
+        num_args = 0..=1,
+        default_missing_value = "5s",
+        value_parser = humantime::parse_duration,
+        requires = "yes",
+        conflicts_with_all = ["watch", "plan_out", "plan_in", "list_only", "confirm_each", "dry_run_delete_only"]
+    )]
+    pub watch_fs: Option<Duration>,
+
+    /// Escribe las estadísticas finales en formato textfile de Prometheus (escritura atómica)
+    #[arg(long, value_name = "RUTA")]
+    pub stats_file: Option<PathBuf>,
+
+    /// Sincronización bidireccional: cada archivo viaja en el sentido del lado
+    /// más reciente (equivale a --mode two-way)
+    #[arg(long, conflicts_with_all = ["subir", "bajar", "delete", "snapshot"])]
+    pub two_way: bool,
+
+    /// Qué hacer en --two-way con archivos cambiados en ambos lados desde la última vez
+    #[arg(long, value_enum, default_value_t, value_name = "ESTRATEGIA")]
+    pub conflict_strategy: ConflictStrategy,
+
     /// Habilita modo verboso para debugging
     #[arg(long)]
     pub verbose: bool,
 
+    /// Registra cada invocación de rsync tal cual, lista para pegar en una
+    /// shell (sin esta opción solo se ven con el nivel de log trace)
+    #[arg(long)]
+    pub trace_commands: bool,
+
+    /// Log sin colores en la terminal (también con la variable NO_COLOR o si
+    /// la salida no es una terminal)
+    #[arg(long)]
+    pub no_color: bool,
+
+    /// Nivel de log explícito; tiene prioridad sobre --verbose y el nivel por host
+    #[arg(long, value_enum, value_name = "NIVEL")]
+    pub log_level: Option<LogLevel>,
+
+    /// Carga y valida la configuración, muestra las rutas resueltas y si existen, y termina
+    #[arg(long)]
+    pub check_config: bool,
+
+    /// Crea una configuración inicial preguntando lo imprescindible (con
+    /// --yes, valores por defecto y los elementos de --item) y termina. Se
+    /// escribe en --config o en el directorio de configuración del usuario
+    #[arg(long, conflicts_with_all = ["check_config", "profile"])]
+    pub init_config: bool,
+
+    /// Con --init-config, sobrescribe la configuración si ya existe
+    #[arg(long, requires = "init_config")]
+    pub force: bool,
+
+    /// Solo recrea los enlaces simbólicos del manifiesto guardado en pCloud, sin transferir archivos
+    #[arg(long, conflicts_with_all = ["subir", "two_way", "watch", "watch_fs", "plan_out", "plan_in", "list_only"])]
+    pub repair_symlinks: bool,
+
+    /// Borra del backup de solo lectura las instantáneas (--snapshot) con más
+    /// de EDAD ("90d", "6months"; un número sin unidad son días). Conserva
+    /// siempre la última y nunca toca la copia vigente; pide confirmación
+    /// salvo con --yes
+    #[arg(
+        long,
+        value_name = "EDAD",
+        value_parser = parse_age,
+        conflicts_with_all = ["mode", "subir", "bajar", "two_way", "repair_symlinks", "watch", "watch_fs", "plan_out", "plan_in", "list_only"]
+    )]
+    pub prune_older_than: Option<Duration>,
+
+    /// Muestra el estado (montaje de pCloud, lock, espacio libre y última ejecución) sin sincronizar
+    #[arg(long)]
+    pub status: bool,
+
+    /// Muestra las últimas N ejecuciones registradas (por defecto 10) sin sincronizar
+    #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "10")]
+    pub history: Option<usize>,
+
+    /// Muestra los elementos que se sincronizarían (origen -> destino) sin ejecutar rsync
+    #[arg(long)]
+    pub list_only: bool,
+
     /// Items específicos para sincronizar (alias de --item)
     #[arg(last = true)]
     pub items: Option<Vec<String>>,
 }
 
 impl Cli {
+    /// Reglas que clap no expresa: sin un modo explícito solo se admiten las
+    /// acciones que no sincronizan (o que fijan el sentido por sí mismas)
     pub fn validate(&self) -> Result<(), String> {
         if self.subir && self.bajar {
             return Err("No puedes usar --subir y --bajar simultáneamente".to_string());
         }
 
-        if !self.subir && !self.bajar && !self.force_unlock {
+        if !self.subir
+            && !self.bajar
+            && !self.two_way
+            && self.mode.is_none()
+            && !self.force_unlock
+            && !self.repair_symlinks
+            && !self.check_config
+            && !self.compare_checksums
+            && !self.init_config
+            && !self.status
+            && self.history.is_none()
+            && self.plan_in.is_none()
+            && self.prune_older_than.is_none()
+        {
             return Err("Debes especificar --subir o --bajar".to_string());
         }
 
+        if self.no_crypto_mount_check && !self.crypto && !self.crypto_only {
+            return Err("--no-crypto-mount-check requiere --crypto o --crypto-only".to_string());
+        }
+
         Ok(())
     }
 
+    /// Elementos indicados en línea de comandos (--item, --items-from y
+    /// argumentos finales), sin duplicados y en el orden en que aparecen
+    pub fn selected_items(&self) -> crate::error::Result<Option<Vec<String>>> {
+        let from_file = match &self.items_from {
+            Some(path) => crate::links::leer_lista_elementos(path)?,
+            None => Vec::new(),
+        };
+
+        let mut items: Vec<String> = Vec::new();
+        for item in self.item.iter().flatten().chain(&from_file).chain(self.items.iter().flatten()) {
+            if !items.contains(item) {
+                items.push(item.clone());
+            }
+        }
+
+        if items.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(items))
+        }
+    }
+
+    /// Vuelve a analizar la línea de comandos `argv` (programa incluido) con
+    /// los argumentos de un perfil delante, de modo que los explícitos
+    /// (después) prevalecen
+    pub fn with_profile<I, T>(argv: I, profile_args: &[String]) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString>,
+    {
+        let mut argv: Vec<std::ffi::OsString> = argv.into_iter().map(Into::into).collect();
+        let at = argv.len().min(1);
+        argv.splice(at..at, profile_args.iter().map(std::ffi::OsString::from));
+        Self::try_parse_from(argv)
+    }
+
+    /// Unifica --mode con los alias --subir/--bajar/--two-way: rechaza
+    /// combinaciones contradictorias y deja marcado el indicador equivalente
+    /// (el resto del código y las restricciones de clap los consultan). Aquí
+    /// se comprueban también las opciones que dependen del modo, ya que clap
+    /// no ve el valor de --mode.
+    pub fn resolve_mode(&mut self) -> Result<(), String> {
+        let from_flags = if self.two_way {
+            Some(SyncMode::TwoWay)
+        } else if self.subir {
+            Some(SyncMode::Upload)
+        } else if self.bajar {
+            Some(SyncMode::Download)
+        } else {
+            None
+        };
+
+        if let (Some(mode), Some(flags)) = (self.mode, from_flags) {
+            if mode != flags {
+                return Err(format!("--mode {} contradice {}", mode.name(), flags.flag()));
+            }
+        }
+        self.mode = self.mode.or(from_flags);
+        if let Some(mode) = self.mode {
+            self.subir = mode == SyncMode::Upload;
+            self.bajar = mode == SyncMode::Download;
+            self.two_way = mode == SyncMode::TwoWay;
+        }
+
+        let mode = self.get_mode();
+        let incompatible = match mode {
+            SyncMode::Upload => vec![
+                ("--compare-dest", self.compare_dest.is_some()),
+                ("--repair-symlinks", self.repair_symlinks),
+            ],
+            SyncMode::Download => vec![("--watch-fs", self.watch_fs.is_some())],
+            SyncMode::TwoWay => vec![
+                ("--delete", self.delete),
+                ("--delete-excluded", self.delete_excluded),
+                ("--snapshot", self.snapshot),
+                ("--plan-out", self.plan_out.is_some()),
+                ("--plan-in", self.plan_in.is_some()),
+                ("--dry-run-delete-only", self.dry_run_delete_only),
+                ("--preview-deletions", self.preview_deletions),
+                ("--compare-dest", self.compare_dest.is_some()),
+                ("--watch-fs", self.watch_fs.is_some()),
+                ("--repair-symlinks", self.repair_symlinks),
+                ("--manifest", self.manifest),
+            ],
+        };
+        match incompatible.iter().find(|(_, set)| *set) {
+            Some((option, _)) => Err(format!("{} no se admite con --mode {}", option, mode.name())),
+            None => Ok(()),
+        }
+    }
+
     pub fn get_mode(&self) -> SyncMode {
-        if self.subir {
+        if let Some(mode) = self.mode {
+            return mode;
+        }
+
+        if self.two_way {
+            SyncMode::TwoWay
+        } else if self.subir {
             SyncMode::Upload
         } else {
             SyncMode::Download
@@ -120,12 +641,129 @@ impl Cli {
             BackupDirMode::Common
         }
     }
+
+    /// Argumentos de --bwlimit para cada uno de `concurrent` procesos rsync
+    /// simultáneos (el límite se reparte). Toda transferencia debe usarlos.
+    pub fn rsync_bwlimit_args(&self, concurrent: usize) -> Vec<String> {
+        match self.bwlimit {
+            Some(bwlimit) => {
+                let per_process = (bwlimit / concurrent.max(1) as u32).max(1);
+                vec!["--bwlimit".to_string(), per_process.to_string()]
+            }
+            None => Vec::new(),
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
 pub enum SyncMode {
     Upload,
     Download,
+    TwoWay,
+}
+
+impl SyncMode {
+    /// Valor de --mode
+    pub fn name(self) -> String {
+        self.to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Alias histórico equivalente
+    fn flag(self) -> &'static str {
+        match self {
+            SyncMode::Upload => "--subir",
+            SyncMode::Download => "--bajar",
+            SyncMode::TwoWay => "--two-way",
+        }
+    }
+}
+
+/// Tratamiento de enlaces simbólicos dentro de rsync (independiente del
+/// manifiesto de enlaces de `links.rs`)
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymlinkPolicy {
+    /// Copia los enlaces como enlaces (--links)
+    #[default]
+    Preserve,
+    /// Copia el contenido al que apuntan (--copy-links)
+    Follow,
+    /// No transfiere enlaces (--no-links)
+    Skip,
+}
+
+/// Nombre que recibe cada archivo en colisión con --resolve-case
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CasePolicy {
+    /// Número entre paréntesis: `informe (2).txt`
+    #[default]
+    Suffix,
+    /// Fecha de modificación: `informe (20240102-030405).txt`
+    Timestamp,
+}
+
+/// Momento en que rsync borra con --delete
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeletePolicy {
+    /// Antes de transferir (--delete-before)
+    Before,
+    /// Mientras transfiere (--delete-during)
+    During,
+    /// Después de transferir (--delete-after)
+    After,
+    /// Decide durante la transferencia y borra al final (--delete-delay)
+    #[default]
+    Delay,
+}
+
+impl DeletePolicy {
+    pub fn flag(self) -> &'static str {
+        match self {
+            DeletePolicy::Before => "--delete-before",
+            DeletePolicy::During => "--delete-during",
+            DeletePolicy::After => "--delete-after",
+            DeletePolicy::Delay => "--delete-delay",
+        }
+    }
+}
+
+/// Resolución de conflictos en --two-way
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum ConflictStrategy {
+    /// No copiar y avisar (se vuelve a detectar en la siguiente ejecución)
+    #[default]
+    Skip,
+    /// Gana la copia con fecha de modificación más reciente
+    Newer,
+    /// Gana la copia local
+    Local,
+    /// Gana la copia de pCloud
+    Remote,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Trace => LevelFilter::Trace,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Error => LevelFilter::Error,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -133,3 +771,322 @@ pub enum BackupDirMode {
     Common,
     ReadOnly,
 }
+
+/// Separa un valor como "1.5G" en número y potencia de 1024 del sufijo (K=1, M=2, G=3)
+fn split_unit_suffix(value: &str) -> Result<(f64, Option<u32>), String> {
+    let value = value.trim();
+    let (number, power) = match value.chars().last().map(|c| c.to_ascii_uppercase()) {
+        Some('K') => (&value[..value.len() - 1], Some(1)),
+        Some('M') => (&value[..value.len() - 1], Some(2)),
+        Some('G') => (&value[..value.len() - 1], Some(3)),
+        _ => (value, None),
+    };
+
+    let number: f64 = number
+        .trim()
+        .parse()
+        .map_err(|_| format!("valor no válido: '{}' (ejemplos: 500K, 2M, 1.5G)", value))?;
+
+    if !number.is_finite() || number < 0.0 {
+        return Err(format!("valor no válido: '{}'", value));
+    }
+
+    Ok((number, power))
+}
+
+/// Comprueba la forma de una regla de filtro de rsync: nombre corto (`+`,
+/// `-`, `P`, `R`, `H`, `S`, `.`, `:`, `!`) o largo (`include`, `merge`...),
+/// modificadores opcionales y el patrón o archivo, salvo en `clear`/`!`.
+/// Lo demás (la sintaxis del patrón) lo decide rsync.
+pub fn parse_filter_rule(value: &str) -> Result<String, String> {
+    const LONG_NAMES: &[&str] = &[
+        "include", "exclude", "show", "hide", "protect", "risk", "merge", "dir-merge", "clear",
+    ];
+    const SHORT_NAMES: &str = "+-PRHS.:!";
+    const MODIFIERS: &str = "/!Csrpxenw+-";
+
+    // Ni el nombre ni los modificadores llevan espacios ni '_': el primero separa
+    let (head, pattern) = match value.find([' ', '_']) {
+        Some(pos) => (&value[..pos], &value[pos + 1..]),
+        None => (value, ""),
+    };
+    let (name, modifiers) = match head.split_once(',') {
+        Some((name, modifiers)) => (name, modifiers),
+        None if head.len() > 1 && SHORT_NAMES.contains(&head[..1]) => head.split_at(1),
+        None => (head, ""),
+    };
+
+    let known = LONG_NAMES.contains(&name) || (name.len() == 1 && SHORT_NAMES.contains(name));
+    if !known {
+        return Err(format!("regla de filtro desconocida: '{}' (p. ej. '- *.tmp' o '+ /conservar')", value));
+    }
+    if let Some(c) = modifiers.chars().find(|c| !MODIFIERS.contains(*c)) {
+        return Err(format!("modificador '{}' no válido en la regla de filtro '{}'", c, value));
+    }
+
+    let clears = name == "!" || name == "clear";
+    match (clears, pattern.is_empty()) {
+        (true, false) => Err(format!("'{}' no admite patrón: '{}'", name, value)),
+        (false, true) => Err(format!("falta el patrón o archivo en la regla de filtro '{}'", value)),
+        _ => Ok(value.to_string()),
+    }
+}
+
+/// Convierte un límite de ancho de banda con sufijo opcional a KB/s (unidad de rsync).
+/// Un número sin sufijo se interpreta directamente como KB/s.
+pub fn parse_bwlimit(value: &str) -> Result<u32, String> {
+    let (number, power) = split_unit_suffix(value)?;
+    let kbps = (number * 1024f64.powi(power.unwrap_or(1) as i32 - 1)).round();
+
+    if kbps > u32::MAX as f64 {
+        return Err(format!("límite de ancho de banda demasiado grande: '{}'", value));
+    }
+    // rsync entiende --bwlimit=0 como "sin límite"
+    if kbps < 1.0 {
+        return Err(format!("el límite de ancho de banda debe ser al menos 1K: '{}'", value));
+    }
+
+    Ok(kbps as u32)
+}
+
+/// Intervalo de --watch: "90s", "15m", "1h 30m"...; un número sin unidad son minutos
+pub fn parse_interval(value: &str) -> Result<Duration, String> {
+    let interval = match value.trim().parse::<u64>() {
+        Ok(minutes) => Duration::from_secs(minutes * 60),
+        Err(_) => humantime::parse_duration(value).map_err(|e| format!("intervalo no válido '{}': {}", value, e))?,
+    };
+
+    if interval.is_zero() {
+        return Err("el intervalo debe ser mayor que cero".to_string());
+    }
+    Ok(interval)
+}
+
+/// Edad de --prune-older-than: como los intervalos, pero un número sin unidad son días
+pub fn parse_age(value: &str) -> Result<Duration, String> {
+    match value.trim().parse::<u64>() {
+        Ok(days) => parse_interval(&format!("{}d", days)),
+        Err(_) => parse_interval(value),
+    }
+}
+
+fn parse_existing_dir(value: &str) -> Result<PathBuf, String> {
+    let path = crate::utils::normalize_path(std::path::Path::new(value));
+    if path.is_dir() {
+        Ok(path)
+    } else {
+        Err(format!("no existe el directorio '{}'", value))
+    }
+}
+
+fn parse_existing_file(value: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(value);
+    if path.is_file() {
+        Ok(path)
+    } else {
+        Err(format!("no existe el archivo '{}'", value))
+    }
+}
+
+/// Tamaño en bytes; admite los mismos sufijos K/M/G que --bwlimit
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let (number, power) = split_unit_suffix(value)?;
+    let bytes = (number * 1024f64.powi(power.unwrap_or(0) as i32)).round();
+
+    if bytes > u64::MAX as f64 {
+        return Err(format!("tamaño demasiado grande: '{}'", value));
+    }
+
+    Ok(bytes as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_level_rejects_unknown_values() {
+        let args = Cli::try_parse_from(["syncb", "--subir", "--log-level", "warn"]).unwrap();
+        assert_eq!(args.log_level, Some(LogLevel::Warn));
+        assert!(Cli::try_parse_from(["syncb", "--subir", "--log-level", "loud"]).is_err());
+    }
+
+    #[test]
+    fn test_items_from_merges_with_item() {
+        let dir = tempfile::tempdir().unwrap();
+        let list = dir.path().join("elementos.txt");
+        std::fs::write(&list, "# lista\nDocumentos\n\n  Fotos  \nMusica\n").unwrap();
+        let list = list.to_str().unwrap();
+
+        let args = Cli::try_parse_from(["syncb", "--subir", "--item", "Musica", "--items-from", list]).unwrap();
+        assert_eq!(
+            args.selected_items().unwrap(),
+            Some(vec!["Musica".to_string(), "Documentos".to_string(), "Fotos".to_string()])
+        );
+
+        let missing = dir.path().join("no-existe.txt");
+        assert!(Cli::try_parse_from(["syncb", "--subir", "--items-from", missing.to_str().unwrap()]).is_err());
+    }
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(parse_interval("15"), Ok(Duration::from_secs(15 * 60)));
+        assert_eq!(parse_interval("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_interval("1h 30m"), Ok(Duration::from_secs(90 * 60)));
+        assert!(parse_interval("0").is_err());
+        assert!(parse_interval("pronto").is_err());
+        let args = Cli::try_parse_from(["syncb", "--subir", "--max-runtime", "2h"]).unwrap();
+        assert_eq!(args.max_runtime, Some(Duration::from_secs(2 * 3600)));
+        assert!(Cli::try_parse_from(["syncb", "--subir", "--yes", "--watch", "1h", "--max-runtime", "2h"]).is_err());
+        assert_eq!(parse_age("30"), Ok(Duration::from_secs(30 * 86400)));
+        assert_eq!(parse_age("2w"), Ok(Duration::from_secs(14 * 86400)));
+        assert!(Cli::try_parse_from(["syncb", "--subir", "--prune-older-than", "30d"]).is_err());
+
+        assert!(Cli::try_parse_from(["syncb", "--subir", "--watch", "30m"]).is_err());
+        let args = Cli::try_parse_from(["syncb", "--subir", "--yes", "--watch", "30m"]).unwrap();
+        assert_eq!(args.watch, Some(Duration::from_secs(30 * 60)));
+
+        let args = Cli::try_parse_from(["syncb", "--subir", "--yes", "--watch-fs"]).unwrap();
+        assert_eq!(args.watch_fs, Some(Duration::from_secs(5)));
+        let mut args = Cli::try_parse_from(["syncb", "--bajar", "--yes", "--watch-fs"]).unwrap();
+        assert!(args.resolve_mode().is_err());
+    }
+
+    #[test]
+    fn test_explicit_options_override_profile() {
+        let profile = ["--mode=upload", "--yes", "--bwlimit=2M", "--exclude=*.iso"].map(String::from);
+        let args = Cli::with_profile(
+            ["syncb", "--profile", "nightly", "--bwlimit", "500", "--yes", "--exclude", "*.tmp"],
+            &profile,
+        )
+        .unwrap();
+        assert_eq!(args.mode, Some(SyncMode::Upload));
+        assert_eq!(args.profile.as_deref(), Some("nightly"));
+        assert_eq!(args.bwlimit, Some(500));
+        assert!(args.yes);
+        assert_eq!(args.exclude, ["*.iso", "*.tmp"]);
+
+        // Sin opciones explícitas queda solo el perfil
+        let args = Cli::with_profile(["syncb", "--profile", "nightly"], &profile).unwrap();
+        assert_eq!(args.bwlimit, Some(2048));
+        assert_eq!(args.exclude, ["*.iso"]);
+
+        // Un perfil no puede contradecir el modo pedido explícitamente
+        let mut args = Cli::with_profile(["syncb", "--profile", "nightly", "--bajar"], &profile).unwrap();
+        assert!(args.resolve_mode().is_err());
+    }
+
+    #[test]
+    fn test_mode_and_legacy_flags() {
+        let resolved = |argv: &[&str]| -> Result<Cli, String> {
+            let mut args = Cli::try_parse_from(argv).map_err(|e| e.to_string())?;
+            args.resolve_mode()?;
+            Ok(args)
+        };
+
+        let args = resolved(&["syncb", "--mode", "upload"]).unwrap();
+        assert_eq!(args.get_mode(), SyncMode::Upload);
+        assert!(args.subir && !args.bajar);
+
+        let args = resolved(&["syncb", "--mode", "two-way"]).unwrap();
+        assert!(args.two_way);
+        assert_eq!(resolved(&["syncb", "--bajar"]).unwrap().mode, Some(SyncMode::Download));
+        assert!(resolved(&["syncb", "--mode", "upload", "--subir"]).is_ok());
+
+        assert!(resolved(&["syncb", "--mode", "upload", "--bajar"]).unwrap_err().contains("--bajar"));
+        assert!(resolved(&["syncb", "--mode", "two-way", "--delete"]).is_err());
+        assert!(resolved(&["syncb", "--mode", "sideways"]).is_err());
+
+        let crypto_only = Cli::try_parse_from(["syncb", "--subir", "--crypto-only", "--no-crypto-mount-check"]).unwrap();
+        assert!(crypto_only.validate().is_ok());
+        assert!(Cli::try_parse_from(["syncb", "--subir", "--crypto-only", "--delete"]).is_err());
+        assert!(Cli::try_parse_from(["syncb", "--subir", "--no-crypto-mount-check"]).unwrap().validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_requires_mode_unless_standalone_action() {
+        let validated = |argv: &[&str]| {
+            let mut args = Cli::try_parse_from(argv).unwrap();
+            args.resolve_mode()?;
+            args.validate()
+        };
+
+        assert!(validated(&["syncb"]).is_err());
+        assert!(validated(&["syncb", "--crypto"]).is_err());
+        assert!(validated(&["syncb", "--dry-run"]).is_err());
+        assert!(validated(&["syncb", "--mode", "download"]).is_ok());
+        assert!(validated(&["syncb", "--two-way"]).is_ok());
+
+        for action in [
+            &["syncb", "--compare-checksums"][..],
+            &["syncb", "--check-config"],
+            &["syncb", "--repair-symlinks"],
+            &["syncb", "--prune-older-than", "30d"],
+            &["syncb", "--force-unlock"],
+            &["syncb", "--status"],
+            &["syncb", "--history", "5"],
+            &["syncb", "--init-config"],
+            &["syncb", "--plan-in", "plan.json"],
+        ] {
+            assert!(validated(action).is_ok(), "{:?}", action);
+        }
+    }
+
+    #[test]
+    fn test_rsync_bwlimit_args_split_limit() {
+        let args = Cli { bwlimit: Some(1000), ..Default::default() };
+        assert_eq!(args.rsync_bwlimit_args(1), ["--bwlimit", "1000"]);
+        assert_eq!(args.rsync_bwlimit_args(3), ["--bwlimit", "333"]);
+        assert_eq!(args.rsync_bwlimit_args(0), ["--bwlimit", "1000"]);
+        assert_eq!(Cli { bwlimit: Some(1), ..Default::default() }.rsync_bwlimit_args(4), ["--bwlimit", "1"]);
+        assert!(Cli::default().rsync_bwlimit_args(1).is_empty());
+    }
+
+    #[test]
+    fn test_parse_filter_rule() {
+        for rule in ["+ /conservar", "- *.tmp", "-_mi_archivo", "merge ~/.rsync-rules", "dir-merge,- .excluir", "-/ /tmp", "P .git/", "!", "clear"] {
+            assert_eq!(parse_filter_rule(rule).as_deref(), Ok(rule), "{}", rule);
+        }
+        for rule in ["", "*.tmp", "x foo", "-", "+ ", "-q foo", "! foo", "borrar foo"] {
+            assert!(parse_filter_rule(rule).is_err(), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn test_parse_bwlimit_suffixes() {
+        assert_eq!(parse_bwlimit("500K"), Ok(500));
+        assert_eq!(parse_bwlimit("2M"), Ok(2048));
+        assert_eq!(parse_bwlimit("1.5G"), Ok(1_572_864));
+        assert_eq!(parse_bwlimit("2m"), Ok(2048));
+    }
+
+    #[test]
+    fn test_parse_bwlimit_bare_number_is_kbps() {
+        assert_eq!(parse_bwlimit("1000"), Ok(1000));
+    }
+
+    #[test]
+    fn test_parse_size_suffixes() {
+        assert_eq!(parse_size("100M"), Ok(100 * 1024 * 1024));
+        assert_eq!(parse_size("2G"), Ok(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("512"), Ok(512));
+        assert!(parse_size("2X").is_err());
+    }
+
+    #[test]
+    fn test_parse_bwlimit_rejects_garbage() {
+        assert!(parse_bwlimit("").is_err());
+        assert!(parse_bwlimit("abc").is_err());
+        assert!(parse_bwlimit("2X").is_err());
+        assert!(parse_bwlimit("-5M").is_err());
+        assert!(parse_bwlimit("M").is_err());
+    }
+
+    #[test]
+    fn test_parse_bwlimit_rejects_zero() {
+        assert!(parse_bwlimit("0").is_err());
+        assert!(parse_bwlimit("0K").is_err());
+        assert!(parse_bwlimit("0.1").is_err());
+        assert_eq!(parse_bwlimit("1K"), Ok(1));
+    }
+}
@@ -1,14 +1,189 @@
-use crate::cli::Cli;
+use crate::cli::{CasePolicy, Cli, DeletePolicy, SymlinkPolicy};
 use crate::error::{AppError, Result};
+use crate::utils::{absolute_path, expand_path, normalize_path};
+use chrono::{NaiveTime, Timelike};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 // DEFINICIÓN DE HOSTCONFIG - debe estar PRIMERO y en ESTE archivo
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct HostConfig {
     pub sync_items: Vec<String>,
     pub exclusions: Vec<String>,
+    /// Directorio local propio del host; si falta se usa `general.local_dir`
+    #[serde(default)]
+    pub local_dir: Option<PathBuf>,
+    /// Comandos de shell que se ejecutan antes de sincronizar; si uno falla se aborta
+    #[serde(default)]
+    pub pre_sync_hooks: Vec<String>,
+    /// Comandos de shell tras una sincronización correcta; sus fallos solo se avisan
+    #[serde(default)]
+    pub post_sync_hooks: Vec<String>,
+    /// Timeout en minutos por elemento; prevalece sobre --timeout y `default_timeout_minutes`
+    #[serde(default)]
+    pub item_timeouts: HashMap<String, u32>,
+    /// Reglas de filtro de rsync tal cual (como --filter), en orden y por
+    /// delante de `exclusions`; las de --filter van antes que estas
+    #[serde(default)]
+    pub filters: Vec<String>,
+    /// Opciones de rsync adicionales por elemento (solo las de `ALLOWED_ITEM_OPTIONS`)
+    #[serde(default)]
+    pub item_options: HashMap<String, Vec<String>>,
+    /// Sincroniza Crypto con `local/remote_crypto_hostname_rtva_dir` y
+    /// activa Debug como nivel de log por defecto
+    #[serde(default)]
+    pub use_alternate_crypto_paths: bool,
+    /// Tamaño máximo de archivo ("100M", "2G"...); --max-filesize prevalece
+    #[serde(default, deserialize_with = "deserialize_size")]
+    pub max_filesize: Option<u64>,
+    /// Tamaño mínimo de archivo; --min-filesize prevalece
+    #[serde(default, deserialize_with = "deserialize_size")]
+    pub min_filesize: Option<u64>,
+}
+
+/// Opciones de `item_options` admitidas tal cual. Quedan fuera las que
+/// ejecutan programas (--rsh, --rsync-path), borran o escriben fuera del destino.
+const ALLOWED_ITEM_OPTIONS: &[&str] = &[
+    "--perms", "--no-perms", "--owner", "--no-owner", "--group", "--no-group",
+    "--times", "--no-times", "--omit-dir-times", "--executability", "--acls", "--xattrs",
+    "--hard-links", "--numeric-ids", "--size-only", "--checksum", "--ignore-times",
+    "--ignore-existing", "--existing", "--inplace", "--whole-file", "--no-whole-file",
+    "--sparse", "--fuzzy", "--copy-links", "--copy-unsafe-links", "--safe-links",
+    "--no-links", "--prune-empty-dirs", "--compress",
+];
+
+/// Opciones de `item_options` admitidas con valor (`--opción=valor`)
+const ALLOWED_ITEM_OPTIONS_WITH_VALUE: &[&str] = &[
+    "--chmod", "--modify-window", "--max-size", "--min-size", "--compress-level",
+    "--skip-compress", "--bwlimit", "--exclude", "--include",
+];
+
+/// Opciones de `rsync_base_options` admitidas además de las de
+/// `ALLOWED_ITEM_OPTIONS`. Quedan fuera las que ejecutan programas, borran,
+/// escriben fuera del destino o cambian la salida que se analiza
+/// (--out-format, --quiet, --info=name0...).
+const ALLOWED_BASE_OPTIONS: &[&str] = &[
+    "--archive", "--recursive", "--links", "--perms", "--times", "--devices", "--specials",
+    "--verbose", "--human-readable", "--progress", "--itemize-changes", "--stats", "--partial",
+    "--update", "--one-file-system", "--protect-args", "--no-motd", "--8-bit-output",
+];
+
+/// Opciones de `rsync_base_options` admitidas con valor (`--opción=valor`),
+/// además de las de `ALLOWED_ITEM_OPTIONS_WITH_VALUE`
+const ALLOWED_BASE_OPTIONS_WITH_VALUE: &[&str] = &["--timeout", "--contimeout", "--iconv"];
+
+/// Opciones cortas admitidas, solas o agrupadas (`-avh`): sin -e (--rsh),
+/// -n (--dry-run), -q (--quiet) ni otras con valor
+const ALLOWED_SHORT_OPTIONS: &str = "arlptgoDvhPiuzHAXxSWcOEmk";
+
+fn is_allowed_base_option(option: &str) -> bool {
+    if let Some(cluster) = option.strip_prefix('-').filter(|rest| !rest.starts_with('-')) {
+        return !cluster.is_empty() && cluster.chars().all(|flag| ALLOWED_SHORT_OPTIONS.contains(flag));
+    }
+
+    match option.split_once('=') {
+        // Solo la información de progreso y estadísticas: el resto puede
+        // quitar los nombres de archivo que se analizan
+        Some(("--info", value)) => value
+            .split(',')
+            .all(|flag| flag.starts_with("progress") || flag.starts_with("stats")),
+        Some((name, value)) => {
+            (!value.is_empty() && ALLOWED_BASE_OPTIONS_WITH_VALUE.contains(&name)) || is_allowed_item_option(option)
+        }
+        None => ALLOWED_BASE_OPTIONS.contains(&option) || is_allowed_item_option(option),
+    }
+}
+
+fn is_allowed_item_option(option: &str) -> bool {
+    match option.split_once('=') {
+        Some((name, value)) => !value.is_empty() && ALLOWED_ITEM_OPTIONS_WITH_VALUE.contains(&name),
+        None => ALLOWED_ITEM_OPTIONS.contains(&option),
+    }
+}
+
+fn deserialize_size<'de, D>(deserializer: D) -> std::result::Result<Option<u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value: Option<String> = Option::deserialize(deserializer)?;
+    value
+        .map(|value| crate::cli::parse_size(&value).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+/// Franja horaria de --bwlimit-schedule: `limit` se aplica en [from, to);
+/// si `from` es posterior a `to` la franja cruza la medianoche
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "BwlimitWindowToml", into = "BwlimitWindowToml")]
+pub struct BwlimitWindow {
+    pub from: NaiveTime,
+    pub to: NaiveTime,
+    /// KB/s, como --bwlimit
+    pub limit: u32,
+}
+
+/// Forma en el TOML: `{ from = "09:00", to = "18:00", limit = "2M" }`
+#[derive(Serialize, Deserialize)]
+struct BwlimitWindowToml {
+    from: String,
+    to: String,
+    limit: String,
+}
+
+impl TryFrom<BwlimitWindowToml> for BwlimitWindow {
+    type Error = String;
+
+    fn try_from(raw: BwlimitWindowToml) -> std::result::Result<Self, String> {
+        let parse_time = |value: &str| {
+            NaiveTime::parse_from_str(value, "%H:%M")
+                .map_err(|_| format!("hora no válida en bwlimit_schedule: '{}' (formato HH:MM)", value))
+        };
+        let window = Self {
+            from: parse_time(&raw.from)?,
+            to: parse_time(&raw.to)?,
+            limit: crate::cli::parse_bwlimit(&raw.limit)?,
+        };
+        if window.from == window.to {
+            return Err(format!("franja vacía en bwlimit_schedule: {}-{}", raw.from, raw.to));
+        }
+        Ok(window)
+    }
+}
+
+impl From<BwlimitWindow> for BwlimitWindowToml {
+    fn from(window: BwlimitWindow) -> Self {
+        Self {
+            from: window.from.format("%H:%M").to_string(),
+            to: window.to.format("%H:%M").to_string(),
+            limit: format!("{}K", window.limit),
+        }
+    }
+}
+
+impl BwlimitWindow {
+    /// Minutos del día cubiertos, en dos tramos si cruza la medianoche
+    /// (si no, el segundo está vacío)
+    fn minute_ranges(&self) -> [std::ops::Range<u32>; 2] {
+        let minutes = |time: NaiveTime| time.hour() * 60 + time.minute();
+        let (from, to) = (minutes(self.from), minutes(self.to));
+        if from < to {
+            [from..to, 0..0]
+        } else {
+            [from..24 * 60, 0..to]
+        }
+    }
+
+    fn overlaps(&self, other: &BwlimitWindow) -> bool {
+        self.minute_ranges()
+            .iter()
+            .any(|a| other.minute_ranges().iter().any(|b| a.start < b.end && b.start < a.end))
+    }
+
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        let minute = time.hour() * 60 + time.minute();
+        self.minute_ranges().iter().any(|range| range.contains(&minute))
+    }
 }
 
 // LUEGO los otros structs
@@ -26,52 +201,435 @@ pub struct CryptoConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GeneralConfig {
     pub local_dir: PathBuf,
+    /// Rutas de pCloud; con `[[remotes]]` las sustituye el remoto seleccionado
+    #[serde(default)]
     pub pcloud_mount_point: PathBuf,
+    #[serde(default)]
     pub pcloud_backup_comun: PathBuf,
+    #[serde(default)]
     pub pcloud_backup_readonly: PathBuf,
+    /// Remoto de `[[remotes]]` usado sin --remote (por defecto, el primero)
+    #[serde(default)]
+    pub default_remote: Option<String>,
     pub log_file: PathBuf,
+    /// Tamaño (MB) a partir del cual se rota el log
+    #[serde(default = "default_log_max_size_mb")]
+    pub log_max_size_mb: u64,
+    /// Logs rotados que se conservan (syncb.log.1, .2, ...); 0 no conserva ninguno
+    #[serde(default = "default_log_max_files")]
+    pub log_max_files: usize,
+    /// Comprimir con gzip los logs rotados
+    #[serde(default)]
+    pub log_compress: bool,
+    /// Historial de ejecuciones (una línea JSON por ejecución)
+    #[serde(default = "default_history_file")]
+    pub history_file: PathBuf,
     pub lock_file: PathBuf,
     pub lock_timeout_seconds: u64,
     pub default_timeout_minutes: u32,
+    /// Reserva (MB) que debe quedar libre en cada lado receptor, además de lo
+    /// estimado a transferir con `estimate_transfer_size`
+    #[serde(default = "default_min_free_space_mb")]
+    pub min_free_space_mb: u64,
+    /// Estimar antes de empezar lo que se transferirá (una pasada --dry-run
+    /// --stats más, que recorre de nuevo los elementos) y exigir ese espacio.
+    /// Con `false` se omite la pasada y solo se exige la reserva
+    #[serde(default = "default_estimate_transfer_size")]
+    pub estimate_transfer_size: bool,
+    /// Nombre del manifiesto de enlaces simbólicos guardado en pCloud
+    #[serde(default = "default_symlinks_file")]
+    pub symlinks_file: String,
+    /// Opciones con las que empieza cada rsync (por defecto `-av --progress
+    /// --itemize-changes`). En montajes FUSE que fallan con chmod/chown, p. ej.
+    /// `["-rtv", "--progress"]`; --itemize-changes se añade si falta
+    #[serde(default = "default_rsync_base_options")]
+    pub rsync_base_options: Vec<String>,
+    /// Tratamiento de enlaces simbólicos en rsync: preserve, follow o skip
+    #[serde(default)]
+    pub symlink_policy: SymlinkPolicy,
+    /// Cómo renombra --resolve-case los nombres en colisión: suffix o timestamp
+    #[serde(default)]
+    pub case_policy: CasePolicy,
+    /// Profundidad máxima al buscar enlaces simbólicos dentro de un elemento
+    #[serde(default = "default_symlink_scan_max_depth")]
+    pub symlink_scan_max_depth: usize,
+    /// Nombres de archivos por directorio con sintaxis .gitignore (p. ej. ".syncignore")
+    #[serde(default)]
+    pub ignore_files: Vec<String>,
+    /// Patrones que siempre se comparan por checksum (como --force-checksum-on)
+    #[serde(default)]
+    pub force_checksum_on: Vec<String>,
+    /// Límites de ancho de banda por franja horaria (para --bwlimit-schedule)
+    #[serde(default)]
+    pub bwlimit_schedule: Vec<BwlimitWindow>,
+    /// Comprimir siempre en rsync (-z), como --compress
+    #[serde(default)]
+    pub compress: bool,
+    /// Nivel de compresión por defecto (0-9); la línea de comandos prevalece
+    #[serde(default)]
+    pub compress_level: Option<u32>,
+    /// Sufijos que no se comprimen, separados por '/' (rsync --skip-compress)
+    #[serde(default)]
+    pub skip_compress: Option<String>,
+    /// Archivo de exclusiones en formato rsync (--exclude-from)
+    #[serde(default)]
+    pub exclude_from: Option<PathBuf>,
+    /// Directorio (relativo al destino) donde rsync guarda transferencias parciales
+    #[serde(default = "default_partial_dir")]
+    pub partial_dir: String,
+    /// URL usada para comprobar la conectividad (solo se abre una conexión TCP)
+    #[serde(default = "default_connectivity_check_url")]
+    pub connectivity_check_url: String,
+    /// Cuándo borra --delete: before, during, after o delay (como --delete-policy)
+    #[serde(default)]
+    pub delete_policy: DeletePolicy,
+    /// Rutas de cada lista (nuevos, actualizados, borrados) que guarda el
+    /// informe por elemento: las primeras y las últimas N. Sin indicar, todas
+    #[serde(default)]
+    pub report_sample_size: Option<usize>,
+    /// Con --delete, borrados a partir de los cuales se pide confirmación expresa
+    #[serde(default = "default_delete_confirm_threshold")]
+    pub delete_confirm_threshold: usize,
+    /// Con --delete --yes, borrados a partir de los cuales se aborta siempre
+    #[serde(default = "default_delete_abort_threshold")]
+    pub delete_abort_threshold: usize,
+    /// Si es true, un elemento configurado que no existe en el origen (local
+    /// al subir, pCloud al bajar) es un error
+    #[serde(default)]
+    pub strict_items: bool,
+    /// Estado de la última sincronización --two-way de cada elemento
+    #[serde(default = "default_two_way_state_file")]
+    pub two_way_state_file: PathBuf,
+    /// Elementos completados en la ejecución en curso (para --resume-items)
+    #[serde(default = "default_resume_state_file")]
+    pub resume_state_file: PathBuf,
+    /// Antigüedad máxima (horas) de una ejecución interrumpida que se puede reanudar
+    #[serde(default = "default_resume_max_age_hours")]
+    pub resume_max_age_hours: u64,
+    /// Algoritmo por defecto de --checksum-choice (p. ej. "xxh64")
+    #[serde(default)]
+    pub checksum_choice: Option<String>,
+    /// Cache de checksums para --checksum (se desactiva si no se indica)
+    #[serde(default)]
+    pub checksum_cache_dir: Option<PathBuf>,
     pub crypto: CryptoConfig,
 }
 
+fn default_log_max_size_mb() -> u64 {
+    10
+}
+
+fn default_log_max_files() -> usize {
+    5
+}
+
+fn default_history_file() -> PathBuf {
+    PathBuf::from("~/.local/share/syncb/history.jsonl")
+}
+
+fn default_two_way_state_file() -> PathBuf {
+    PathBuf::from("~/.local/share/syncb/two_way_state.json")
+}
+
+fn default_resume_state_file() -> PathBuf {
+    PathBuf::from("~/.local/share/syncb/resume_state.json")
+}
+
+fn default_resume_max_age_hours() -> u64 {
+    24
+}
+
+fn default_rsync_base_options() -> Vec<String> {
+    ["-av", "--progress", "--itemize-changes"].map(String::from).to_vec()
+}
+
+fn default_symlinks_file() -> String {
+    ".syncb_symlinks.meta".to_string()
+}
+
+fn default_symlink_scan_max_depth() -> usize {
+    64
+}
+
+fn default_partial_dir() -> String {
+    ".rsync-partial".to_string()
+}
+
+fn default_min_free_space_mb() -> u64 {
+    500
+}
+
+fn default_estimate_transfer_size() -> bool {
+    true
+}
+
+fn default_connectivity_check_url() -> String {
+    "https://www.pcloud.com/".to_string()
+}
+
+fn default_delete_confirm_threshold() -> usize {
+    50
+}
+
+fn default_delete_abort_threshold() -> usize {
+    1000
+}
+
+/// Canal por el que se avisa del resultado de cada sincronización
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationBackend {
+    #[default]
+    Desktop,
+    Webhook,
+    Email,
+    None,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailConfig {
+    /// p. ej. "smtps://smtp.example.com:465"
+    pub smtp_url: String,
+    pub from: String,
+    pub to: Vec<String>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotificationsConfig {
+    #[serde(default)]
+    pub backend: NotificationBackend,
+    /// Destino del POST con el resumen en JSON (backend = "webhook")
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+    /// Configuración SMTP (backend = "email")
+    #[serde(default)]
+    pub email: Option<EmailConfig>,
+    /// Qué ejecuciones avisan: always, errors o never
+    #[serde(default)]
+    pub notify_on: NotifyOn,
+    /// Las ejecuciones correctas más cortas que esto (segundos) no avisan
+    #[serde(default)]
+    pub min_duration_seconds: u64,
+}
+
+/// Qué resultados se notifican (`notifications.notify_on`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifyOn {
+    #[default]
+    Always,
+    /// Solo las ejecuciones con errores (útil en las automáticas frecuentes)
+    Errors,
+    Never,
+}
+
+impl NotificationsConfig {
+    /// Si una ejecución con ese resultado y duración debe avisar. Los errores
+    /// no dependen de la duración; los éxitos cortos son ruido
+    pub fn should_notify(&self, failed: bool, duration: std::time::Duration) -> bool {
+        match self.notify_on {
+            NotifyOn::Never => false,
+            NotifyOn::Errors => failed,
+            NotifyOn::Always => failed || duration.as_secs() >= self.min_duration_seconds,
+        }
+    }
+}
+
+/// Cómo se llega a un remoto de `[[remotes]]`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RemoteKind {
+    /// Montaje FUSE de pCloud (mount_point, backup_comun, backup_readonly)
+    #[default]
+    Pcloud,
+    /// Equipo accesible por SSH (host, user, port, path); rsync usa `-e ssh`
+    Ssh,
+}
+
+/// Una cuenta/montaje de pCloud (o un equipo por SSH) seleccionable con --remote
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteConfig {
+    pub name: String,
+    #[serde(default, rename = "type")]
+    pub kind: RemoteKind,
+    #[serde(default)]
+    pub mount_point: PathBuf,
+    #[serde(default)]
+    pub backup_comun: PathBuf,
+    #[serde(default)]
+    pub backup_readonly: PathBuf,
+    #[serde(default)]
+    pub host: Option<String>,
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// Directorio remoto con los elementos (type = "ssh"); hace de backup
+    /// común y de solo lectura a la vez
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+}
+
+/// Equipo del remoto SSH elegido
+#[derive(Debug, Clone, PartialEq)]
+pub struct SshTarget {
+    pub host: String,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+}
+
+impl SshTarget {
+    /// `usuario@host`, o solo `host` si no se indica usuario
+    pub fn destination(&self) -> String {
+        match &self.user {
+            Some(user) => format!("{}@{}", user, self.host),
+            None => self.host.clone(),
+        }
+    }
+
+    /// Ruta remota en la sintaxis de rsync (`usuario@host:ruta`)
+    pub fn remote_path(&self, path: &Path) -> std::ffi::OsString {
+        let mut remote = std::ffi::OsString::from(format!("{}:", self.destination()));
+        remote.push(path);
+        remote
+    }
+
+    /// Programa de transporte para `rsync -e`
+    pub fn rsh(&self) -> String {
+        match self.port {
+            Some(port) => format!("ssh -p {}", port),
+            None => "ssh".to_string(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
     pub general: GeneralConfig,
+    #[serde(default)]
+    pub remotes: Vec<RemoteConfig>,
     pub hosts: HashMap<String, HostConfig>, // Ahora HostConfig está definido
     #[serde(default)]
     pub exclusion_patterns: Vec<String>,
+    #[serde(default)]
+    pub notifications: NotificationsConfig,
+    /// Combinaciones de opciones con nombre para --profile (clave = opción larga)
+    #[serde(default)]
+    pub profiles: HashMap<String, toml::Table>,
+    /// Archivo del que se cargó la configuración
+    #[serde(skip)]
+    pub config_path: PathBuf,
+    /// Nombre del remoto elegido de `[[remotes]]`, si los hay
+    #[serde(skip)]
+    pub remote_name: Option<String>,
+    /// Equipo si el remoto elegido es `type = "ssh"`: las rutas `pcloud_*`
+    /// son entonces rutas de ese equipo y no hay montaje local
+    #[serde(skip)]
+    pub ssh: Option<SshTarget>,
 }
 
 impl AppConfig {
     pub fn load(args: &Cli) -> Result<Self> {
-        let config_path = Self::find_config_file()?;
+        let config_path = match &args.config {
+            Some(path) => {
+                let path = normalize_path(path);
+                if !path.exists() {
+                    return Err(AppError::Config(format!(
+                        "El archivo de configuración no existe: {:?}",
+                        path
+                    )));
+                }
+                path
+            }
+            None => Self::find_config_file()?,
+        };
         let config_content = std::fs::read_to_string(&config_path)?;
 
         let mut config: AppConfig = toml::from_str(&config_content)?;
+        config.config_path = config_path;
+
+        // Rutas de pCloud del remoto seleccionado
+        config.select_remote(args.remote.as_deref())?;
+
+        config.check_ssh_compatibility(args)?;
 
-        // Aplicar expansión de ~ en las rutas
+        // Aplicar expansión de ~ y variables de entorno en las rutas
         config.expand_paths()?;
 
         // Apply command line overrides
-        config.apply_cli_overrides(args);
+        config.apply_cli_overrides(args)?;
 
         // Validate configuration
-        config.validate()?;
+        config.validate(args.lenient)?;
+
+        if let Some(exclude_from) = &args.exclude_from {
+            let exclude_from = normalize_path(exclude_from);
+            if !exclude_from.exists() {
+                return Err(AppError::Config(format!(
+                    "El archivo de exclusiones no existe: {:?}",
+                    exclude_from
+                )));
+            }
+        }
 
         Ok(config)
     }
 
+    /// Argumentos de línea de comandos equivalentes a `[profiles.<name>]`:
+    /// `true` activa la opción, `false` la omite, las listas la repiten y el
+    /// resto se pasa como `--opción=valor`
+    pub fn profile_args(&self, name: &str) -> Result<Vec<String>> {
+        let profile = self.profiles.get(name).ok_or_else(|| {
+            let mut available: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            available.sort();
+            AppError::Config(format!(
+                "El perfil '{}' no está en [profiles] (disponibles: {})",
+                name,
+                if available.is_empty() { "ninguno".to_string() } else { available.join(", ") }
+            ))
+        })?;
+
+        let mut args = Vec::new();
+        for (key, value) in profile {
+            if matches!(key.as_str(), "config" | "profile") {
+                return Err(AppError::Config(format!("'{}' no se puede fijar en el perfil '{}'", key, name)));
+            }
+
+            let option = format!("--{}", key.replace('_', "-"));
+            let values = match value {
+                toml::Value::Boolean(true) => {
+                    args.push(option);
+                    continue;
+                }
+                toml::Value::Boolean(false) => continue,
+                toml::Value::Array(values) => values.iter().collect(),
+                value => vec![value],
+            };
+            for value in values {
+                let value = match value {
+                    toml::Value::String(value) => value.clone(),
+                    toml::Value::Integer(value) => value.to_string(),
+                    toml::Value::Float(value) => value.to_string(),
+                    _ => {
+                        return Err(AppError::Config(format!(
+                            "Valor no admitido para '{}' en el perfil '{}'",
+                            key, name
+                        )))
+                    }
+                };
+                args.push(format!("{}={}", option, value));
+            }
+        }
+
+        Ok(args)
+    }
+
     fn find_config_file() -> Result<PathBuf> {
-        let possible_paths = [
-            PathBuf::from("syncb_config.toml"),
-            PathBuf::from("./config.toml"),
-            dirs::config_dir()
-                .unwrap_or_default()
-                .join("syncb/config.toml"),
-            PathBuf::from("/etc/syncb/config.toml"),
-        ];
+        let possible_paths = config_search_paths();
 
         for path in &possible_paths {
             if path.exists() {
@@ -79,21 +637,131 @@ impl AppConfig {
             }
         }
 
-        Err(AppError::Config("No configuration file found".to_string()))
+        Err(AppError::Config(format!(
+            "No configuration file found (buscado en: {}); créela con --init-config",
+            possible_paths
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )))
+    }
+
+    /// Copia en `general` las rutas del remoto elegido (--remote, después
+    /// `default_remote`, después el primero), de modo que el resto del código
+    /// solo consulta `general.pcloud_*`. Sin `[[remotes]]` se usan tal cual.
+    fn select_remote(&mut self, requested: Option<&str>) -> Result<()> {
+        let name = requested.or(self.general.default_remote.as_deref());
+
+        let remote = match name {
+            Some(name) => self.remotes.iter().find(|remote| remote.name == name).ok_or_else(|| {
+                AppError::Config(format!(
+                    "El remoto '{}' no está en [[remotes]] (disponibles: {})",
+                    name,
+                    self.remotes.iter().map(|r| r.name.as_str()).collect::<Vec<_>>().join(", ")
+                ))
+            })?,
+            None => match self.remotes.first() {
+                Some(remote) => remote,
+                None if self.general.pcloud_mount_point.as_os_str().is_empty() => {
+                    return Err(AppError::Config(
+                        "Falta general.pcloud_mount_point o una sección [[remotes]]".to_string(),
+                    ))
+                }
+                None => return Ok(()),
+            },
+        };
+
+        log::debug!("Remoto seleccionado: {}", remote.name);
+        match remote.kind {
+            RemoteKind::Pcloud => {
+                self.general.pcloud_mount_point = remote.mount_point.clone();
+                self.general.pcloud_backup_comun = remote.backup_comun.clone();
+                self.general.pcloud_backup_readonly = remote.backup_readonly.clone();
+                self.ssh = None;
+            }
+            RemoteKind::Ssh => {
+                let (Some(host), Some(path)) = (&remote.host, &remote.path) else {
+                    return Err(AppError::Config(format!(
+                        "El remoto SSH '{}' necesita host y path",
+                        remote.name
+                    )));
+                };
+                if !path.is_absolute() {
+                    return Err(AppError::Config(format!(
+                        "La ruta del remoto SSH '{}' debe ser absoluta: {:?}",
+                        remote.name, path
+                    )));
+                }
+                self.general.pcloud_mount_point = path.clone();
+                self.general.pcloud_backup_comun = path.clone();
+                self.general.pcloud_backup_readonly = path.clone();
+                self.ssh = Some(SshTarget { host: host.clone(), user: remote.user.clone(), port: remote.port });
+            }
+        }
+        self.remote_name = Some(remote.name.clone());
+        Ok(())
+    }
+
+    /// Lo que trabaja sobre el lado de pCloud como un directorio local
+    /// (manifiestos, instantáneas, --two-way...) no vale con un remoto SSH
+    fn check_ssh_compatibility(&self, args: &Cli) -> Result<()> {
+        if self.ssh.is_none() {
+            return Ok(());
+        }
+
+        let unsupported = [
+            ("--two-way", args.get_mode() == crate::cli::SyncMode::TwoWay),
+            ("--snapshot", args.snapshot),
+            ("--manifest", args.manifest),
+            ("--repair-symlinks", args.repair_symlinks),
+            ("--prune-older-than", args.prune_older_than.is_some()),
+        ];
+        match unsupported.iter().find(|(_, used)| *used) {
+            Some((flag, _)) => Err(AppError::Config(format!(
+                "{} no está disponible con un remoto SSH ('{}')",
+                flag,
+                self.remote_name.as_deref().unwrap_or_default()
+            ))),
+            None => Ok(()),
+        }
     }
 
-    fn apply_cli_overrides(&mut self, args: &Cli) {
-        if let Some(items) = &args.items {
+    /// Sin entrada para el host ni `default`, --item crea una configuración
+    /// transitoria; --exclude sin --item sería un no-op silencioso, así que falla
+    fn apply_cli_overrides(&mut self, args: &Cli) -> Result<()> {
+        if let Some(items) = args.selected_items()? {
             if let Some(host_config) = self.get_current_host_config_mut() {
-                host_config.sync_items = items.clone();
+                host_config.sync_items = items;
+            } else {
+                let hostname = Self::get_hostname();
+                log::info!(
+                    "Sin configuración para el host '{}': se usan solo los elementos de --item",
+                    hostname
+                );
+                self.hosts.insert(
+                    hostname,
+                    HostConfig {
+                        sync_items: items,
+                        ..Default::default()
+                    },
+                );
             }
         }
 
         if !args.exclude.is_empty() {
-            if let Some(host_config) = self.get_current_host_config_mut() {
-                host_config.exclusions.extend(args.exclude.clone());
+            match self.get_current_host_config_mut() {
+                Some(host_config) => host_config.exclusions.extend(args.exclude.clone()),
+                None => {
+                    return Err(AppError::Config(format!(
+                        "No hay configuración para el host '{}' ni 'default': use --item para indicar qué sincronizar",
+                        Self::get_hostname()
+                    )))
+                }
             }
         }
+
+        Ok(())
     }
 
     pub fn get_current_host_config(&self) -> Result<&HostConfig> {
@@ -106,6 +774,14 @@ impl AppConfig {
             })
     }
 
+    /// Directorio local efectivo: el del host actual si lo define, o el global
+    pub fn local_dir(&self) -> PathBuf {
+        self.get_current_host_config()
+            .ok()
+            .and_then(|host_config| host_config.local_dir.clone())
+            .unwrap_or_else(|| self.general.local_dir.clone())
+    }
+
     fn get_current_host_config_mut(&mut self) -> Option<&mut HostConfig> {
         let hostname = Self::get_hostname();
 
@@ -124,24 +800,28 @@ impl AppConfig {
     }
 
     fn expand_paths(&mut self) -> Result<()> {
-        // CORREGIDO: usar if let Some en lugar de if let Ok
         let expand_path = |path: &mut PathBuf| {
-            if let Some(path_str) = path.to_str() {
-                // to_str() devuelve Option<&str>, no Result
-                if path_str.starts_with('~') {
-                    if let Some(home_dir) = dirs::home_dir() {
-                        let expanded = path_str.replacen('~', &home_dir.to_string_lossy(), 1);
-                        *path = PathBuf::from(expanded);
-                    }
-                }
-            }
+            *path = expand_path(path);
         };
 
         expand_path(&mut self.general.local_dir);
-        expand_path(&mut self.general.pcloud_mount_point);
-        expand_path(&mut self.general.pcloud_backup_comun);
-        expand_path(&mut self.general.pcloud_backup_readonly);
+        // Las rutas de un remoto SSH son del otro equipo: ni ~ ni variables locales
+        if self.ssh.is_none() {
+            expand_path(&mut self.general.pcloud_mount_point);
+            expand_path(&mut self.general.pcloud_backup_comun);
+            expand_path(&mut self.general.pcloud_backup_readonly);
+        }
         expand_path(&mut self.general.log_file);
+        expand_path(&mut self.general.history_file);
+        expand_path(&mut self.general.two_way_state_file);
+        expand_path(&mut self.general.resume_state_file);
+        if let Some(exclude_from) = self.general.exclude_from.as_mut() {
+            expand_path(exclude_from);
+            *exclude_from = absolute_path(exclude_from);
+        }
+        if let Some(checksum_cache_dir) = self.general.checksum_cache_dir.as_mut() {
+            expand_path(checksum_cache_dir);
+        }
         expand_path(&mut self.general.lock_file);
         expand_path(&mut self.general.crypto.local_crypto_dir);
         expand_path(&mut self.general.crypto.remote_crypto_dir);
@@ -150,23 +830,346 @@ impl AppConfig {
         expand_path(&mut self.general.crypto.local_crypto_hostname_rtva_dir);
         expand_path(&mut self.general.crypto.remote_crypto_hostname_rtva_dir);
 
+        for host_config in self.hosts.values_mut() {
+            if let Some(local_dir) = host_config.local_dir.as_mut() {
+                expand_path(local_dir);
+            }
+        }
+
         Ok(())
     }
 
-    fn validate(&self) -> Result<()> {
-        // Validar que las rutas existen
-        let paths_to_check = [&self.general.local_dir, &self.general.pcloud_mount_point];
+    /// Valida las rutas principales. Un `local_dir` inexistente es un error salvo
+    /// en modo `lenient`; el punto de montaje puede montarse después, solo avisa
+    fn validate(&self, lenient: bool) -> Result<()> {
+        let local_dir = self.local_dir();
+        if !local_dir.exists() {
+            if lenient {
+                log::warn!("El directorio local no existe: {:?}", local_dir);
+            } else {
+                return Err(AppError::Config(format!(
+                    "El directorio local no existe: {:?} (use --lenient para continuar)",
+                    local_dir
+                )));
+            }
+        }
+
+        if let Some(exclude_from) = &self.general.exclude_from {
+            if !exclude_from.exists() {
+                return Err(AppError::Config(format!(
+                    "El archivo de exclusiones no existe: {:?}",
+                    exclude_from
+                )));
+            }
+        }
 
-        for path in &paths_to_check {
-            if !path.exists() {
-                log::warn!("El directorio no existe: {:?}", path);
+        // Las franjas de --bwlimit-schedule no pueden solaparse
+        let schedule = &self.general.bwlimit_schedule;
+        for (i, a) in schedule.iter().enumerate() {
+            for b in &schedule[i + 1..] {
+                if a.overlaps(b) {
+                    return Err(AppError::Config(format!(
+                        "Franjas solapadas en bwlimit_schedule: {}-{} y {}-{}",
+                        a.from.format("%H:%M"),
+                        a.to.format("%H:%M"),
+                        b.from.format("%H:%M"),
+                        b.to.format("%H:%M")
+                    )));
+                }
+            }
+        }
+
+        if let Some(option) = self.general.rsync_base_options.iter().find(|option| !is_allowed_base_option(option)) {
+            return Err(AppError::Config(format!("Opción de rsync no permitida en rsync_base_options: {}", option)));
+        }
+
+        for (host, host_config) in &self.hosts {
+            for rule in &host_config.filters {
+                crate::cli::parse_filter_rule(rule)
+                    .map_err(|e| AppError::Config(format!("filters de '{}': {}", host, e)))?;
+            }
+            for (item, options) in &host_config.item_options {
+                if let Some(option) = options.iter().find(|option| !is_allowed_item_option(option)) {
+                    return Err(AppError::Config(format!(
+                        "Opción de rsync no permitida en item_options de '{}' ({}): {}",
+                        host, item, option
+                    )));
+                }
+            }
+        }
+
+        let mount_point = &self.general.pcloud_mount_point;
+        if self.ssh.is_some() {
+            return Ok(());
+        }
+        if !mount_point.exists() {
+            log::warn!("El punto de montaje de pCloud no existe: {:?}", mount_point);
+        }
+
+        // Los directorios de backup deben colgar del punto de montaje
+        let backup_dirs = [
+            &self.general.pcloud_backup_comun,
+            &self.general.pcloud_backup_readonly,
+        ];
+        for backup_dir in backup_dirs {
+            if !backup_dir.starts_with(mount_point) {
+                log::warn!(
+                    "El directorio de backup {:?} no está dentro del punto de montaje {:?}",
+                    backup_dir, mount_point
+                );
             }
         }
 
         Ok(())
     }
 
-    pub fn is_host_rtva(&self) -> bool {
-        Self::get_hostname() == "feynman.rtva.dnf"
+    /// Límite de --bwlimit-schedule para la hora `at`; sin franja, sin límite
+    pub fn scheduled_bwlimit(&self, at: NaiveTime) -> Option<u32> {
+        self.general
+            .bwlimit_schedule
+            .iter()
+            .find(|window| window.contains(at))
+            .map(|window| window.limit)
+    }
+
+    /// Host con `use_alternate_crypto_paths`: usa los directorios Crypto
+    /// `*_hostname_rtva_dir` y registra en nivel Debug por defecto
+    pub fn uses_alternate_crypto_paths(&self) -> bool {
+        self.get_current_host_config()
+            .map(|host_config| host_config.use_alternate_crypto_paths)
+            .unwrap_or(false)
+    }
+}
+
+/// Rutas candidatas en orden de prioridad; en cada directorio se prueban
+/// `syncb_config.toml` y `config.toml`
+fn config_search_paths() -> Vec<PathBuf> {
+    let mut dirs_to_search = vec![PathBuf::from(".")];
+
+    // Directorio del ejecutable (como hacía la versión anterior)
+    if let Some(exe_dir) = std::env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf)) {
+        dirs_to_search.push(exe_dir);
+    }
+
+    // XDG en Linux, %APPDATA% en Windows, Application Support en macOS
+    if let Some(config_dir) = dirs::config_dir() {
+        dirs_to_search.push(config_dir.join("syncb"));
+    }
+
+    // ~/.config/syncb también en plataformas donde config_dir es otro
+    if let Some(home_dir) = dirs::home_dir() {
+        dirs_to_search.push(home_dir.join(".config").join("syncb"));
+    }
+
+    #[cfg(unix)]
+    dirs_to_search.push(PathBuf::from("/etc/syncb"));
+
+    dirs_to_search.dedup();
+
+    dirs_to_search
+        .iter()
+        .flat_map(|dir| [dir.join("syncb_config.toml"), dir.join("config.toml")])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_config_with;
+
+    #[test]
+    fn test_bwlimit_schedule() {
+        #[derive(Deserialize)]
+        struct Schedule {
+            bwlimit_schedule: Vec<BwlimitWindow>,
+        }
+        let schedule: Schedule = toml::from_str(r#"
+            bwlimit_schedule = [
+                { from = "09:00", to = "18:00", limit = "2M" },
+                { from = "22:00", to = "06:00", limit = "10M" },
+            ]
+        "#).unwrap();
+        let at = |time: &str| NaiveTime::parse_from_str(time, "%H:%M").unwrap();
+
+        let work = &schedule.bwlimit_schedule[0];
+        assert_eq!(work.limit, 2048);
+        assert!(work.contains(at("09:00")) && work.contains(at("17:59")));
+        assert!(!work.contains(at("18:00")));
+
+        let night = &schedule.bwlimit_schedule[1];
+        assert!(night.contains(at("23:30")) && night.contains(at("05:00")));
+        assert!(!night.contains(at("12:00")));
+
+        for malformed in [
+            r#"bwlimit_schedule = [{ from = "9h", to = "18:00", limit = "2M" }]"#,
+            r#"bwlimit_schedule = [{ from = "09:00", to = "18:00", limit = "mucho" }]"#,
+            r#"bwlimit_schedule = [{ from = "09:00", to = "18:00", limit = "0" }]"#,
+            r#"bwlimit_schedule = [{ from = "09:00", to = "09:00", limit = "2M" }]"#,
+        ] {
+            assert!(toml::from_str::<Schedule>(malformed).is_err());
+        }
+
+        let evening = BwlimitWindow { from: at("18:00"), to: at("22:00"), limit: 1 };
+        assert!(!evening.overlaps(work) && !evening.overlaps(night));
+        let overlapping = BwlimitWindow { from: at("17:00"), to: at("23:00"), limit: 1 };
+        assert!(overlapping.overlaps(night) && overlapping.overlaps(work));
+    }
+
+    #[test]
+    fn test_notification_thresholds() {
+        let default = NotificationsConfig::default();
+        assert!(default.should_notify(false, std::time::Duration::ZERO));
+
+        let config: NotificationsConfig = toml::from_str("notify_on = \"always\"\nmin_duration_seconds = 60").unwrap();
+        assert!(!config.should_notify(false, std::time::Duration::from_secs(59)));
+        assert!(config.should_notify(false, std::time::Duration::from_secs(60)));
+        assert!(config.should_notify(true, std::time::Duration::from_secs(1)));
+
+        let errors: NotificationsConfig = toml::from_str("notify_on = \"errors\"").unwrap();
+        assert!(!errors.should_notify(false, std::time::Duration::from_secs(3600)));
+        assert!(errors.should_notify(true, std::time::Duration::ZERO));
+
+        let never = NotificationsConfig { notify_on: NotifyOn::Never, ..Default::default() };
+        assert!(!never.should_notify(true, std::time::Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn test_item_options_allowlist() {
+        assert!(is_allowed_item_option("--no-perms"));
+        assert!(is_allowed_item_option("--size-only"));
+        assert!(is_allowed_item_option("--chmod=Du+rwx"));
+        assert!(!is_allowed_item_option("--chmod="));
+        assert!(!is_allowed_item_option("--chmod"));
+        assert!(!is_allowed_item_option("--rsh=sh -c 'rm -rf ~'"));
+        assert!(!is_allowed_item_option("--rsync-path=evil"));
+        assert!(!is_allowed_item_option("--remove-source-files"));
+        assert!(!is_allowed_item_option("-e"));
+
+        assert!(is_allowed_base_option("-rtv"));
+        assert!(is_allowed_base_option("-av"));
+        assert!(is_allowed_base_option("--no-perms"));
+        assert!(is_allowed_base_option("--archive"));
+        assert!(is_allowed_base_option("--info=progress2"));
+        assert!(is_allowed_base_option("--timeout=60"));
+        assert!(is_allowed_base_option("--chmod=Du+rwx"));
+        assert!(default_rsync_base_options().iter().all(|option| is_allowed_base_option(option)));
+        assert!(!is_allowed_base_option("--rsh=ssh"));
+        assert!(!is_allowed_base_option("--delete"));
+        assert!(!is_allowed_base_option("rtv"));
+        assert!(!is_allowed_base_option("-"));
+        assert!(!is_allowed_base_option("-avessh"));
+        assert!(!is_allowed_base_option("-avn"));
+        assert!(!is_allowed_base_option("--info=name0"));
+        assert!(!is_allowed_base_option("--timeout="));
+        for option in [
+            "--del", "--remove-sent-files", "--log-file=/tmp/x", "--write-batch=b", "--temp-dir=/tmp",
+            "--backup-dir=/tmp", "--link-dest=/tmp", "--out-format=%n", "--quiet",
+        ] {
+            assert!(!is_allowed_base_option(option), "{}", option);
+        }
+    }
+
+    #[test]
+    fn test_select_remote() {
+        let mut config = test_config_with(Path::new("/home/test"), r#"
+            [[remotes]]
+            name = "personal"
+            mount_point = "/home/test/pCloudDrive"
+            backup_comun = "/home/test/pCloudDrive/Backups/Backup_Comun"
+            backup_readonly = "/home/test/pCloudDrive/pCloud Backup/test"
+
+            [[remotes]]
+            name = "trabajo"
+            mount_point = "/home/test/pCloudTrabajo"
+            backup_comun = "/home/test/pCloudTrabajo/Backups"
+            backup_readonly = "/home/test/pCloudTrabajo/Solo lectura"
+
+            [[remotes]]
+            name = "nas"
+            type = "ssh"
+            host = "nas.local"
+            user = "copias"
+            path = "/srv/copias"
+
+            [[remotes]]
+            name = "incompleto"
+            type = "ssh"
+            path = "/srv/copias"
+
+            [hosts]
+        "#);
+
+        let mut first = config.clone();
+        first.select_remote(None).unwrap();
+        assert_eq!(first.general.pcloud_mount_point, Path::new("/home/test/pCloudDrive"));
+
+        config.general.default_remote = Some("trabajo".to_string());
+        let mut by_default = config.clone();
+        by_default.select_remote(None).unwrap();
+        assert_eq!(by_default.general.pcloud_backup_comun, Path::new("/home/test/pCloudTrabajo/Backups"));
+        assert_eq!(by_default.remote_name.as_deref(), Some("trabajo"));
+
+        let mut explicit = config.clone();
+        explicit.select_remote(Some("personal")).unwrap();
+        assert_eq!(explicit.general.pcloud_mount_point, Path::new("/home/test/pCloudDrive"));
+
+        assert!(config.select_remote(Some("no_existe")).is_err());
+        assert!(config.ssh.is_none());
+
+        // Un remoto SSH usa su ruta como backup común y de solo lectura
+        let mut ssh = config.clone();
+        ssh.select_remote(Some("nas")).unwrap();
+        assert_eq!(ssh.general.pcloud_backup_readonly, Path::new("/srv/copias"));
+        assert_eq!(ssh.ssh.as_ref().unwrap().destination(), "copias@nas.local");
+        assert!(ssh.check_ssh_compatibility(&Cli { subir: true, ..Default::default() }).is_ok());
+        assert!(ssh.check_ssh_compatibility(&Cli { subir: true, snapshot: true, ..Default::default() }).is_err());
+        assert!(ssh.check_ssh_compatibility(&Cli { two_way: true, ..Default::default() }).is_err());
+
+        assert!(config.select_remote(Some("incompleto")).is_err());
+    }
+
+    #[test]
+    fn test_profile_args() {
+        let config = test_config_with(Path::new("/home/test"), r#"
+            [hosts]
+
+            [profiles.nightly]
+            mode = "upload"
+            delete = true
+            yes = true
+            dry_run = false
+            bwlimit = "2M"
+            exclude = ["*.iso", "-raro"]
+            timeout = 60
+
+            [profiles.malo]
+            config = "/otro.toml"
+        "#);
+
+        let mut args = config.profile_args("nightly").unwrap();
+        args.sort();
+        assert_eq!(
+            args,
+            ["--bwlimit=2M", "--delete", "--exclude=*.iso", "--exclude=-raro", "--mode=upload", "--timeout=60", "--yes"]
+        );
+
+        let error = config.profile_args("rapido").unwrap_err().to_string();
+        assert!(error.contains("malo, nightly"));
+        assert!(config.profile_args("malo").is_err());
+    }
+
+    #[test]
+    fn test_config_search_paths_order() {
+        let paths = config_search_paths();
+
+        assert_eq!(paths[0], Path::new(".").join("syncb_config.toml"));
+        assert_eq!(paths[1], Path::new(".").join("config.toml"));
+
+        let exe_dir = std::env::current_exe().unwrap().parent().unwrap().to_path_buf();
+        assert!(paths.contains(&exe_dir.join("config.toml")));
+
+        if let Some(config_dir) = dirs::config_dir() {
+            assert!(paths.contains(&config_dir.join("syncb").join("syncb_config.toml")));
+        }
     }
 }
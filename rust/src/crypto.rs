@@ -1,26 +1,44 @@
+use crate::cli::{Cli, SyncMode};
 use crate::config::AppConfig;
-use crate::error::Result;
+use crate::error::{describe_rsync_exit, AppError, Result};
+use crate::stats::{ItemReport, SyncStats};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
 
 pub struct CryptoManager {
     config: AppConfig,
+    args: Cli,
+}
+
+/// Qué hacer con una base de datos KeePass tras copiarla a un temporal
+/// junto al destino
+#[derive(Debug, PartialEq)]
+enum KeepassAction {
+    /// Ambas copias son iguales
+    Unchanged,
+    /// Reemplazar la copia de destino por la de origen
+    Replace,
+    /// La copia de destino es más reciente: no se toca
+    KeepDestination,
 }
 
 impl CryptoManager {
-    pub fn new(config: AppConfig) -> Self {
-        Self { config }
+    pub fn new(config: AppConfig, args: Cli) -> Self {
+        Self { config, args }
     }
 
-    pub async fn sync_crypto(&self) -> Result<()> {
+    pub async fn sync_crypto(&self, stats: &mut SyncStats) -> Result<()> {
         log::info!("Iniciando sincronización de directorio Crypto");
 
         // Verificar que el volumen Crypto está montado
         self.verify_crypto_mounted().await?;
 
         // Sincronizar directorio principal de Crypto
-        self.sync_main_crypto().await?;
+        self.sync_main_crypto(stats).await?;
 
         // Sincronizar KeePass2Android
-        self.sync_keepass().await?;
+        self.sync_keepass(stats).await?;
 
         log::info!("Sincronización Crypto completada");
         Ok(())
@@ -31,8 +49,15 @@ impl CryptoManager {
             .join(&self.config.general.crypto.cloud_mount_check_file);
 
         if !check_file.exists() {
+            if self.args.no_crypto_mount_check {
+                log::warn!(
+                    "¡ATENCIÓN! No existe {:?}: se continúa sin verificar que Crypto está montado (--no-crypto-mount-check)",
+                    check_file
+                );
+                return Ok(());
+            }
             return Err(crate::error::AppError::Crypto(
-                "El volumen Crypto no está montado o el archivo de verificación no existe".to_string()
+                "El volumen Crypto no está montado o el archivo de verificación no existe (use --no-crypto-mount-check si es intencionado)".to_string()
             ));
         }
 
@@ -40,42 +65,305 @@ impl CryptoManager {
         Ok(())
     }
 
-    async fn sync_main_crypto(&self) -> Result<()> {
-        let (source, destination) = if self.config.is_host_rtva() {
-            (
-                self.config.general.crypto.local_crypto_hostname_rtva_dir.clone(),
-                self.config.general.crypto.remote_crypto_hostname_rtva_dir.clone(),
-            )
+    /// Origen y destino del directorio Crypto en el sentido de la
+    /// sincronización; `None` con --two-way, que no lo admite
+    fn main_crypto_paths(&self) -> Option<(PathBuf, PathBuf)> {
+        let crypto = &self.config.general.crypto;
+        let (local, remote) = if self.config.uses_alternate_crypto_paths() {
+            (&crypto.local_crypto_hostname_rtva_dir, &crypto.remote_crypto_hostname_rtva_dir)
         } else {
-            (
-                self.config.general.crypto.local_crypto_dir.clone(),
-                self.config.general.crypto.remote_crypto_dir.clone(),
-            )
+            (&crypto.local_crypto_dir, &crypto.remote_crypto_dir)
         };
 
+        match self.args.get_mode() {
+            SyncMode::Upload => Some((local.clone(), remote.clone())),
+            SyncMode::Download => Some((remote.clone(), local.clone())),
+            SyncMode::TwoWay => None,
+        }
+    }
+
+    async fn sync_main_crypto(&self, stats: &mut SyncStats) -> Result<()> {
+        let Some((source, destination)) = self.main_crypto_paths() else {
+            log::warn!("--two-way no sincroniza el directorio Crypto; solo KeePass");
+            return Ok(());
+        };
+
+        if !source.is_dir() {
+            log::warn!("Directorio Crypto de origen no encontrado: {}", source.display());
+            return Ok(());
+        }
+
         log::info!("Sincronizando Crypto: {:?} -> {:?}", source, destination);
+        // Como KeePass, tras los elementos y sin otras transferencias a la vez
+        let command = build_crypto_command(
+            &self.config.general.rsync_base_options,
+            &self.args.rsync_bwlimit_args(1),
+            &source,
+            &destination,
+            self.args.dry_run,
+        );
+        let output = tokio::process::Command::from(command).output().await?;
 
-        // Implementar lógica de sincronización específica para Crypto
-        // Similar a la sincronización principal pero con opciones específicas
+        if !output.status.success() {
+            return Err(AppError::Crypto(format!(
+                "Error sincronizando {} ({}): {}",
+                source.display(),
+                describe_rsync_exit(output.status.code()),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
 
+        let report = ItemReport::from_itemize_output("Crypto", &String::from_utf8_lossy(&output.stdout));
+        let transferred = report.created_count() + report.updated_count();
+        stats.crypto_files_transferred += transferred as u32;
+        log::info!("Crypto: {} cambios", transferred);
         Ok(())
     }
 
-    async fn sync_keepass(&self) -> Result<()> {
+    /// Origen y destino de KeePass: al subir, de local a pCloud; en otro
+    /// caso (también con --two-way), de pCloud a local
+    fn keepass_dirs(&self) -> (&Path, &Path) {
+        let crypto = &self.config.general.crypto;
+        match self.args.get_mode() {
+            SyncMode::Upload => (crypto.local_keepass_dir.as_path(), crypto.remote_keepass_dir.as_path()),
+            SyncMode::Download | SyncMode::TwoWay => (crypto.remote_keepass_dir.as_path(), crypto.local_keepass_dir.as_path()),
+        }
+    }
+
+    /// Bases de datos KeePass en el sentido de `keepass_dirs`; nunca se
+    /// pisa una copia más reciente
+    async fn sync_keepass(&self, stats: &mut SyncStats) -> Result<()> {
         log::info!("Sincronizando KeePass2Android");
 
-        // Sincronizar desde pCloud -> local para KeePass
-        let _source = self.config.general.crypto.remote_keepass_dir.clone();
-        let destination = self.config.general.crypto.local_keepass_dir.clone();
+        let (source, destination) = self.keepass_dirs();
+        if !source.is_dir() {
+            log::warn!("Directorio KeePass de origen no encontrado: {}", source.display());
+            return Ok(());
+        }
 
         // Crear directorio destino si no existe
-        if let Some(parent) = destination.parent() {
-            std::fs::create_dir_all(parent)?;
+        if !self.args.dry_run {
+            std::fs::create_dir_all(destination)?;
         }
 
-        // Implementar sincronización de KeePass
-        // Usar rsync con opciones específicas
+        for entry in std::fs::read_dir(source)? {
+            let source_file = entry?.path();
+            if !source_file.is_file() || source_file.extension().is_none_or(|ext| ext != "kdbx") {
+                continue;
+            }
+
+            if let Some(name) = source_file.file_name() {
+                self.sync_keepass_file(&source_file, &destination.join(name), stats).await?;
+            }
+        }
 
         Ok(())
     }
+
+    /// Copia una base de datos a un temporal junto al destino y solo la
+    /// coloca en su sitio (rename atómico) si no pisa cambios del destino.
+    async fn sync_keepass_file(&self, source: &Path, destination: &Path, stats: &mut SyncStats) -> Result<()> {
+        if self.args.dry_run {
+            println!("KeePass (simulación): {} -> {}", source.display(), destination.display());
+            return Ok(());
+        }
+
+        let file_name = destination.file_name().unwrap_or_default().to_string_lossy();
+        let temp = destination.with_file_name(format!(".{}.syncb-tmp", file_name));
+
+        // Se ejecuta tras los elementos, sin otras transferencias a la vez
+        let bwlimit = self.args.rsync_bwlimit_args(1);
+        let output = tokio::process::Command::from(build_keepass_command(source, &temp, &bwlimit))
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            let _ = std::fs::remove_file(&temp);
+            return Err(AppError::Crypto(format!(
+                "Error copiando {}: {}",
+                source.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        let action = keepass_action(file_signature(destination), file_signature(&temp), self.args.overwrite);
+        match action {
+            KeepassAction::Unchanged => {
+                log::debug!("KeePass sin cambios: {}", destination.display());
+                std::fs::remove_file(&temp)?;
+            }
+            KeepassAction::KeepDestination => {
+                log::warn!(
+                    "{} es más reciente que {}; no se sobrescribe (use --overwrite para forzarlo)",
+                    destination.display(),
+                    source.display()
+                );
+                std::fs::remove_file(&temp)?;
+            }
+            KeepassAction::Replace => {
+                std::fs::rename(&temp, destination)?;
+                stats.crypto_files_transferred += 1;
+                log::info!("KeePass actualizado: {}", destination.display());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// rsync del directorio Crypto con las opciones base de la configuración;
+/// nunca borra en el destino
+fn build_crypto_command(
+    base_options: &[String],
+    bwlimit: &[String],
+    source: &Path,
+    destination: &Path,
+    dry_run: bool,
+) -> Command {
+    let mut cmd = Command::new("rsync");
+    cmd.args(base_options.iter().filter(|option| *option != "--progress"));
+    if !crate::sync::has_rsync_option(base_options, "--itemize-changes", 'i') {
+        cmd.arg("--itemize-changes");
+    }
+    cmd.args(bwlimit);
+    if dry_run {
+        cmd.arg("--dry-run");
+    }
+    cmd.args(crate::sync::rsync_paths(source, destination));
+    cmd
+}
+
+fn build_keepass_command(source: &Path, temp: &Path, bwlimit: &[String]) -> Command {
+    let mut cmd = Command::new("rsync");
+    // -t conserva la fecha de modificación para poder compararla con la del destino
+    cmd.arg("-t").args(bwlimit).arg(source).arg(temp);
+    cmd
+}
+
+fn file_signature(path: &Path) -> Option<(u64, SystemTime)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+fn keepass_action(
+    destination: Option<(u64, SystemTime)>,
+    source: Option<(u64, SystemTime)>,
+    overwrite: bool,
+) -> KeepassAction {
+    let Some((destination_size, destination_mtime)) = destination else {
+        return KeepassAction::Replace;
+    };
+    let Some((source_size, source_mtime)) = source else {
+        return KeepassAction::KeepDestination;
+    };
+
+    if destination_size == source_size && destination_mtime == source_mtime {
+        KeepassAction::Unchanged
+    } else if destination_mtime > source_mtime && !overwrite {
+        KeepassAction::KeepDestination
+    } else {
+        KeepassAction::Replace
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_config;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_crypto_mount_check_can_be_skipped() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = test_config(temp_dir.path());
+
+        let strict = CryptoManager::new(config.clone(), Cli { crypto: true, ..Default::default() });
+        assert!(matches!(strict.verify_crypto_mounted().await, Err(AppError::Crypto(_))));
+
+        let args = Cli {
+            crypto: true,
+            no_crypto_mount_check: true,
+            ..Default::default()
+        };
+        assert!(CryptoManager::new(config, args).verify_crypto_mounted().await.is_ok());
+    }
+
+    #[test]
+    fn test_main_crypto_command_honours_bwlimit() {
+        let args = Cli { bwlimit: Some(2048), parallel: 4, ..Default::default() };
+        let cmd = build_crypto_command(
+            &["-av".to_string(), "--progress".to_string()],
+            &args.rsync_bwlimit_args(1),
+            Path::new("/l/Crypto"),
+            Path::new("/r/Crypto"),
+            false,
+        );
+        assert_eq!(argv(&cmd), ["-av", "--itemize-changes", "--bwlimit", "2048", "/l/Crypto", "/r/Crypto"]);
+    }
+
+    #[test]
+    fn test_keepass_command_honours_bwlimit() {
+        let args = Cli { bwlimit: Some(2048), parallel: 4, ..Default::default() };
+        let cmd = build_keepass_command(Path::new("/r/a.kdbx"), Path::new("/l/.a.kdbx.syncb-tmp"), &args.rsync_bwlimit_args(1));
+        let argv: Vec<_> = cmd.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect();
+        assert_eq!(argv, ["-t", "--bwlimit", "2048", "/r/a.kdbx", "/l/.a.kdbx.syncb-tmp"]);
+    }
+
+    fn argv(cmd: &Command) -> Vec<String> {
+        cmd.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn test_main_crypto_follows_sync_direction() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = test_config(temp_dir.path());
+        let local = config.general.crypto.local_crypto_dir.clone();
+        let remote = config.general.crypto.remote_crypto_dir.clone();
+        std::fs::create_dir_all(&local).unwrap();
+        std::fs::create_dir_all(&remote).unwrap();
+        let slash = |path: &Path| format!("{}/", path.display());
+
+        let upload = CryptoManager::new(config.clone(), Cli { subir: true, crypto_only: true, ..Default::default() });
+        let (source, destination) = upload.main_crypto_paths().unwrap();
+        assert_eq!((&source, &destination), (&local, &remote));
+        let args = argv(&build_crypto_command(&["-a".to_string()], &[], &source, &destination, false));
+        assert_eq!(args, ["-a", "--itemize-changes", &slash(&local), &slash(&remote)]);
+
+        let download = CryptoManager::new(config.clone(), Cli { bajar: true, dry_run: true, ..Default::default() });
+        let (source, destination) = download.main_crypto_paths().unwrap();
+        assert_eq!((&source, &destination), (&remote, &local));
+        let args = argv(&build_crypto_command(&["-ai".to_string()], &[], &source, &destination, true));
+        assert_eq!(args, ["-ai", "--dry-run", &slash(&remote), &slash(&local)]);
+
+        assert!(CryptoManager::new(config, Cli { two_way: true, ..Default::default() }).main_crypto_paths().is_none());
+    }
+
+    #[test]
+    fn test_keepass_follows_sync_direction() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = test_config(temp_dir.path());
+        let local = config.general.crypto.local_keepass_dir.clone();
+        let remote = config.general.crypto.remote_keepass_dir.clone();
+
+        let upload = CryptoManager::new(config.clone(), Cli { subir: true, ..Default::default() });
+        assert_eq!(upload.keepass_dirs(), (local.as_path(), remote.as_path()));
+
+        let download = CryptoManager::new(config.clone(), Cli { bajar: true, ..Default::default() });
+        assert_eq!(download.keepass_dirs(), (remote.as_path(), local.as_path()));
+
+        let two_way = CryptoManager::new(config, Cli { two_way: true, ..Default::default() });
+        assert_eq!(two_way.keepass_dirs(), (remote.as_path(), local.as_path()));
+    }
+
+    #[test]
+    fn test_keepass_action_protects_newer_destination_copy() {
+        let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let new = old + Duration::from_secs(60);
+
+        assert_eq!(keepass_action(None, Some((10, old)), false), KeepassAction::Replace);
+        assert_eq!(keepass_action(Some((10, old)), Some((10, old)), false), KeepassAction::Unchanged);
+        assert_eq!(keepass_action(Some((10, old)), Some((12, new)), false), KeepassAction::Replace);
+        assert_eq!(keepass_action(Some((12, new)), Some((10, old)), false), KeepassAction::KeepDestination);
+        assert_eq!(keepass_action(Some((12, new)), Some((10, old)), true), KeepassAction::Replace);
+    }
 }
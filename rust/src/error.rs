@@ -15,6 +15,14 @@ pub enum AppError {
     #[error("Sync error: {0}")]
     Sync(String),
 
+    /// rsync terminó con error; `code` es su código de salida (None si lo mató una señal)
+    #[error("rsync failed for {item} ({}): {message}", describe_rsync_exit(*.code))]
+    Rsync {
+        item: String,
+        code: Option<i32>,
+        message: String,
+    },
+
     #[error("Crypto error: {0}")]
     Crypto(String),
 
@@ -36,8 +44,83 @@ pub enum AppError {
     #[error("Insufficient disk space: {0}")]
     InsufficientSpace(String),
 
-    #[error("Process already running (PID: {0})")]
-    AlreadyRunning(u32),
+    /// Falta un programa externo; el mensaje indica cuál y cómo instalarlo
+    #[error("Missing dependency: {0}")]
+    DependencyMissing(String),
+
+    #[error("Destination not writable: {}", .0.display())]
+    NotWritable(PathBuf),
+
+    #[error("Operation cancelled by user")]
+    Cancelled,
+
+    #[error("Process already running (PID: {pid}, host: {hostname})")]
+    AlreadyRunning { pid: u32, hostname: String },
+}
+
+impl AppError {
+    /// Código de salida del proceso para cada categoría de error, para que los
+    /// scripts puedan decidir si reintentar, avisar o esperar:
+    ///
+    /// | Código | Error |
+    /// |--------|-------|
+    /// | 1   | Genérico |
+    /// | 2   | Configuración |
+    /// | 10  | Ya hay otra ejecución en curso (lock) |
+    /// | 11  | pCloud no montado |
+    /// | 12  | Tiempo límite excedido |
+    /// | 13  | Espacio en disco insuficiente |
+    /// | 14  | Errores de transferencia/verificación |
+    /// | 15  | Falta una dependencia (rsync, curl) |
+    /// | 16  | Destino sin permiso de escritura |
+    /// | 130 | Cancelado por el usuario |
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            AppError::Config(_) => 2,
+            AppError::AlreadyRunning { .. } => 10,
+            AppError::PCloudNotMounted(_) => 11,
+            AppError::Timeout(_) => 12,
+            AppError::InsufficientSpace(_) => 13,
+            AppError::Sync(_) | AppError::Rsync { .. } => 14,
+            AppError::DependencyMissing(_) => 15,
+            AppError::NotWritable(_) => 16,
+            AppError::Cancelled => 130,
+            _ => 1,
+        }
+    }
+}
+
+impl AppError {
+    /// Código de salida de rsync si el error procede de una transferencia
+    pub fn rsync_exit_code(&self) -> Option<i32> {
+        match self {
+            AppError::Rsync { code, .. } => *code,
+            _ => None,
+        }
+    }
+}
+
+/// Código de salida de rsync con su significado (ver "EXIT VALUES" en man rsync)
+pub fn describe_rsync_exit(code: Option<i32>) -> String {
+    let Some(code) = code else {
+        return "terminado por una señal".to_string();
+    };
+    let meaning = match code {
+        1 => "error de sintaxis o de uso",
+        2 => "protocolo incompatible",
+        3 => "error seleccionando archivos o directorios",
+        5 => "error iniciando el protocolo",
+        10 => "error de E/S en el socket",
+        11 => "error de E/S en un archivo",
+        12 => "error en el flujo de datos",
+        20 => "recibió SIGUSR1 o SIGINT",
+        23 => "transferencia parcial por errores",
+        24 => "transferencia parcial: archivos desaparecidos en origen",
+        30 => "tiempo de espera agotado en envío/recepción",
+        35 => "tiempo de espera agotado en la conexión",
+        _ => "error desconocido",
+    };
+    format!("código {}: {}", code, meaning)
 }
 
 impl From<toml::de::Error> for AppError {
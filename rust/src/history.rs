@@ -0,0 +1,168 @@
+use crate::cli::Cli;
+use crate::config::AppConfig;
+use crate::error::{AppError, Result};
+use crate::stats::{format_bytes, SyncStats};
+use chrono::{DateTime, Local};
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+/// Tamaño a partir del cual se rota el historial
+const HISTORY_MAX_SIZE: u64 = 5 * 1024 * 1024; // 5MB
+
+/// Registro de una ejecución, una línea JSON por ejecución en el historial
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub timestamp: DateTime<Local>,
+    pub mode: String,
+    pub host: String,
+    pub dry_run: bool,
+    pub items_processed: u32,
+    pub files_transferred: u32,
+    pub bytes_transferred: u64,
+    pub sync_errors: u32,
+    pub duration_secs: f64,
+}
+
+impl HistoryEntry {
+    pub fn from_stats(args: &Cli, stats: &SyncStats) -> Self {
+        let duration = stats.start_time.map(|t| t.elapsed()).unwrap_or_default();
+
+        Self {
+            timestamp: Local::now(),
+            mode: format!("{:?}", args.get_mode()),
+            host: AppConfig::get_hostname(),
+            dry_run: args.dry_run,
+            items_processed: stats.items_processed,
+            files_transferred: stats.files_transferred,
+            bytes_transferred: stats.bytes_transferred,
+            sync_errors: stats.sync_errors,
+            duration_secs: duration.as_secs_f64(),
+        }
+    }
+}
+
+/// Añade una entrada al historial con un bloqueo exclusivo sobre el archivo
+pub fn append(history_file: &Path, entry: &HistoryEntry) -> Result<()> {
+    if let Some(parent) = history_file.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    rotate_if_needed(history_file)?;
+
+    let mut line = serde_json::to_string(entry)
+        .map_err(|e| AppError::Config(format!("Error serializando historial: {}", e)))?;
+    line.push('\n');
+
+    let mut file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(history_file)?;
+
+    file.lock_exclusive()?;
+    let result = file.write_all(line.as_bytes());
+    file.unlock()?;
+    result?;
+
+    Ok(())
+}
+
+/// Lee las últimas `count` entradas del historial (las líneas corruptas se ignoran)
+pub fn read_last(history_file: &Path, count: usize) -> Result<Vec<HistoryEntry>> {
+    if !history_file.exists() {
+        return Ok(Vec::new());
+    }
+
+    let contents = std::fs::read_to_string(history_file)?;
+    let entries: Vec<HistoryEntry> = contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+
+    let skip = entries.len().saturating_sub(count);
+    Ok(entries.into_iter().skip(skip).collect())
+}
+
+pub fn show(history_file: &Path, count: usize) -> Result<()> {
+    let entries = read_last(history_file, count)?;
+
+    if entries.is_empty() {
+        println!("No hay ejecuciones registradas en {:?}", history_file);
+        return Ok(());
+    }
+
+    println!(
+        "{:<19}  {:<8}  {:<20}  {:>5}  {:>8}  {:>12}  {:>7}  {:>9}",
+        "FECHA", "MODO", "HOST", "ELEM", "ARCHIVOS", "DATOS", "ERRORES", "DURACIÓN"
+    );
+    for entry in &entries {
+        let mode = if entry.dry_run {
+            format!("{}*", entry.mode)
+        } else {
+            entry.mode.clone()
+        };
+
+        println!(
+            "{:<19}  {:<8}  {:<20}  {:>5}  {:>8}  {:>12}  {:>7}  {:>8.1}s",
+            entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            mode,
+            entry.host,
+            entry.items_processed,
+            entry.files_transferred,
+            format_bytes(entry.bytes_transferred),
+            entry.sync_errors,
+            entry.duration_secs
+        );
+    }
+    println!("(* = simulación con --dry-run)");
+
+    Ok(())
+}
+
+fn rotate_if_needed(history_file: &Path) -> Result<()> {
+    if let Ok(metadata) = std::fs::metadata(history_file) {
+        if metadata.len() > HISTORY_MAX_SIZE {
+            let rotated_name = format!("{}.old", history_file.display());
+            std::fs::rename(history_file, rotated_name)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entry(files_transferred: u32) -> HistoryEntry {
+        HistoryEntry {
+            timestamp: Local::now(),
+            mode: "Upload".to_string(),
+            host: "test".to_string(),
+            dry_run: false,
+            items_processed: 1,
+            files_transferred,
+            bytes_transferred: 1024,
+            sync_errors: 0,
+            duration_secs: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_append_and_read_last() {
+        let temp_dir = TempDir::new().unwrap();
+        let history_file = temp_dir.path().join("sub/history.jsonl");
+
+        for files in 1..=5 {
+            append(&history_file, &entry(files)).unwrap();
+        }
+
+        let last = read_last(&history_file, 2).unwrap();
+        assert_eq!(last.len(), 2);
+        assert_eq!(last[0].files_transferred, 4);
+        assert_eq!(last[1].files_transferred, 5);
+    }
+}
@@ -0,0 +1,108 @@
+use crate::cli::{Cli, SyncMode};
+use crate::config::AppConfig;
+use crate::error::{AppError, Result};
+use crate::stats::SyncStats;
+use std::process::Command;
+
+/// Ejecuta los `pre_sync_hooks` del host; el primero que falle aborta la sincronización
+pub fn run_pre_sync(args: &Cli, config: &AppConfig) -> Result<()> {
+    let hooks = &config.get_current_host_config()?.pre_sync_hooks;
+    let env = hook_env(args, &SyncStats::new());
+
+    for hook in hooks {
+        if args.dry_run {
+            println!("Hook previo (simulación): {}", hook);
+            continue;
+        }
+
+        log::info!("Ejecutando hook previo: {}", hook);
+        let status = shell_command(hook).envs(env.iter().cloned()).status()?;
+        if !status.success() {
+            return Err(AppError::Sync(format!(
+                "El hook previo '{}' falló ({})",
+                hook, status
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Ejecuta los `post_sync_hooks` del host; los fallos solo se registran como aviso
+pub fn run_post_sync(args: &Cli, config: &AppConfig, stats: &SyncStats) {
+    let hooks = match config.get_current_host_config() {
+        Ok(host_config) => &host_config.post_sync_hooks,
+        Err(_) => return,
+    };
+    let env = hook_env(args, stats);
+
+    for hook in hooks {
+        if args.dry_run {
+            println!("Hook posterior (simulación): {}", hook);
+            continue;
+        }
+
+        log::info!("Ejecutando hook posterior: {}", hook);
+        match shell_command(hook).envs(env.iter().cloned()).status() {
+            Ok(status) if status.success() => {}
+            Ok(status) => log::warn!("El hook posterior '{}' falló ({})", hook, status),
+            Err(e) => log::warn!("No se pudo ejecutar el hook posterior '{}': {}", hook, e),
+        }
+    }
+}
+
+fn hook_env(args: &Cli, stats: &SyncStats) -> Vec<(&'static str, String)> {
+    let mode = match args.get_mode() {
+        SyncMode::Upload => "subir",
+        SyncMode::Download => "bajar",
+        SyncMode::TwoWay => "dos-vias",
+    };
+
+    vec![
+        ("SYNCB_MODE", mode.to_string()),
+        ("SYNCB_HOST", AppConfig::get_hostname()),
+        ("SYNCB_DRY_RUN", (args.dry_run as u8).to_string()),
+        ("SYNCB_ITEMS_PROCESSED", stats.items_processed.to_string()),
+        ("SYNCB_FILES_TRANSFERRED", stats.files_transferred.to_string()),
+        ("SYNCB_FILES_UNCHANGED", stats.files_unchanged.to_string()),
+        ("SYNCB_BYTES_TRANSFERRED", stats.bytes_transferred.to_string()),
+        ("SYNCB_FILES_DELETED", stats.files_deleted.to_string()),
+        ("SYNCB_SYNC_ERRORS", stats.sync_errors.to_string()),
+    ]
+}
+
+#[cfg(unix)]
+fn shell_command(hook: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(hook);
+    cmd
+}
+
+#[cfg(windows)]
+fn shell_command(hook: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(hook);
+    cmd
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hooks_receive_sync_environment() {
+        let mut stats = SyncStats::new();
+        stats.files_transferred = 3;
+        let args = Cli {
+            subir: true,
+            ..Default::default()
+        };
+
+        let output = shell_command("echo \"$SYNCB_MODE $SYNCB_FILES_TRANSFERRED\"")
+            .envs(hook_env(&args, &stats))
+            .output()
+            .unwrap();
+
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "subir 3");
+    }
+}
@@ -0,0 +1,172 @@
+use crate::error::Result;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// Regla de filtro de rsync (`+ patrón` / `- patrón`) traducida de un archivo
+/// con sintaxis de .gitignore
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterRule {
+    pub include: bool,
+    pub pattern: String,
+}
+
+impl FilterRule {
+    /// Valor para `--filter=`
+    pub fn to_rsync(&self) -> String {
+        format!("{} {}", if self.include { '+' } else { '-' }, self.pattern)
+    }
+}
+
+/// Traduce un archivo .gitignore ubicado en `base` (relativo a la raíz de la
+/// transferencia, "" para la raíz) a reglas de rsync.
+///
+/// En .gitignore gana la última regla que coincide y en rsync la primera, así
+/// que las reglas salen en orden inverso. `!patrón` pasa a ser una inclusión,
+/// `dir/` sigue aplicándose solo a directorios y un patrón con `/` al
+/// principio o en medio queda anclado al directorio del archivo.
+pub fn parse(contents: &str, base: &str) -> Vec<FilterRule> {
+    let base = base.trim_matches('/');
+    let mut rules = Vec::new();
+
+    for line in contents.lines() {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (include, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        // \! y \# al principio son literales
+        let line = line.strip_prefix('\\').unwrap_or(line);
+
+        let (body, dir_only) = match line.strip_suffix('/') {
+            Some(body) => (body, "/"),
+            None => (line, ""),
+        };
+        if body.is_empty() {
+            continue;
+        }
+
+        for pattern in translate(body, base) {
+            rules.push(FilterRule {
+                include,
+                pattern: format!("{}{}", pattern, dir_only),
+            });
+        }
+    }
+
+    rules.reverse();
+    rules
+}
+
+/// Patrones de rsync equivalentes a `body` (sin la `/` final) en `base`
+fn translate(body: &str, base: &str) -> Vec<String> {
+    let prefix = if base.is_empty() { String::new() } else { format!("/{}", base) };
+
+    // "**/x" equivale a "x" en cualquier nivel
+    let (anywhere, body) = match body.strip_prefix("**/") {
+        Some(rest) => (true, rest),
+        None => (false, body),
+    };
+
+    let anchored = !anywhere && body.contains('/');
+    if anchored {
+        return vec![format!("{}/{}", prefix, body.trim_start_matches('/'))];
+    }
+
+    if prefix.is_empty() && !body.contains('/') {
+        // Sin barra, rsync ya lo compara con el último componente a cualquier profundidad
+        return vec![body.to_string()];
+    }
+
+    vec![format!("{}/{}", prefix, body), format!("{}/**/{}", prefix, body)]
+}
+
+/// Busca archivos de ignorados con alguno de `names` bajo `root` y devuelve
+/// sus reglas para `--filter`. Los más profundos van primero: en rsync la
+/// primera regla gana y un archivo anidado debe prevalecer sobre el del padre.
+pub fn collect(root: &Path, names: &[String]) -> Result<Vec<String>> {
+    if names.is_empty() || !root.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut found = Vec::new();
+    for entry in WalkDir::new(root).follow_links(false).sort_by_file_name() {
+        let entry = entry.map_err(std::io::Error::from)?;
+        let name = entry.file_name().to_string_lossy();
+        if !entry.file_type().is_file() || !names.iter().any(|n| *n == name) {
+            continue;
+        }
+
+        let base = entry
+            .path()
+            .parent()
+            .and_then(|parent| parent.strip_prefix(root).ok())
+            .map(|base| base.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        found.push((entry.depth(), base, std::fs::read_to_string(entry.path())?));
+    }
+
+    // sort estable: a igual profundidad se conserva el orden por nombre
+    found.sort_by_key(|(depth, _, _)| std::cmp::Reverse(*depth));
+
+    Ok(found
+        .iter()
+        .flat_map(|(_, base, contents)| parse(contents, base))
+        .map(|rule| rule.to_rsync())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn rsync_rules(contents: &str, base: &str) -> Vec<String> {
+        parse(contents, base).iter().map(FilterRule::to_rsync).collect()
+    }
+
+    #[test]
+    fn test_negation_is_checked_before_the_exclusion() {
+        // En .gitignore gana la última: keep.log se sincroniza
+        let rules = rsync_rules("# registros\n*.log\n!keep.log\n", "");
+        assert_eq!(rules, vec!["+ keep.log", "- *.log"]);
+    }
+
+    #[test]
+    fn test_directory_only_and_anchoring() {
+        assert_eq!(rsync_rules("build/", ""), vec!["- build/"]);
+        assert_eq!(rsync_rules("/solo-raiz", ""), vec!["- /solo-raiz"]);
+        assert_eq!(rsync_rules("doc/interno.txt", ""), vec!["- /doc/interno.txt"]);
+        assert_eq!(rsync_rules("**/cache/", ""), vec!["- cache/"]);
+        assert_eq!(rsync_rules("\\#no-es-comentario", ""), vec!["- #no-es-comentario"]);
+        assert_eq!(rsync_rules("\\!literal", ""), vec!["- !literal"]);
+    }
+
+    #[test]
+    fn test_nested_file_is_scoped_to_its_directory() {
+        assert_eq!(rsync_rules("/local", "sub/dir"), vec!["- /sub/dir/local"]);
+        assert_eq!(
+            rsync_rules("tmp/", "sub"),
+            vec!["- /sub/**/tmp/", "- /sub/tmp/"]
+        );
+    }
+
+    #[test]
+    fn test_collect_puts_deeper_files_first() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("sub")).unwrap();
+        std::fs::write(temp_dir.path().join(".syncignore"), "*.log\n").unwrap();
+        std::fs::write(temp_dir.path().join("sub/.syncignore"), "!importante.log\n").unwrap();
+        std::fs::write(temp_dir.path().join("sub/.gitignore"), "ignorado\n").unwrap();
+
+        let rules = collect(temp_dir.path(), &[".syncignore".to_string()]).unwrap();
+        assert_eq!(
+            rules,
+            vec!["+ /sub/**/importante.log", "+ /sub/importante.log", "- *.log"]
+        );
+        assert!(collect(temp_dir.path(), &[]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+use crate::cli::Cli;
+use crate::config::AppConfig;
+use crate::error::{AppError, Result};
+use crate::utils::expand_path;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+/// Respuestas del asistente de --init-config
+#[derive(Debug, Clone, PartialEq)]
+pub struct InitAnswers {
+    pub local_dir: String,
+    pub pcloud_mount_point: String,
+    pub host: String,
+    pub sync_items: Vec<String>,
+}
+
+impl InitAnswers {
+    /// Valores por defecto: el home, `~/pCloudDrive`, este equipo y los
+    /// elementos de --item (o "Documentos")
+    pub fn defaults(args: &Cli) -> Result<Self> {
+        Ok(Self {
+            local_dir: "~".to_string(),
+            pcloud_mount_point: "~/pCloudDrive".to_string(),
+            host: AppConfig::get_hostname(),
+            sync_items: args.selected_items()?.unwrap_or_else(|| vec!["Documentos".to_string()]),
+        })
+    }
+}
+
+/// Dónde se crea la configuración: --config o `<config XDG>/syncb/syncb_config.toml`
+fn target_path(args: &Cli) -> Result<PathBuf> {
+    if let Some(path) = &args.config {
+        return Ok(expand_path(path));
+    }
+    dirs::config_dir()
+        .map(|dir| dir.join("syncb").join("syncb_config.toml"))
+        .ok_or_else(|| AppError::Config("No se encuentra el directorio de configuración del usuario".to_string()))
+}
+
+/// --init-config: pregunta lo imprescindible (sin terminal o con --yes usa
+/// los valores por defecto) y escribe una configuración inicial. No
+/// sobrescribe una existente sin --force. Devuelve la ruta creada.
+pub fn run(args: &Cli) -> Result<PathBuf> {
+    let path = target_path(args)?;
+    if path.exists() && !args.force {
+        return Err(AppError::Config(format!(
+            "Ya existe {}; use --force para sobrescribirla",
+            path.display()
+        )));
+    }
+
+    let mut answers = InitAnswers::defaults(args)?;
+    if !args.yes && std::io::stdin().is_terminal() {
+        let mut input = std::io::stdin().lock();
+        answers.local_dir = prompt(&mut input, "Directorio local", &answers.local_dir)?;
+        answers.pcloud_mount_point = prompt(&mut input, "Punto de montaje de pCloud", &answers.pcloud_mount_point)?;
+        answers.host = prompt(&mut input, "Nombre del equipo", &answers.host)?;
+        let items = prompt(&mut input, "Elementos a sincronizar (separados por comas)", &answers.sync_items.join(", "))?;
+        answers.sync_items = items.split(',').map(str::trim).filter(|item| !item.is_empty()).map(String::from).collect();
+    }
+
+    for (name, value) in [("local_dir", &answers.local_dir), ("pcloud_mount_point", &answers.pcloud_mount_point)] {
+        let expanded = expand_path(Path::new(value));
+        if !expanded.exists() {
+            println!("Aviso: {} no existe todavía: {}", name, expanded.display());
+        }
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, render(&answers))?;
+    Ok(path)
+}
+
+/// Pregunta con un valor por defecto entre corchetes; vacío lo acepta
+fn prompt(input: &mut impl BufRead, label: &str, default: &str) -> Result<String> {
+    print!("{} [{}]: ", label, default);
+    std::io::stdout().flush()?;
+
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+    match line.trim() {
+        "" => Ok(default.to_string()),
+        answer => Ok(answer.to_string()),
+    }
+}
+
+/// Configuración mínima válida con el resto de valores por defecto. Las
+/// cadenas se escriben con el formato de TOML para que cualquier ruta o
+/// nombre de equipo se lea tal cual.
+pub fn render(answers: &InitAnswers) -> String {
+    let quote = |value: &str| toml::Value::String(value.to_string()).to_string();
+    let mount = answers.pcloud_mount_point.trim_end_matches('/');
+    let host_key = match answers.host.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        true if !answers.host.is_empty() => answers.host.clone(),
+        _ => quote(&answers.host),
+    };
+    let items: Vec<String> = answers.sync_items.iter().map(|item| format!("    {},\n", quote(item))).collect();
+
+    format!(
+        r#"# Generado con syncb --init-config; el resto de opciones, en el config.toml de ejemplo
+[general]
+local_dir = {local_dir}
+pcloud_mount_point = {mount}
+pcloud_backup_comun = {comun}
+pcloud_backup_readonly = {readonly}
+log_file = "~/.local/share/syncb/syncb.log"
+lock_file = "/tmp/syncb.lock"
+lock_timeout_seconds = 3600
+default_timeout_minutes = 30
+
+[general.crypto]
+local_crypto_dir = "~/Crypto"
+remote_crypto_dir = {crypto}
+cloud_mount_check_file = "mount.check"
+local_keepass_dir = "~/Crypto/Keepass2Android"
+remote_keepass_dir = {keepass}
+local_crypto_hostname_rtva_dir = "~/Crypto/ficheros_sensibles"
+remote_crypto_hostname_rtva_dir = {crypto_rtva}
+
+[hosts.{host_key}]
+exclusions = ["*.tmp", "*.swp", ".cache/"]
+sync_items = [
+{items}]
+"#,
+        local_dir = quote(&answers.local_dir),
+        mount = quote(mount),
+        comun = quote(&format!("{}/Backups/Backup_Comun", mount)),
+        readonly = quote(&format!("{}/pCloud Backup/{}", mount, answers.host)),
+        crypto = quote(&format!("{}/Crypto Folder", mount)),
+        keepass = quote(&format!("{}/Applications/Keepass2Android", mount)),
+        crypto_rtva = quote(&format!("{}/Crypto Folder/ficheros_sensibles", mount)),
+        host_key = host_key,
+        items = items.concat(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_roundtrips() {
+        let answers = InitAnswers {
+            local_dir: "/home/yo".to_string(),
+            pcloud_mount_point: "/home/yo/pCloud \"Drive\"/".to_string(),
+            host: "feynman.sobremesa".to_string(),
+            sync_items: vec!["Documentos".to_string(), ".config/nvim".to_string()],
+        };
+
+        let config: AppConfig = toml::from_str(&render(&answers)).unwrap();
+        assert_eq!(config.general.local_dir, Path::new("/home/yo"));
+        assert_eq!(config.general.pcloud_mount_point, Path::new("/home/yo/pCloud \"Drive\""));
+        assert_eq!(
+            config.general.pcloud_backup_readonly,
+            Path::new("/home/yo/pCloud \"Drive\"/pCloud Backup/feynman.sobremesa")
+        );
+        assert_eq!(config.hosts["feynman.sobremesa"].sync_items, answers.sync_items);
+
+        let simple: AppConfig = toml::from_str(&render(&InitAnswers { host: "portatil".to_string(), ..answers })).unwrap();
+        assert!(simple.hosts.contains_key("portatil"));
+    }
+
+    #[test]
+    fn test_prompt_default_and_refuses_overwrite() {
+        let mut input = std::io::Cursor::new("\n  otro  \n");
+        assert_eq!(prompt(&mut input, "Directorio local", "~").unwrap(), "~");
+        assert_eq!(prompt(&mut input, "Directorio local", "~").unwrap(), "otro");
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("syncb_config.toml");
+        std::fs::write(&path, "existente").unwrap();
+        let args = Cli { init_config: true, yes: true, config: Some(path.clone()), ..Default::default() };
+        assert!(matches!(run(&args), Err(AppError::Config(_))));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "existente");
+
+        run(&Cli { force: true, ..args }).unwrap();
+        assert!(toml::from_str::<AppConfig>(&std::fs::read_to_string(&path).unwrap()).is_ok());
+    }
+}
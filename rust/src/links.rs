@@ -0,0 +1,588 @@
+use crate::error::{AppError, Result};
+use crate::stats::SyncStats;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Marca del manifiesto antiguo para enlaces cuyo destino queda dentro del elemento
+const DENTRO: &str = "interno";
+/// Marca del manifiesto antiguo para enlaces que apuntan fuera del elemento
+const FUERA: &str = "externo";
+
+/// Enlaces registrados: ruta relativa -> (destino, apunta fuera del elemento)
+type Enlaces = BTreeMap<String, (String, bool)>;
+
+/// Una línea del manifiesto: un objeto JSON, así las rutas con tabuladores,
+/// saltos de línea o espacios al principio o al final se leen tal cual
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct EntradaManifiesto {
+    ruta: String,
+    destino: String,
+    externo: bool,
+}
+
+/// Respaldo y restauración de enlaces simbólicos mediante un archivo de
+/// manifiesto (una `EntradaManifiesto` en JSON por línea; los manifiestos
+/// antiguos, `ruta<TAB>destino[<TAB>interno|externo]`, se siguen leyendo)
+/// guardado en pCloud, ya que el montaje FUSE de pCloud no conserva los enlaces.
+pub struct SymbolicLinks {
+    local_dir: PathBuf,
+    symlinks_file: String,
+    dry_run: bool,
+    max_depth: usize,
+}
+
+impl SymbolicLinks {
+    pub fn new(local_dir: PathBuf, symlinks_file: String, dry_run: bool, max_depth: usize) -> Self {
+        Self {
+            local_dir,
+            symlinks_file,
+            dry_run,
+            max_depth,
+        }
+    }
+
+    /// Busca los enlaces de `items` (o, si está vacío, de los elementos listados en
+    /// `sync_list_path`) y guarda el manifiesto en `pcloud_dir`
+    pub fn generar_archivo_enlaces(
+        &self,
+        items: &[String],
+        sync_list_path: Option<&Path>,
+        pcloud_dir: &Path,
+        stats: &mut SyncStats,
+    ) -> Result<()> {
+        let mut enlaces = Enlaces::new();
+
+        let items = if items.is_empty() {
+            match sync_list_path {
+                Some(path) => leer_lista_elementos(path)?,
+                None => Vec::new(),
+            }
+        } else {
+            items.to_vec()
+        };
+
+        for item in &items {
+            self.procesar_item(item, &mut enlaces, stats);
+        }
+
+        if enlaces.is_empty() {
+            log::debug!("No se encontraron enlaces simbólicos para registrar");
+            return Ok(());
+        }
+
+        let destino_enlaces = pcloud_dir.join(&self.symlinks_file);
+        if self.dry_run {
+            log::info!(
+                "SIMULACIÓN: se guardarían {} enlaces en {}",
+                enlaces.len(),
+                destino_enlaces.display()
+            );
+            return Ok(());
+        }
+
+        let externos = enlaces.values().filter(|(_, externo)| *externo).count();
+        if externos > 0 {
+            log::info!("{} enlaces apuntan fuera de su elemento", externos);
+        }
+
+        let total = enlaces.len();
+        let mut contenido = String::new();
+        for (ruta, (destino, externo)) in enlaces {
+            let entrada = EntradaManifiesto { ruta, destino, externo };
+            contenido.push_str(&serde_json::to_string(&entrada).map_err(std::io::Error::from)?);
+            contenido.push('\n');
+        }
+
+        if let Some(parent) = destino_enlaces.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&destino_enlaces, contenido)?;
+
+        log::info!("Enlaces detectados/guardados en meta: {}", total);
+        log::info!("Archivo de enlaces sincronizado: {}", destino_enlaces.display());
+
+        Ok(())
+    }
+
+    fn procesar_item(&self, item: &str, enlaces: &mut Enlaces, stats: &mut SyncStats) {
+        let ruta_completa = self.local_dir.join(item);
+        log::debug!("Buscando enlaces para elemento: {}", ruta_completa.display());
+
+        if ruta_completa.is_symlink() {
+            self.registrar_enlace(&ruta_completa, &ruta_completa, enlaces, stats);
+        } else if ruta_completa.is_dir() {
+            let mut visitados = HashSet::new();
+            self.buscar_enlaces_en_directorio(&ruta_completa, &ruta_completa, 0, &mut visitados, enlaces, stats);
+        }
+    }
+
+    /// Registra `enlace`, anotando si su destino queda fuera de `raiz` (el elemento)
+    fn registrar_enlace(&self, raiz: &Path, enlace: &Path, enlaces: &mut Enlaces, stats: &mut SyncStats) {
+        let ruta_relativa = match enlace.strip_prefix(&self.local_dir) {
+            Ok(relativa) => relativa.to_string_lossy().to_string(),
+            Err(_) => enlace.to_string_lossy().trim_start_matches('/').to_string(),
+        };
+
+        let destino = match fs::read_link(enlace) {
+            Ok(destino) => destino.to_string_lossy().to_string(),
+            Err(e) => {
+                log::warn!("No se pudo leer el enlace {}: {}", enlace.display(), e);
+                stats.symbolic_links_errors += 1;
+                return;
+            }
+        };
+
+        if ruta_relativa.is_empty() || destino.is_empty() {
+            log::warn!("Enlace no válido u origen/destino vacío: {}", enlace.display());
+            return;
+        }
+
+        // Un elemento que es en sí un enlace siempre apunta fuera de sí mismo
+        let externo = raiz == enlace || !resolver_destino(enlace, Path::new(&destino)).starts_with(raiz);
+        let destino = normalizar_destino(&destino);
+        log::debug!("Registrado enlace simbólico: {} -> {}", ruta_relativa, destino);
+        enlaces.insert(ruta_relativa, (destino, externo));
+        stats.symbolic_links_detected += 1;
+    }
+
+    /// Recorre `dir` registrando enlaces. No entra en directorios enlazados, no
+    /// repite directorios ya visitados (p. ej. montajes bind en bucle) y se
+    /// detiene al superar `max_depth`
+    fn buscar_enlaces_en_directorio(
+        &self,
+        raiz: &Path,
+        dir: &Path,
+        profundidad: usize,
+        visitados: &mut HashSet<PathBuf>,
+        enlaces: &mut Enlaces,
+        stats: &mut SyncStats,
+    ) {
+        if profundidad > self.max_depth {
+            log::warn!(
+                "Profundidad máxima ({}) alcanzada buscando enlaces, se omite: {}",
+                self.max_depth,
+                dir.display()
+            );
+            return;
+        }
+
+        let canonico = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+        if !visitados.insert(canonico) {
+            log::warn!("Directorio ya visitado (posible bucle), se omite: {}", dir.display());
+            return;
+        }
+
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::warn!("No se pudo leer el directorio {}: {}", dir.display(), e);
+                return;
+            }
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            // Los enlaces a directorios se registran, nunca se recorren
+            if path.is_symlink() {
+                self.registrar_enlace(raiz, &path, enlaces, stats);
+            } else if path.is_dir() {
+                self.buscar_enlaces_en_directorio(raiz, &path, profundidad + 1, visitados, enlaces, stats);
+            }
+        }
+    }
+
+    /// Recrea los enlaces del manifiesto guardado en `pcloud_dir`
+    pub fn recrear_enlaces_desde_archivo(&self, pcloud_dir: &Path, stats: &mut SyncStats) -> Result<()> {
+        let archivo_enlaces_origen = pcloud_dir.join(&self.symlinks_file);
+        let archivo_enlaces_local = self.local_dir.join(&self.symlinks_file);
+
+        let archivo_a_usar = if archivo_enlaces_origen.exists() {
+            if self.dry_run {
+                archivo_enlaces_origen.clone()
+            } else {
+                fs::copy(&archivo_enlaces_origen, &archivo_enlaces_local)?;
+                log::info!("Archivo de enlaces copiado localmente");
+                archivo_enlaces_local.clone()
+            }
+        } else if archivo_enlaces_local.exists() {
+            log::info!("Usando archivo de enlaces local existente");
+            archivo_enlaces_local.clone()
+        } else {
+            log::debug!("No se encontró archivo de enlaces, omitiendo recreación");
+            return Ok(());
+        };
+
+        log::info!("Recreando enlaces simbólicos...");
+        let contenido = fs::read_to_string(&archivo_a_usar)?;
+
+        for linea in contenido.lines() {
+            if linea.trim().is_empty() {
+                continue;
+            }
+            let Some(EntradaManifiesto { ruta, destino, externo }) = leer_linea_manifiesto(linea) else {
+                log::warn!("Línea inválida en archivo de enlaces (se omite): {}", linea);
+                continue;
+            };
+            let (ruta_enlace, destino) = (ruta.as_str(), destino.as_str());
+
+            match self.procesar_linea_enlace(ruta_enlace, destino) {
+                Ok(Some(creado)) => {
+                    if creado {
+                        stats.symbolic_links_created += 1;
+                    } else {
+                        stats.symbolic_links_existing += 1;
+                    }
+                    if externo {
+                        self.avisar_si_destino_no_existe(ruta_enlace, destino);
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    log::error!("Error creando enlace {} -> {}: {}", ruta_enlace, destino, e);
+                    stats.symbolic_links_errors += 1;
+                }
+            }
+        }
+
+        log::info!(
+            "Enlaces recreados: {}, Existentes: {}, Errores: {}",
+            stats.symbolic_links_created,
+            stats.symbolic_links_existing,
+            stats.symbolic_links_errors
+        );
+
+        if !self.dry_run {
+            let _ = fs::remove_file(&archivo_enlaces_local);
+        }
+
+        Ok(())
+    }
+
+    /// Un enlace que sale del elemento depende de algo que syncb no
+    /// sincroniza: si ese destino no existe aquí, el enlace queda roto
+    fn avisar_si_destino_no_existe(&self, ruta_enlace: &str, destino: &str) {
+        let enlace = self.local_dir.join(ruta_enlace);
+        let destino = resolver_destino(&enlace, Path::new(&expandir_destino(destino)));
+        if fs::metadata(&destino).is_err() {
+            log::warn!(
+                "Enlace a un destino fuera del elemento que no existe en este equipo: {} -> {}",
+                ruta_enlace,
+                destino.display()
+            );
+        }
+    }
+
+    /// Crea un enlace del manifiesto. Devuelve `Some(true)` si se creó,
+    /// `Some(false)` si ya existía correcto y `None` si se omitió
+    fn procesar_linea_enlace(&self, ruta_enlace: &str, destino: &str) -> Result<Option<bool>> {
+        let ruta_completa = self.local_dir.join(ruta_enlace);
+        let destino = expandir_destino(destino);
+
+        // Solo se recrean enlaces que apunten dentro de $HOME (los relativos,
+        // una vez resueltos desde su directorio)
+        if let Some(home) = dirs::home_dir() {
+            if !resolver_destino(&ruta_completa, Path::new(&destino)).starts_with(&home) {
+                log::warn!(
+                    "Destino de enlace fuera de $HOME, se omite: {} -> {}",
+                    ruta_enlace, destino
+                );
+                return Ok(None);
+            }
+        }
+
+        if let Ok(metadata) = fs::symlink_metadata(&ruta_completa) {
+            if !metadata.file_type().is_symlink() {
+                log::warn!(
+                    "Ya existe un archivo o directorio que no es un enlace, se omite: {}",
+                    ruta_completa.display()
+                );
+                return Ok(None);
+            }
+
+            if fs::read_link(&ruta_completa)?.to_string_lossy() == destino {
+                log::debug!("Enlace ya existe y es correcto: {} -> {}", ruta_enlace, destino);
+                return Ok(Some(false));
+            }
+
+            if !self.dry_run {
+                fs::remove_file(&ruta_completa)?;
+            }
+        }
+
+        if self.dry_run {
+            log::info!("SIMULACIÓN: Enlace a crear: {} -> {}", ruta_completa.display(), destino);
+            return Ok(Some(true));
+        }
+
+        if let Some(dir_padre) = ruta_completa.parent() {
+            fs::create_dir_all(dir_padre)?;
+        }
+
+        crear_enlace(Path::new(&destino), &ruta_completa)?;
+        log::debug!("Enlace creado: {} -> {}", ruta_completa.display(), destino);
+
+        Ok(Some(true))
+    }
+}
+
+/// Una línea del manifiesto en JSON o, si no empieza por `{`, en el formato
+/// antiguo separado por tabuladores; `None` si no es válida
+fn leer_linea_manifiesto(linea: &str) -> Option<EntradaManifiesto> {
+    let entrada = if linea.starts_with('{') {
+        serde_json::from_str(linea).ok()?
+    } else {
+        let (ruta, resto) = linea.split_once('\t')?;
+        let (destino, externo) = match resto.rsplit_once('\t') {
+            Some((destino, FUERA)) => (destino, true),
+            Some((destino, DENTRO)) => (destino, false),
+            _ => (resto, false),
+        };
+        EntradaManifiesto { ruta: ruta.to_string(), destino: destino.to_string(), externo }
+    };
+
+    if entrada.ruta.is_empty() || entrada.destino.is_empty() {
+        return None;
+    }
+    Some(entrada)
+}
+
+/// Lee una lista de elementos, uno por línea, ignorando vacías y comentarios
+/// (#). Se quitan los espacios de los extremos salvo si el elemento va entre
+/// comillas dobles, como cadena JSON (`"  con espacios\t"`)
+pub fn leer_lista_elementos(path: &Path) -> Result<Vec<String>> {
+    let contenido = fs::read_to_string(path)?;
+    let mut elementos = Vec::new();
+    for linea in contenido.lines().map(str::trim) {
+        if linea.is_empty() || linea.starts_with('#') {
+            continue;
+        }
+        if linea.starts_with('"') {
+            let elemento: String = serde_json::from_str(linea).map_err(|e| {
+                AppError::Config(format!("Elemento entre comillas no válido en {}: {} ({})", path.display(), linea, e))
+            })?;
+            elementos.push(elemento);
+        } else {
+            elementos.push(linea.to_string());
+        }
+    }
+    Ok(elementos)
+}
+
+/// Ruta absoluta del destino de `enlace` (los relativos parten de su
+/// directorio), normalizada sin acceder al disco
+fn resolver_destino(enlace: &Path, destino: &Path) -> PathBuf {
+    let base = enlace.parent().unwrap_or(Path::new("/"));
+    let mut resuelto = PathBuf::new();
+    for componente in base.join(destino).components() {
+        match componente {
+            Component::ParentDir => {
+                resuelto.pop();
+            }
+            Component::CurDir => {}
+            otro => resuelto.push(otro),
+        }
+    }
+    resuelto
+}
+
+/// Sustituye el home del usuario por `$HOME` (y otros `/home/<usuario>` por
+/// `/home/$USERNAME`) para que el manifiesto sea válido en otras máquinas
+fn normalizar_destino(destino: &str) -> String {
+    if let Some(home) = dirs::home_dir() {
+        let home = home.to_string_lossy();
+        if let Some(resto) = destino.strip_prefix(home.as_ref()) {
+            if resto.is_empty() || resto.starts_with('/') {
+                return format!("$HOME{}", resto);
+            }
+        }
+    }
+
+    if let Some(tmp) = destino.strip_prefix("/home/") {
+        return match tmp.split_once('/') {
+            Some((_, resto)) => format!("/home/$USERNAME/{}", resto),
+            None => "/home/$USERNAME".to_string(),
+        };
+    }
+
+    destino.to_string()
+}
+
+/// Operación inversa de `normalizar_destino`
+fn expandir_destino(destino: &str) -> String {
+    let home = dirs::home_dir()
+        .map(|h| h.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let username = users::get_current_username()
+        .map(|u| u.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let destino = match destino.strip_prefix("$HOME") {
+        Some(resto) => format!("{}{}", home, resto),
+        None => destino.to_string(),
+    };
+
+    destino.replace("$USERNAME", &username)
+}
+
+#[cfg(unix)]
+fn crear_enlace(destino: &Path, enlace: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(destino, enlace)
+}
+
+#[cfg(windows)]
+fn crear_enlace(destino: &Path, enlace: &Path) -> std::io::Result<()> {
+    if destino.is_dir() {
+        std::os::windows::fs::symlink_dir(destino, enlace)
+    } else {
+        std::os::windows::fs::symlink_file(destino, enlace)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_manifest_round_trip() {
+        let local = TempDir::new().unwrap();
+        let pcloud = TempDir::new().unwrap();
+        let home = dirs::home_dir().unwrap();
+        let target = home.join("syncb_test_target");
+
+        fs::create_dir_all(local.path().join("docs/sub")).unwrap();
+        crear_enlace(&target, &local.path().join("docs/sub/enlace")).unwrap();
+
+        let links = SymbolicLinks::new(local.path().to_path_buf(), ".meta".to_string(), false, 64);
+        let mut stats = SyncStats::default();
+        links
+            .generar_archivo_enlaces(&["docs".to_string()], None, pcloud.path(), &mut stats)
+            .unwrap();
+        assert_eq!(stats.symbolic_links_detected, 1);
+
+        let manifest = fs::read_to_string(pcloud.path().join(".meta")).unwrap();
+        assert_eq!(manifest, "{\"ruta\":\"docs/sub/enlace\",\"destino\":\"$HOME/syncb_test_target\",\"externo\":true}\n");
+
+        fs::remove_file(local.path().join("docs/sub/enlace")).unwrap();
+        let mut stats = SyncStats::default();
+        links.recrear_enlaces_desde_archivo(pcloud.path(), &mut stats).unwrap();
+
+        assert_eq!(stats.symbolic_links_created, 1);
+        assert_eq!(fs::read_link(local.path().join("docs/sub/enlace")).unwrap(), target);
+    }
+
+    #[test]
+    fn test_manifest_classifies_out_of_tree_links() {
+        let local = TempDir::new().unwrap();
+        let pcloud = TempDir::new().unwrap();
+        let docs = local.path().join("docs");
+        fs::create_dir_all(docs.join("sub")).unwrap();
+        crear_enlace(Path::new("../notas.txt"), &docs.join("sub/dentro")).unwrap();
+        crear_enlace(Path::new("../../fuera.txt"), &docs.join("sub/escapa")).unwrap();
+
+        let links = SymbolicLinks::new(local.path().to_path_buf(), ".meta".to_string(), false, 64);
+        let mut stats = SyncStats::default();
+        links
+            .generar_archivo_enlaces(&["docs".to_string()], None, pcloud.path(), &mut stats)
+            .unwrap();
+
+        let manifest = fs::read_to_string(pcloud.path().join(".meta")).unwrap();
+        let entradas: Vec<_> = manifest.lines().map(|linea| leer_linea_manifiesto(linea).unwrap()).collect();
+        assert_eq!(
+            entradas,
+            [
+                EntradaManifiesto { ruta: "docs/sub/dentro".into(), destino: "../notas.txt".into(), externo: false },
+                EntradaManifiesto { ruta: "docs/sub/escapa".into(), destino: "../../fuera.txt".into(), externo: true },
+            ]
+        );
+
+        // Los manifiestos sin la tercera columna se siguen leyendo
+        fs::write(pcloud.path().join(".meta"), "docs/antiguo\t$HOME/syncb_test_target\n").unwrap();
+        let mut stats = SyncStats::default();
+        links.recrear_enlaces_desde_archivo(pcloud.path(), &mut stats).unwrap();
+        assert_eq!(stats.symbolic_links_created, 1);
+        assert_eq!(fs::read_link(docs.join("antiguo")).unwrap(), dirs::home_dir().unwrap().join("syncb_test_target"));
+        assert_eq!(resolver_destino(&docs.join("sub/escapa"), Path::new("../../fuera.txt")), local.path().join("fuera.txt"));
+    }
+
+    #[test]
+    fn test_manifest_round_trips_pathological_names() {
+        let local = TempDir::new().unwrap();
+        let pcloud = TempDir::new().unwrap();
+        let docs = local.path().join("docs");
+        fs::create_dir_all(&docs).unwrap();
+        // Solo se recrean destinos dentro de $HOME
+        let destino = |nombre: &str| dirs::home_dir().unwrap().join(format!("syncb_test {}.txt", nombre));
+        let nombres = ["con\ttab", "con\nsalto", " espacios ", "comillas \"y\" \\barra", "interno\texterno", "{llaves}"];
+        for nombre in nombres {
+            crear_enlace(&destino(nombre), &docs.join(nombre)).unwrap();
+        }
+
+        let links = SymbolicLinks::new(local.path().to_path_buf(), ".meta".to_string(), false, 64);
+        let mut stats = SyncStats::default();
+        links
+            .generar_archivo_enlaces(&["docs".to_string()], None, pcloud.path(), &mut stats)
+            .unwrap();
+        assert_eq!(stats.symbolic_links_detected, nombres.len() as u32);
+        assert_eq!(fs::read_to_string(pcloud.path().join(".meta")).unwrap().lines().count(), nombres.len());
+
+        for nombre in nombres {
+            fs::remove_file(docs.join(nombre)).unwrap();
+        }
+        let mut stats = SyncStats::default();
+        links.recrear_enlaces_desde_archivo(pcloud.path(), &mut stats).unwrap();
+        assert_eq!(stats.symbolic_links_errors, 0);
+        for nombre in nombres {
+            assert_eq!(fs::read_link(docs.join(nombre)).unwrap(), destino(nombre));
+        }
+    }
+
+    #[test]
+    fn test_item_list_keeps_quoted_whitespace() {
+        let dir = TempDir::new().unwrap();
+        let lista = dir.path().join("lista.txt");
+        fs::write(&lista, "# comentario\n  Documentos  \n\n\" Notas \"\n\"con\\ttab\"\n").unwrap();
+        assert_eq!(leer_lista_elementos(&lista).unwrap(), ["Documentos", " Notas ", "con\ttab"]);
+
+        fs::write(&lista, "\"sin cerrar\n").unwrap();
+        assert!(leer_lista_elementos(&lista).is_err());
+    }
+
+    #[test]
+    fn test_self_referential_symlink_terminates() {
+        let local = TempDir::new().unwrap();
+        let pcloud = TempDir::new().unwrap();
+        let dir = local.path().join("bucle/a");
+        fs::create_dir_all(&dir).unwrap();
+        crear_enlace(Path::new(".."), &dir.join("arriba")).unwrap();
+        crear_enlace(&local.path().join("bucle"), &dir.join("raiz")).unwrap();
+
+        let links = SymbolicLinks::new(local.path().to_path_buf(), ".meta".to_string(), true, 64);
+        let mut stats = SyncStats::default();
+        links
+            .generar_archivo_enlaces(&["bucle".to_string()], None, pcloud.path(), &mut stats)
+            .unwrap();
+
+        // Los enlaces se registran sin recorrerlos
+        assert_eq!(stats.symbolic_links_detected, 2);
+    }
+
+    #[test]
+    fn test_depth_limit_stops_scan() {
+        let local = TempDir::new().unwrap();
+        let pcloud = TempDir::new().unwrap();
+        let deep = local.path().join("p/1/2/3");
+        fs::create_dir_all(&deep).unwrap();
+        crear_enlace(Path::new("/tmp"), &deep.join("enlace")).unwrap();
+
+        let links = SymbolicLinks::new(local.path().to_path_buf(), ".meta".to_string(), true, 2);
+        let mut stats = SyncStats::default();
+        links
+            .generar_archivo_enlaces(&["p".to_string()], None, pcloud.path(), &mut stats)
+            .unwrap();
+
+        assert_eq!(stats.symbolic_links_detected, 0);
+    }
+}
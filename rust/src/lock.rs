@@ -3,44 +3,84 @@ use crate::error::{AppError, Result};
 use nix::unistd::Pid;
 use std::fs::{File, OpenOptions};
 use std::io::{Read, Write};
-use std::path::Path;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub struct LockGuard {
     lock_file: std::path::PathBuf, // CAMBIADO a PathBuf
+    heartbeat_stop: Option<Sender<()>>,
+    heartbeat: Option<JoinHandle<()>>,
+}
+
+/// Datos escritos en el archivo de lock
+pub struct LockInfo {
+    pub pid: u32,
+    pub timestamp: Option<u64>,
+    pub hostname: String,
+}
+
+/// Ruta del lock: siempre `general.lock_file` (ya expandida al cargar la
+/// configuración); la usan el lock, --force-unlock y --status
+pub fn lock_path(config: &AppConfig) -> &Path {
+    &config.general.lock_file
 }
 
 impl LockGuard {
     pub fn acquire(config: &AppConfig) -> Result<Self> {
-        let lock_file = config.general.lock_file.clone(); // YA ES PathBuf
+        let lock_file = lock_path(config).to_path_buf();
+        let lock_timeout = config.general.lock_timeout_seconds;
 
         // Verificar si el lock existe y es válido
-        if let Some(pid) = Self::check_existing_lock(&lock_file)? {
-            return Err(AppError::AlreadyRunning(pid));
+        if let Some(info) = Self::check_existing_lock(&lock_file, lock_timeout)? {
+            return Err(AppError::AlreadyRunning {
+                pid: info.pid,
+                hostname: info.hostname,
+            });
         }
 
         // Crear nuevo lock
-        Self::create_lock(&lock_file)?;
+        Self::create_lock(&lock_file)
+            .map_err(|e| AppError::Lock(format!("No se pudo crear {}: {}", lock_file.display(), e)))?;
 
-        Ok(Self { lock_file })
+        // Latido periódico para que un proceso largo no se considere obsoleto
+        let (heartbeat_stop, heartbeat) = Self::spawn_heartbeat(lock_file.clone(), lock_timeout);
+
+        Ok(Self {
+            lock_file,
+            heartbeat_stop: Some(heartbeat_stop),
+            heartbeat: Some(heartbeat),
+        })
     }
 
-    fn check_existing_lock(lock_file: &Path) -> Result<Option<u32>> {
+    fn check_existing_lock(lock_file: &Path, lock_timeout: u64) -> Result<Option<LockInfo>> {
         // CAMBIADO a &Path
         if !lock_file.exists() {
             // AHORA FUNCIONA
             return Ok(None);
         }
 
-        let mut file = File::open(lock_file)?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
+        // Un lock ilegible (binario, no UTF-8, truncado) se trata como obsoleto
+        let mut bytes = Vec::new();
+        File::open(lock_file)?.read_to_end(&mut bytes)?;
+        let info = String::from_utf8(bytes).ok().and_then(|contents| Self::parse_lock(&contents));
+        if info.is_none() {
+            log::warn!("Lock ilegible en {:?}: se considera obsoleto y se elimina", lock_file);
+        }
 
-        if let Some(pid_str) = contents.lines().next() {
-            if let Ok(pid) = pid_str.parse::<u32>() {
-                // Verificar si el proceso todavía está ejecutándose
-                if Self::is_process_running(pid) {
-                    return Ok(Some(pid));
+        if let Some(info) = info {
+            // Verificar si el proceso todavía está ejecutándose
+            if Self::is_process_running(info.pid) {
+                let age = info.timestamp.map(|ts| now_secs().saturating_sub(ts));
+                match age {
+                    Some(age) if age > lock_timeout => {
+                        log::warn!(
+                            "¡ATENCIÓN! Lock obsoleto: PID {} en {} sin actividad desde hace {}s (límite {}s). Se reclama el lock",
+                            info.pid, info.hostname, age, lock_timeout
+                        );
+                    }
+                    _ => return Ok(Some(info)),
                 }
             }
         }
@@ -50,7 +90,34 @@ impl LockGuard {
         Ok(None)
     }
 
-    fn is_process_running(pid: u32) -> bool {
+    fn parse_lock(contents: &str) -> Option<LockInfo> {
+        let mut lines = contents.lines();
+        let pid = lines.next()?.trim().parse::<u32>().ok()?;
+        let mut timestamp = None;
+        let mut hostname = "desconocido".to_string();
+
+        for line in lines {
+            if let Some(value) = line.strip_prefix("timestamp:") {
+                timestamp = value.trim().parse::<u64>().ok();
+            } else if let Some(value) = line.strip_prefix("hostname:") {
+                hostname = value.trim().to_string();
+            }
+        }
+
+        Some(LockInfo {
+            pid,
+            timestamp,
+            hostname,
+        })
+    }
+
+    /// Lee el lock actual sin modificarlo; `None` si no existe o es ilegible
+    pub fn read_owner(lock_file: &Path) -> Option<LockInfo> {
+        let contents = std::fs::read_to_string(lock_file).ok()?;
+        Self::parse_lock(&contents)
+    }
+
+    pub fn is_process_running(pid: u32) -> bool {
         // Usar Pid::from_raw con el tipo correcto de nix
         match nix::sys::signal::kill(Pid::from_raw(pid as i32), None) {
             Ok(_) => true,
@@ -66,14 +133,16 @@ impl LockGuard {
             .create_new(true)
             .open(lock_file)?;
 
+        Self::write_lock_contents(&mut file)?;
+
+        Ok(())
+    }
+
+    fn write_lock_contents(file: &mut File) -> Result<()> {
         let pid = std::process::id();
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
 
         writeln!(file, "{}", pid)?;
-        writeln!(file, "timestamp: {}", timestamp)?;
+        writeln!(file, "timestamp: {}", now_secs())?;
         writeln!(
             file,
             "hostname: {}",
@@ -82,22 +151,170 @@ impl LockGuard {
 
         Ok(())
     }
+
+    /// Reescribe el lock con un timestamp nuevo (archivo temporal + rename
+    /// para que otro proceso nunca lea un lock a medio escribir)
+    fn refresh_lock(lock_file: &Path) -> Result<()> {
+        let tmp_file = PathBuf::from(format!("{}.tmp", lock_file.display()));
+        let mut file = File::create(&tmp_file)?;
+        Self::write_lock_contents(&mut file)?;
+        std::fs::rename(&tmp_file, lock_file)?;
+        Ok(())
+    }
+
+    /// Indica si el lock sigue siendo de este proceso: tras --force-unlock o
+    /// si otro lo reclamó, ni se renueva ni se borra
+    fn is_owned(lock_file: &Path) -> bool {
+        Self::read_owner(lock_file).is_some_and(|info| info.pid == std::process::id())
+    }
+
+    /// Renueva el timestamp cada tercio de `lock_timeout`. El latido va en su
+    /// propio hilo, así que solo demuestra que el proceso vive, no que avance:
+    /// uno colgado (p. ej. en un montaje caído) mantiene el lock fresco y
+    /// hay que liberarlo con --force-unlock tras matarlo
+    fn spawn_heartbeat(lock_file: PathBuf, lock_timeout: u64) -> (Sender<()>, JoinHandle<()>) {
+        let interval = Duration::from_secs((lock_timeout / 3).max(1));
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+
+        let handle = std::thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stop_rx.recv_timeout(interval) {
+                // Si el lock ha desaparecido o es de otro proceso no se pisa
+                if !Self::is_owned(&lock_file) {
+                    log::warn!("El lock {:?} ya no es de este proceso: se detiene el latido", lock_file);
+                    break;
+                }
+                if let Err(e) = Self::refresh_lock(&lock_file) {
+                    log::warn!("No se pudo actualizar el latido del lock: {}", e);
+                }
+            }
+        });
+
+        (stop_tx, handle)
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
 }
 
 impl Drop for LockGuard {
     fn drop(&mut self) {
-        let _ = std::fs::remove_file(&self.lock_file);
+        // Detener el latido antes de borrar el lock
+        drop(self.heartbeat_stop.take());
+        if let Some(handle) = self.heartbeat.take() {
+            let _ = handle.join();
+        }
+        if Self::is_owned(&self.lock_file) {
+            let _ = std::fs::remove_file(&self.lock_file);
+        }
     }
 }
 
 pub fn force_unlock(config: &AppConfig) -> Result<()> {
-    let lock_file = &config.general.lock_file;
+    let lock_file = lock_path(config);
 
     if lock_file.exists() {
-        // AHORA FUNCIONA
-        std::fs::remove_file(lock_file)?;
+        std::fs::remove_file(lock_file)
+            .map_err(|e| AppError::Lock(format!("No se pudo eliminar {}: {}", lock_file.display(), e)))?;
         log::info!("Lock forzado eliminado: {:?}", lock_file);
+    } else {
+        log::info!("No hay lock que eliminar: {:?}", lock_file);
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_config;
+    use tempfile::TempDir;
+
+    fn write_lock(lock_file: &Path, timestamp: u64) {
+        let contents = format!(
+            "{}\ntimestamp: {}\nhostname: otra-maquina\n",
+            std::process::id(),
+            timestamp
+        );
+        std::fs::write(lock_file, contents).unwrap();
+    }
+
+    #[test]
+    fn test_custom_lock_file_is_created_and_removed() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+        config.general.lock_file = temp_dir.path().join("otro_lock.pid");
+        let custom = temp_dir.path().join("otro_lock.pid");
+        assert_eq!(lock_path(&config), custom);
+
+        let guard = LockGuard::acquire(&config).unwrap();
+        assert!(custom.exists());
+        drop(guard);
+        assert!(!custom.exists());
+
+        let _guard = LockGuard::acquire(&config).unwrap();
+        force_unlock(&config).unwrap();
+        assert!(!custom.exists());
+    }
+
+    #[test]
+    fn test_unreadable_lock_is_treated_as_stale() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock_file = temp_dir.path().join("syncb.lock");
+        std::fs::write(&lock_file, [0xff, 0xfe, 0x00, b'\n', 0xc3]).unwrap();
+
+        assert!(LockGuard::check_existing_lock(&lock_file, 3600).unwrap().is_none());
+        assert!(!lock_file.exists());
+
+        // Y se puede crear un lock nuevo en su lugar
+        LockGuard::create_lock(&lock_file).unwrap();
+        assert_eq!(LockGuard::read_owner(&lock_file).unwrap().pid, std::process::id());
+    }
+
+    #[test]
+    fn test_fresh_lock_of_running_process_is_kept() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock_file = temp_dir.path().join("syncb.lock");
+        write_lock(&lock_file, now_secs());
+
+        let info = LockGuard::check_existing_lock(&lock_file, 3600).unwrap().unwrap();
+        assert_eq!(info.pid, std::process::id());
+        assert_eq!(info.hostname, "otra-maquina");
+        assert!(lock_file.exists());
+    }
+
+    #[test]
+    fn test_expired_lock_is_reclaimed_even_if_process_runs() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock_file = temp_dir.path().join("syncb.lock");
+        write_lock(&lock_file, now_secs() - 7200);
+
+        assert!(LockGuard::check_existing_lock(&lock_file, 3600).unwrap().is_none());
+        assert!(!lock_file.exists());
+    }
+
+    #[test]
+    fn test_lock_taken_by_another_process_is_left_alone() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+        config.general.lock_timeout_seconds = 3;
+        let lock_file = lock_path(&config).to_path_buf();
+
+        // Tras --force-unlock otro proceso crea su lock: el latido (cada
+        // segundo) no lo pisa y al terminar no se borra
+        let guard = LockGuard::acquire(&config).unwrap();
+        let other = std::process::id() + 1;
+        std::fs::write(&lock_file, format!("{}
+timestamp: {}
+hostname: otro
+", other, now_secs())).unwrap();
+        std::thread::sleep(Duration::from_millis(1500));
+        assert_eq!(LockGuard::read_owner(&lock_file).unwrap().pid, other);
+
+        drop(guard);
+        assert_eq!(LockGuard::read_owner(&lock_file).unwrap().pid, other);
+    }
+}
@@ -1,81 +1,305 @@
+use crate::cli::Cli;
 use crate::config::AppConfig;
-use crate::error::Result;
+use crate::error::{AppError, Result};
 use chrono::Local;
-use log::{LevelFilter, Record};
+use lazy_static::lazy_static;
+use log::{LevelFilter, Log, Metadata, Record};
+use regex::Regex;
 use std::fs::{File, OpenOptions};
-use std::io::Write;
-use std::path::PathBuf;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
 
-pub struct Logger {
-    log_file: PathBuf,
+lazy_static! {
+    static ref ANSI_ESCAPE: Regex = Regex::new(r"\x1b\[[0-9;]*[A-Za-z]").unwrap();
 }
 
+/// Registro global: terminal y `log_file` a la vez (ver `TeeLogger`)
+pub struct Logger;
+
 impl Logger {
-    pub fn init(config: &AppConfig) -> Result<Self> {
+    pub fn init(config: &AppConfig, args: &Cli) -> Result<()> {
         let log_file = config.general.log_file.clone();
 
-        // Configurar env_logger
-        env_logger::Builder::new()
-            .filter_level(if config.is_host_rtva() {
-                LevelFilter::Debug
-            } else {
-                LevelFilter::Info
-            })
-            .format(Self::log_format)
-            .init();
+        // Configurar env_logger para la terminal (stderr)
+        let color = colors_enabled(
+            args.no_color,
+            std::env::var_os("NO_COLOR").as_deref(),
+            std::io::stderr().is_terminal(),
+        );
+        let terminal = env_logger::Builder::new()
+            .filter_level(Self::level_filter(config, args))
+            .format(move |buf, record| Self::log_format(buf, record, color))
+            .build();
 
         // Crear archivo de log
         if let Some(parent) = log_file.parent() {
             std::fs::create_dir_all(parent)?;
         }
 
-        File::create(&log_file)?;
+        let tee = TeeLogger::new(terminal, log_file.clone(), Rotation::from_config(config))?;
+        let max_level = tee.terminal.filter();
+
+        log::set_boxed_logger(Box::new(tee))
+            .map_err(|e| AppError::Config(format!("No se pudo inicializar el logger: {}", e)))?;
+        log::set_max_level(max_level);
+
+        Ok(())
+    }
 
-        Ok(Self { log_file })
+    /// --log-level manda; si no, --verbose o `use_alternate_crypto_paths` activan Debug
+    fn level_filter(config: &AppConfig, args: &Cli) -> LevelFilter {
+        match args.log_level {
+            Some(level) => level.into(),
+            None if args.verbose || config.uses_alternate_crypto_paths() => LevelFilter::Debug,
+            None => LevelFilter::Info,
+        }
     }
 
-    fn log_format(buf: &mut env_logger::fmt::Formatter, record: &Record) -> std::io::Result<()> {
-        let level = record.level();
+    fn log_format(buf: &mut env_logger::fmt::Formatter, record: &Record, color: bool) -> std::io::Result<()> {
         let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
+        writeln!(buf, "{} [{}] {}", timestamp, level_label(record.level(), color), record.args())
+    }
+}
 
-        // Colores para niveles (solo en terminal)
-        let colored_level = match level {
-            log::Level::Error => format!("\x1b[31m{}\x1b[0m", level), // Rojo
-            log::Level::Warn => format!("\x1b[33m{}\x1b[0m", level),  // Amarillo
-            log::Level::Info => format!("\x1b[34m{}\x1b[0m", level),  // Azul
-            log::Level::Debug => format!("\x1b[35m{}\x1b[0m", level), // Magenta
-            log::Level::Trace => format!("\x1b[36m{}\x1b[0m", level), // Cian
-        };
+/// Colores en la terminal salvo con --no-color, con `NO_COLOR` definida y no
+/// vacía (https://no-color.org) o si stderr no es una terminal (redirigido)
+fn colors_enabled(no_color: bool, no_color_env: Option<&std::ffi::OsStr>, is_terminal: bool) -> bool {
+    !no_color && no_color_env.is_none_or(|value| value.is_empty()) && is_terminal
+}
 
-        writeln!(buf, "{} [{}] {}", timestamp, colored_level, record.args())
+fn level_label(level: log::Level, color: bool) -> String {
+    if !color {
+        return level.to_string();
     }
+    let code = match level {
+        log::Level::Error => 31, // Rojo
+        log::Level::Warn => 33,  // Amarillo
+        log::Level::Info => 34,  // Azul
+        log::Level::Debug => 35, // Magenta
+        log::Level::Trace => 36, // Cian
+    };
+    format!("\x1b[{}m{}\x1b[0m", code, level)
+}
 
-    pub fn log_to_file(&self, message: &str) -> Result<()> {
-        let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
-        let log_entry = format!("{} - {}\n", timestamp, message);
+/// Cuándo se rota el log y cuántos archivos numerados se conservan
+#[derive(Debug, Clone)]
+struct Rotation {
+    max_size: u64,
+    max_files: usize,
+    compress: bool,
+}
 
-        let mut file = OpenOptions::new()
-            .append(true)
-            .create(true)
-            .open(&self.log_file)?;
+impl Rotation {
+    fn from_config(config: &AppConfig) -> Self {
+        Self {
+            max_size: config.general.log_max_size_mb * 1024 * 1024,
+            max_files: config.general.log_max_files,
+            compress: config.general.log_compress,
+        }
+    }
+}
 
+/// Envía cada registro a la terminal (con colores si procede) y a `log_file` (siempre sin ellos)
+struct TeeLogger {
+    terminal: env_logger::Logger,
+    log_file: PathBuf,
+    rotation: Rotation,
+    file: Mutex<File>,
+}
+
+impl TeeLogger {
+    fn new(terminal: env_logger::Logger, log_file: PathBuf, rotation: Rotation) -> Result<Self> {
+        let file = open_log_file(&log_file)?;
+        Ok(Self {
+            terminal,
+            log_file,
+            rotation,
+            file: Mutex::new(file),
+        })
+    }
+
+    fn write_to_file(&self, record: &Record) -> Result<()> {
+        let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
+        let message = strip_ansi(&record.args().to_string());
+        let log_entry = format!("{} [{}] {}\n", timestamp, record.level(), message);
+
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
         file.write_all(log_entry.as_bytes())?;
 
         // Rotación de logs si es necesario
-        self.rotate_log_if_needed()?;
+        self.rotate_log_if_needed(&mut file)
+    }
+
+    fn rotate_log_if_needed(&self, file: &mut File) -> Result<()> {
+        if file.metadata()?.len() > self.rotation.max_size {
+            rotate(&self.log_file, &self.rotation)?;
+            *file = open_log_file(&self.log_file)?;
+        }
 
         Ok(())
     }
+}
+
+/// syncb.log -> syncb.log.1 -> syncb.log.2 ...; se borran los que pasan de
+/// `max_files` (también los que sobren si se redujo el límite)
+fn rotate(log_file: &Path, rotation: &Rotation) -> std::io::Result<()> {
+    let mut index = rotation.max_files.max(1);
+    while let Some(archive) = existing_archive(log_file, index) {
+        std::fs::remove_file(archive)?;
+        index += 1;
+    }
 
-    fn rotate_log_if_needed(&self) -> Result<()> {
-        if let Ok(metadata) = std::fs::metadata(&self.log_file) {
-            if metadata.len() > 10 * 1024 * 1024 {
-                // 10MB
-                let rotated_name = format!("{}.old", self.log_file.display());
-                std::fs::rename(&self.log_file, rotated_name)?;
-            }
+    if rotation.max_files == 0 {
+        return std::fs::remove_file(log_file);
+    }
+
+    for index in (1..rotation.max_files).rev() {
+        if let Some(archive) = existing_archive(log_file, index) {
+            let gz = archive.extension().is_some_and(|ext| ext == "gz");
+            std::fs::rename(&archive, archive_path(log_file, index + 1, gz))?;
         }
+    }
 
-        Ok(())
+    let first = archive_path(log_file, 1, false);
+    std::fs::rename(log_file, &first)?;
+
+    if rotation.compress {
+        // Sin gzip el archivo queda rotado igualmente, solo que sin comprimir
+        match Command::new("gzip").arg("-f").arg(&first).status() {
+            Ok(status) if status.success() => {}
+            Ok(status) => eprintln!("gzip terminó con {} al comprimir {}", status, first.display()),
+            Err(e) => eprintln!("No se pudo ejecutar gzip para {}: {}", first.display(), e),
+        }
+    }
+
+    Ok(())
+}
+
+fn archive_path(log_file: &Path, index: usize, gz: bool) -> PathBuf {
+    let suffix = if gz { ".gz" } else { "" };
+    PathBuf::from(format!("{}.{}{}", log_file.display(), index, suffix))
+}
+
+/// Archivo rotado número `index`, comprimido o no
+fn existing_archive(log_file: &Path, index: usize) -> Option<PathBuf> {
+    [false, true]
+        .into_iter()
+        .map(|gz| archive_path(log_file, index, gz))
+        .find(|path| path.exists())
+}
+
+impl Log for TeeLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.terminal.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.terminal.matches(record) {
+            return;
+        }
+
+        self.terminal.log(record);
+
+        if let Err(e) = self.write_to_file(record) {
+            eprintln!("Error escribiendo en {}: {}", self.log_file.display(), e);
+        }
+    }
+
+    fn flush(&self) {
+        self.terminal.flush();
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.flush();
+        }
+    }
+}
+
+fn open_log_file(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().append(true).create(true).open(path)
+}
+
+fn strip_ansi(text: &str) -> String {
+    ANSI_ESCAPE.replace_all(text, "").into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_reach_log_file_without_colors() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_file = dir.path().join("syncb.log");
+        let terminal = env_logger::Builder::new()
+            .filter_level(LevelFilter::Info)
+            .build();
+        let rotation = Rotation {
+            max_size: 10 * 1024 * 1024,
+            max_files: 5,
+            compress: false,
+        };
+        let tee = TeeLogger::new(terminal, log_file.clone(), rotation).unwrap();
+
+        tee.log(
+            &Record::builder()
+                .level(log::Level::Warn)
+                .args(format_args!("\x1b[32mSincronización\x1b[0m completada"))
+                .build(),
+        );
+        tee.log(
+            &Record::builder()
+                .level(log::Level::Debug)
+                .args(format_args!("filtrado"))
+                .build(),
+        );
+        tee.flush();
+
+        let contents = std::fs::read_to_string(&log_file).unwrap();
+        assert!(contents.contains("[WARN] Sincronización completada"));
+        assert!(!contents.contains('\x1b'));
+        assert!(!contents.contains("filtrado"));
+    }
+
+    #[test]
+    fn test_colors_only_on_a_terminal_without_opt_out() {
+        assert!(colors_enabled(false, None, true));
+        assert!(colors_enabled(false, Some("".as_ref()), true));
+        assert!(!colors_enabled(true, None, true));
+        assert!(!colors_enabled(false, Some("1".as_ref()), true));
+        assert!(!colors_enabled(false, None, false));
+
+        assert_eq!(level_label(log::Level::Warn, false), "WARN");
+        assert_eq!(level_label(log::Level::Error, true), "\x1b[31mERROR\x1b[0m");
+    }
+
+    #[test]
+    fn test_numbered_rotation_keeps_max_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_file = dir.path().join("syncb.log");
+        let rotation = Rotation {
+            max_size: 0,
+            max_files: 2,
+            compress: false,
+        };
+        // Resto de un límite anterior más alto
+        std::fs::write(archive_path(&log_file, 3, true), "antiguo").unwrap();
+
+        for run in ["primero", "segundo", "tercero"] {
+            std::fs::write(&log_file, run).unwrap();
+            rotate(&log_file, &rotation).unwrap();
+        }
+
+        assert!(!log_file.exists());
+        let read = |index| std::fs::read_to_string(archive_path(&log_file, index, false)).unwrap();
+        assert_eq!(read(1), "tercero");
+        assert_eq!(read(2), "segundo");
+        assert!(existing_archive(&log_file, 3).is_none());
+
+        let none = Rotation { max_files: 0, ..rotation };
+        std::fs::write(&log_file, "cuarto").unwrap();
+        rotate(&log_file, &none).unwrap();
+        assert!(!log_file.exists());
+        assert!(existing_archive(&log_file, 1).is_none());
     }
 }
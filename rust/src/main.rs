@@ -1,94 +1,480 @@
 use anyhow::Result;
-use clap::Parser;
+use clap::{CommandFactory, Parser};
 use std::process;
+use std::time::{Duration, SystemTime};
 
+mod case_collisions;
+mod check;
+mod checksum_cache;
 mod cli;
 mod config;
 mod crypto;
 mod error;
+mod history;
+mod hooks;
+mod ignore_files;
+mod init_config;
+mod links;
 mod lock;
 mod logging;
+mod manifest;
+mod notifications;
+mod plan;
+mod reconcile;
+mod resume;
+mod retention;
+mod shutdown;
 mod stats;
+mod status;
 mod sync;
+#[cfg(test)]
+mod test_support;
+mod two_way;
+mod utils;
+mod watch_fs;
 
 use config::AppConfig;
+use error::AppError;
 use logging::Logger;
-use stats::SyncStats;
 
 
-ATENCION, PELIGRO SI SE EJECUTA ESTA VERSION SIN NINGUNA OPCION RELIZA UNA SINCRONIZACION
+// ATENCION, PELIGRO SI SE EJECUTA ESTA VERSION SIN NINGUNA OPCION RELIZA UNA SINCRONIZACION
 
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Parse command line arguments
-    let args = cli::Cli::parse();
+    let mut args = cli::Cli::parse();
+    // Con --profile el modo puede venir del perfil: se valida tras aplicarlo
+    let has_profile = args.profile.is_some();
+    resolve_args(&mut args, !has_profile);
+
+    // --init-config: aún no hay configuración que cargar
+    if args.init_config {
+        match init_config::run(&args) {
+            Ok(path) => {
+                println!("Configuración creada en {}; revísela con --check-config", path.display());
+                process::exit(0);
+            }
+            Err(e) => {
+                eprintln!("Error creating configuration: {}", e);
+                process::exit(e.exit_code());
+            }
+        }
+    }
 
     // Initialize configuration
-    let config = match AppConfig::load(&args) {
-        Ok(config) => config,
-        Err(e) => {
+    let mut config = load_config(&args);
+
+    // --profile: se vuelve a analizar la línea de comandos con el perfil
+    // delante y se recarga la configuración con el resultado
+    if let Some(name) = &args.profile {
+        let profile_args = config.profile_args(name).unwrap_or_else(|e| {
             eprintln!("Error loading configuration: {}", e);
-            process::exit(1);
-        }
-    };
+            process::exit(e.exit_code());
+        });
+        args = cli::Cli::with_profile(std::env::args_os(), &profile_args).unwrap_or_else(|e| e.exit());
+        resolve_args(&mut args, true);
+        config = load_config(&args);
+    }
 
     // Initialize logging
-    let logger = match Logger::init(&config) {
-        Ok(logger) => logger,
-        Err(e) => {
-            eprintln!("Error initializing logger: {}", e);
-            process::exit(1);
-        }
-    };
+    if let Err(e) = Logger::init(&config, &args) {
+        eprintln!("Error initializing logger: {}", e);
+        process::exit(1);
+    }
+
+    log::debug!("Configuración cargada desde {}", config.config_path.display());
+
+    // --delete-excluded es una forma más agresiva de --delete
+    if args.delete_excluded {
+        args.delete = true;
+        log::warn!("--delete-excluded: se borrarán en el destino los archivos que coincidan con las exclusiones");
+    }
+
+    apply_bwlimit_schedule(&mut args, &config);
 
     // Set up signal handlers for graceful shutdown
-    setup_signal_handlers();
+    shutdown::setup_signal_handlers();
 
     // Run the application
-    if let Err(e) = run(args, config, logger).await {
+    if let Err(e) = run(args, config).await {
         log::error!("Application error: {}", e);
-        process::exit(1);
+        let exit_code = e
+            .downcast_ref::<AppError>()
+            .map(AppError::exit_code)
+            .unwrap_or(1);
+        process::exit(exit_code);
     }
 
     Ok(())
 }
 
-async fn run(args: cli::Cli, config: AppConfig, _logger: Logger) -> Result<()> {
+/// Unifica el modo y, si `validate`, comprueba las reglas que clap no
+/// expresa; ante un error termina como lo haría clap
+fn resolve_args(args: &mut cli::Cli, validate: bool) {
+    if let Err(message) = args.resolve_mode() {
+        cli::Cli::command()
+            .error(clap::error::ErrorKind::ArgumentConflict, message)
+            .exit();
+    }
+    if validate {
+        if let Err(message) = args.validate() {
+            cli::Cli::command()
+                .error(clap::error::ErrorKind::MissingRequiredArgument, message)
+                .exit();
+        }
+    }
+}
+
+fn load_config(args: &cli::Cli) -> AppConfig {
+    match AppConfig::load(args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error loading configuration: {}", e);
+            process::exit(e.exit_code());
+        }
+    }
+}
+
+/// Límite de ancho de banda de la franja horaria actual (no se reevalúa
+/// durante una sincronización; con --watch, en cada iteración)
+fn apply_bwlimit_schedule(args: &mut cli::Cli, config: &AppConfig) {
+    if args.bwlimit_schedule {
+        args.bwlimit = config.scheduled_bwlimit(chrono::Local::now().time());
+        match args.bwlimit {
+            Some(limit) => log::info!("--bwlimit-schedule: límite de {} KB/s", limit),
+            None => log::info!("--bwlimit-schedule: ninguna franja vigente, sin límite"),
+        }
+    }
+}
+
+async fn run(args: cli::Cli, config: AppConfig) -> Result<()> {
+    // Solo comprobar la configuración (ya cargada y validada)
+    if args.check_config {
+        check::check_config(&config)?;
+        return Ok(());
+    }
+
+    // Estado del sistema, sin adquirir el lock
+    if args.status {
+        status::show_status(&config)?;
+        return Ok(());
+    }
+
+    // Mostrar historial sin sincronizar
+    if let Some(count) = args.history {
+        history::show(&config.general.history_file, count)?;
+        return Ok(());
+    }
+
+    // Eliminar un lock obsoleto sin sincronizar
+    if args.force_unlock {
+        lock::force_unlock(&config)?;
+        if !args.subir && !args.bajar && !args.two_way && !args.repair_symlinks && args.prune_older_than.is_none() {
+            return Ok(());
+        }
+    }
+
+    // Retención del backup de solo lectura: nada de rsync, pero con lock
+    if let Some(max_age) = args.prune_older_than {
+        let _lock_guard = lock::LockGuard::acquire(&config)?;
+        retention::prune(&args, &config, max_age)?;
+        return Ok(());
+    }
+
     // Show banner
-    sync::show_banner(&args, &config);
+    if !args.json {
+        sync::show_banner(&args, &config);
+    }
+
+    // Solo listar elementos, sin verificaciones ni rsync
+    if args.list_only {
+        sync::list_only(&args, &config)?;
+        return Ok(());
+    }
 
+    // Guardar el plan resuelto sin ejecutar nada
+    if let Some(path) = &args.plan_out {
+        sync::write_plan(&args, &config, path)?;
+        return Ok(());
+    }
+
+    // Solo reparar enlaces simbólicos: sin rsync, pero con lock para no
+    // pisarse con una sincronización en curso
+    if args.repair_symlinks {
+        let _lock_guard = lock::LockGuard::acquire(&config)?;
+        let stats = sync::repair_symlinks(&args, &config).await?;
+        if args.json {
+            stats.display_json();
+        } else {
+            stats.display_summary();
+        }
+        if stats.symbolic_links_errors > 0 {
+            return Err(AppError::Sync(format!(
+                "{} enlaces simbólicos no se pudieron recrear",
+                stats.symbolic_links_errors
+            ))
+            .into());
+        }
+        return Ok(());
+    }
+
+    // Informe de diferencias entre local y pCloud, sin transferir nada
+    if args.compare_checksums {
+        sync::verify_dependencies(&config)?;
+        let diffs = sync::compare_checksums(&args, &config).await?;
+        if args.json {
+            reconcile::display_json(&diffs);
+        } else {
+            reconcile::display(&diffs);
+        }
+        let total: usize = diffs.iter().map(reconcile::ItemDiff::total).sum();
+        if total > 0 {
+            return Err(AppError::Validation(format!("Local y remoto difieren en {} rutas", total)).into());
+        }
+        return Ok(());
+    }
+
+    // Modo demonio: sincronizar cada intervalo hasta recibir una señal
+    if let Some(interval) = args.watch {
+        return watch(args, config, interval).await;
+    }
+
+    // Subir al poco de cambiar algo en local
+    if let Some(debounce) = args.watch_fs {
+        return watch_fs(args, config, debounce).await;
+    }
+
+    // Un plan guardado se lee antes de nada para fallar pronto
+    let saved_plan = args.plan_in.as_deref().map(plan::ExecutionPlan::load).transpose()?;
+
+    sync_once(&args, &config, saved_plan.as_ref()).await
+}
+
+/// --watch: sincroniza, espera `interval` y repite. Entre iteraciones se
+/// recarga la configuración si su archivo ha cambiado. Los errores de una
+/// iteración solo se registran, salvo los de configuración.
+async fn watch(mut args: cli::Cli, mut config: AppConfig, interval: Duration) -> Result<()> {
+    let mut config_mtime = modified(&config.config_path);
+    log::info!(
+        "Modo --watch: sincronización cada {}",
+        humantime::format_duration(interval)
+    );
+
+    loop {
+        sync_iteration(&args, &config).await?;
+
+        if shutdown::is_requested() {
+            break;
+        }
+        log::info!("Próxima sincronización en {}", humantime::format_duration(interval));
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = shutdown::requested() => break,
+        }
+
+        let mtime = modified(&config.config_path);
+        if mtime != config_mtime {
+            log::info!("Configuración modificada, recargando {}", config.config_path.display());
+            config = AppConfig::load(&args)?;
+            config_mtime = mtime;
+        }
+        apply_bwlimit_schedule(&mut args, &config);
+    }
+
+    log::info!("Parada solicitada: fin del modo --watch");
+    Ok(())
+}
+
+/// --watch-fs: una sincronización inicial (lo cambiado mientras no se
+/// vigilaba) y otra cada vez que se calman los cambios locales
+async fn watch_fs(mut args: cli::Cli, mut config: AppConfig, debounce: Duration) -> Result<()> {
+    let mut config_mtime = modified(&config.config_path);
+    let mut watcher = sync::watch_local_changes(&args, &config)?;
+    log::info!(
+        "Modo --watch-fs: se sube {} después del último cambio local",
+        humantime::format_duration(debounce)
+    );
+
+    sync_iteration(&args, &config).await?;
+
+    while let Some(changes) = watcher.wait_for_changes(debounce).await? {
+        log::info!("--watch-fs: {} cambios locales, sincronizando", changes.len());
+        for path in changes.iter().take(10) {
+            log::debug!("  cambio: {}", path.display());
+        }
+
+        // La configuración puede cambiar qué se vigila
+        let mtime = modified(&config.config_path);
+        if mtime != config_mtime {
+            log::info!("Configuración modificada, recargando {}", config.config_path.display());
+            config = AppConfig::load(&args)?;
+            config_mtime = mtime;
+            watcher = sync::watch_local_changes(&args, &config)?;
+        }
+        apply_bwlimit_schedule(&mut args, &config);
+
+        sync_iteration(&args, &config).await?;
+    }
+
+    log::info!("Parada solicitada: fin del modo --watch-fs");
+    Ok(())
+}
+
+/// Una sincronización de --watch/--watch-fs: solo los errores de
+/// configuración detienen el bucle; el resto se registran
+async fn sync_iteration(args: &cli::Cli, config: &AppConfig) -> Result<()> {
+    match sync_once(args, config, None).await {
+        Ok(()) => log::info!("Iteración completada"),
+        Err(e) => match e.downcast_ref::<AppError>() {
+            Some(AppError::Cancelled) => {}
+            Some(AppError::Config(_)) => return Err(e),
+            _ => log::error!("Iteración fallida: {}", e),
+        },
+    }
+    Ok(())
+}
+
+fn modified(path: &std::path::Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// --max-runtime: al vencer el plazo se pide una parada ordenada (se
+/// detiene el rsync en curso y no se lanzan más elementos) y se espera a
+/// que termine para conservar las estadísticas parciales
+async fn within_max_runtime<F>(limit: Duration, run: F) -> (stats::SyncStats, error::Result<()>)
+where
+    F: std::future::Future<Output = (stats::SyncStats, error::Result<()>)>,
+{
+    let mut run = std::pin::pin!(run);
+    match tokio::time::timeout(limit, &mut run).await {
+        Ok(outcome) => outcome,
+        Err(_) => {
+            let limit = humantime::format_duration(limit);
+            log::error!("Tiempo máximo de ejecución ({}) agotado: deteniendo la sincronización", limit);
+            shutdown::request();
+            let (stats, _) = run.await;
+            (stats, Err(AppError::Timeout(format!("--max-runtime {}", limit))))
+        }
+    }
+}
+
+/// Una sincronización completa: dependencias, lock, comprobaciones, rsync y resumen
+async fn sync_once(args: &cli::Cli, config: &AppConfig, saved_plan: Option<&plan::ExecutionPlan>) -> Result<()> {
     // Verify dependencies
-    sync::verify_dependencies()?;
+    sync::verify_dependencies(config)?;
 
     // Set up lock file
-    let _lock_guard = lock::LockGuard::acquire(&config)?;
+    let _lock_guard = lock::LockGuard::acquire(config)?;
 
     // Verify preconditions
-    sync::verify_preconditions(&config).await?;
+    sync::verify_preconditions(args, config).await?;
+
+    // Auditoría de borrados: no se transfiere nada
+    if args.dry_run_delete_only {
+        let deletions = sync::audit_deletions(args, config).await?;
+        if deletions > 0 {
+            return Err(AppError::Validation(format!(
+                "--delete borraría {} archivos en el destino",
+                deletions
+            ))
+            .into());
+        }
+        return Ok(());
+    }
+
+    // Informe de lo que borraría --delete, sin activarlo en esta ejecución;
+    // con --json va en el resumen final
+    let mut preview_deletions = None;
+    if args.preview_deletions {
+        let deletions = sync::preview_deletions(args, config).await?;
+        if args.json {
+            preview_deletions = Some(deletions);
+        } else {
+            sync::display_deletions(&deletions);
+        }
+    }
+
+    // Contar los borrados antes de permitir un --delete real (o un plan que
+    // borre, aunque esta ejecución no lleve --delete)
+    if !args.dry_run {
+        match saved_plan {
+            Some(saved_plan) => sync::guard_plan_deletions(args, config, saved_plan).await?,
+            None if args.delete => sync::guard_deletions(args, config).await?,
+            None => {}
+        }
+    }
 
-    // Confirm execution if needed
-    if !args.yes && !args.dry_run {
+    // Confirm execution if needed (--confirm-each pregunta después, elemento a elemento)
+    if args.confirm_each {
+        sync::ensure_interactive()?;
+    } else if !args.yes && !args.dry_run {
         sync::confirm_execution()?;
     }
 
-    // Initialize statistics
-    let mut stats = SyncStats::new();
+    // Hooks previos: un fallo aborta antes de tocar nada
+    hooks::run_pre_sync(args, config)?;
 
     // Perform synchronization
-    match sync::perform_sync(&args, &config, &mut stats).await {
-        Ok(()) => Ok(()),
-        Err(e) => {
-            // Convert AppError to anyhow::Error
-            Err(anyhow::anyhow!("Sync error: {}", e))
+    let run = async {
+        match saved_plan {
+            Some(saved_plan) => sync::execute_plan(args, config, saved_plan).await,
+            None => sync::perform_sync(args, config).await,
+        }
+    };
+    let (mut stats, result) = match args.max_runtime {
+        Some(limit) => within_max_runtime(limit, run).await,
+        None => run.await,
+    };
+    stats.preview_deletions = preview_deletions;
+
+    // Show summary
+    if args.json {
+        stats.display_json();
+    } else {
+        stats.display_summary();
+        if args.dry_run {
+            stats.display_item_report(args.verbose);
         }
     }
-}
+    stats.send_notification(&config.notifications);
 
-fn setup_signal_handlers() {
-    // Set up CTRL+C handler for graceful shutdown
-    let _ = signal_hook::flag::register(
-        signal_hook::consts::SIGINT,
-        std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false))
-    );
+    // Registrar la ejecución en el historial
+    let entry = history::HistoryEntry::from_stats(args, &stats);
+    if let Err(e) = history::append(&config.general.history_file, &entry) {
+        log::warn!("No se pudo registrar la ejecución en el historial: {}", e);
+    }
+
+    if let Some(stats_file) = &args.stats_file {
+        let success = result.is_ok() && stats.sync_errors == 0 && stats.verification_failures == 0;
+        if let Err(e) = stats.write_stats_file(stats_file, success) {
+            log::warn!("No se pudieron escribir las estadísticas en {:?}: {}", stats_file, e);
+        }
+    }
+
+    // Mantener el AppError para que main pueda elegir el código de salida
+    result?;
+
+    if stats.sync_errors == 0 && stats.verification_failures == 0 {
+        hooks::run_post_sync(args, config, &stats);
+    }
+
+    if stats.verification_failures > 0 {
+        return Err(AppError::Sync(format!(
+            "Verificación fallida: {} diferencias tras sincronizar",
+            stats.verification_failures
+        ))
+        .into());
+    }
+
+    if stats.sync_errors > 0 {
+        return Err(AppError::Sync(format!(
+            "{} elementos terminaron con errores",
+            stats.sync_errors
+        ))
+        .into());
+    }
+
+    Ok(())
 }
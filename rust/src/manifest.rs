@@ -0,0 +1,198 @@
+use crate::checksum_cache::sha256_file;
+use crate::error::{AppError, Result};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Directorio (en la raíz de pCloud) con un manifiesto por elemento
+pub const MANIFEST_DIR: &str = ".syncb_manifests";
+
+/// Hash SHA-256 y ruta relativa de cada archivo, en el orden de `sha256sum`
+pub type Manifest = Vec<(String, String)>;
+
+/// Ruta del manifiesto de `item` bajo `pcloud_dir`: el nombre legible del
+/// elemento más el inicio del hash de su ruta, para que dos elementos que
+/// se escriben igual (`.config/nvim` y `.config_nvim`) no compartan archivo
+pub fn manifest_path(pcloud_dir: &Path, item: &str) -> PathBuf {
+    let item = item.trim_end_matches('/');
+    let readable: String = item
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+        .collect();
+    let hash = format!("{:x}", Sha256::digest(item.as_bytes()));
+
+    pcloud_dir.join(MANIFEST_DIR).join(format!("{}-{}.SHA256SUMS", readable, &hash[..16]))
+}
+
+/// Archivos regulares bajo `root` ordenados por ruta, sin el directorio de
+/// transferencias parciales. Si `root` es un archivo, solo él (por su nombre).
+pub fn generate(root: &Path, partial_dir: &str) -> Result<Manifest> {
+    let mut manifest = Manifest::new();
+
+    for entry in WalkDir::new(root)
+        .follow_links(false)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| entry.depth() == 0 || entry.file_name() != partial_dir)
+    {
+        let entry = entry.map_err(std::io::Error::from)?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        manifest.push((sha256_file(entry.path())?, relative_name(root, entry.path())));
+    }
+
+    Ok(manifest)
+}
+
+fn relative_name(root: &Path, path: &Path) -> String {
+    match path.strip_prefix(root) {
+        Ok(relative) if !relative.as_os_str().is_empty() => relative.to_string_lossy().into_owned(),
+        _ => path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+    }
+}
+
+/// Línea de SHA256SUMS como la escribe `sha256sum`: si la ruta lleva '\\'
+/// o saltos de línea se escapan y la línea empieza por '\\'
+fn format_line(hash: &str, name: &str) -> String {
+    if name.contains(['\\', '\n', '\r']) {
+        let escaped = name.replace('\\', "\\\\").replace('\n', "\\n").replace('\r', "\\r");
+        format!("\\{}  {}\n", hash, escaped)
+    } else {
+        format!("{}  {}\n", hash, name)
+    }
+}
+
+/// Deshace el escapado de `format_line` (solo en líneas que empiezan por '\\')
+fn unescape_name(name: &str) -> String {
+    let mut unescaped = String::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Escribe el manifiesto en formato SHA256SUMS (`<hash>  <ruta>`), de forma
+/// atómica para no dejar uno a medias en pCloud
+pub fn write(path: &Path, manifest: &Manifest) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let contents: String = manifest
+        .iter()
+        .map(|(hash, name)| format_line(hash, name))
+        .collect();
+    let temp = path.with_extension("SHA256SUMS.tmp");
+    std::fs::write(&temp, contents)?;
+    std::fs::rename(&temp, path)?;
+    Ok(())
+}
+
+pub fn read(path: &Path) -> Result<Manifest> {
+    std::fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (escaped, line) = match line.strip_prefix('\\') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            // `sha256sum -b` marca el modo binario con '*' en lugar del segundo espacio
+            line.split_once("  ")
+                .or_else(|| line.split_once(" *"))
+                .map(|(hash, name)| {
+                    let name = if escaped { unescape_name(name) } else { name.to_string() };
+                    (hash.to_lowercase(), name)
+                })
+                .ok_or_else(|| AppError::Validation(format!("Línea de manifiesto no válida en {:?}: {}", path, line)))
+        })
+        .collect()
+}
+
+/// Comprueba los archivos del manifiesto bajo `root`. Devuelve los que no
+/// coinciden y, aparte, los que no existen (p. ej. excluidos al bajar).
+pub fn verify(root: &Path, manifest: &Manifest) -> Result<(Vec<String>, Vec<String>)> {
+    let (mut mismatched, mut missing) = (Vec::new(), Vec::new());
+
+    for (hash, name) in manifest {
+        let path = if root.is_file() { root.to_path_buf() } else { root.join(name) };
+        if !path.is_file() {
+            missing.push(name.clone());
+        } else if sha256_file(&path)? != *hash {
+            mismatched.push(name.clone());
+        }
+    }
+
+    Ok((mismatched, missing))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_manifest_roundtrip_and_verify() {
+        let temp_dir = TempDir::new().unwrap();
+        let data = temp_dir.path().join("Documentos");
+        std::fs::create_dir_all(data.join("sub")).unwrap();
+        std::fs::create_dir_all(data.join(".rsync-partial")).unwrap();
+        std::fs::write(data.join("b.txt"), b"hola").unwrap();
+        std::fs::write(data.join("sub").join("a.txt"), b"adios").unwrap();
+        std::fs::write(data.join(".rsync-partial").join("b.txt"), b"ho").unwrap();
+
+        let manifest = generate(&data, ".rsync-partial").unwrap();
+        let names: Vec<&str> = manifest.iter().map(|(_, name)| name.as_str()).collect();
+        assert_eq!(names, ["b.txt", "sub/a.txt"]);
+        assert_eq!(manifest[0].0, "b221d9dbb083a7f33428d7c2a3c3198ae925614d70210e28716ccaa7cd4ddb79");
+
+        let path = manifest_path(temp_dir.path(), ".config/nvim/");
+        assert!(path.starts_with(temp_dir.path().join(".syncb_manifests")));
+        assert!(path.file_name().unwrap().to_string_lossy().starts_with(".config_nvim-"));
+        assert_eq!(path, manifest_path(temp_dir.path(), ".config/nvim"));
+        assert_ne!(path, manifest_path(temp_dir.path(), ".config_nvim"));
+        write(&path, &manifest).unwrap();
+        assert_eq!(read(&path).unwrap(), manifest);
+
+        std::fs::write(data.join("b.txt"), b"HOLA").unwrap();
+        std::fs::remove_file(data.join("sub").join("a.txt")).unwrap();
+        let (mismatched, missing) = verify(&data, &manifest).unwrap();
+        assert_eq!(mismatched, ["b.txt"]);
+        assert_eq!(missing, ["sub/a.txt"]);
+
+        // Un elemento que es un archivo se registra por su nombre
+        let single = generate(&data.join("b.txt"), ".rsync-partial").unwrap();
+        assert_eq!(single[0].1, "b.txt");
+        assert!(verify(&data.join("b.txt"), &single).unwrap().0.is_empty());
+    }
+
+    #[test]
+    fn test_manifest_escapes_names_like_sha256sum() {
+        let hash = "b221d9dbb083a7f33428d7c2a3c3198ae925614d70210e28716ccaa7cd4ddb79";
+        assert_eq!(format_line(hash, "a.txt"), format!("{}  a.txt\n", hash));
+        assert_eq!(format_line(hash, "a\nb\\c"), format!("\\{}  a\\nb\\\\c\n", hash));
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("x.SHA256SUMS");
+        let manifest = vec![
+            (hash.to_string(), "línea\nnueva.txt".to_string()),
+            (hash.to_string(), "barra\\invertida.txt".to_string()),
+            (hash.to_string(), "normal.txt".to_string()),
+        ];
+        write(&path, &manifest).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 3);
+        assert_eq!(read(&path).unwrap(), manifest);
+    }
+}
@@ -0,0 +1,231 @@
+use crate::config::{EmailConfig, NotificationBackend, NotificationsConfig};
+use notify_rust::Notification;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Envía la notificación por el backend configurado. Nunca falla: un
+/// problema al notificar solo se registra como aviso.
+pub fn send(config: &NotificationsConfig, title: &str, body: &str, payload: &serde_json::Value) {
+    let result = match config.backend {
+        NotificationBackend::None => Ok(()),
+        NotificationBackend::Desktop => send_desktop(title, body),
+        NotificationBackend::Webhook => send_webhook(config, payload),
+        NotificationBackend::Email => send_email(config, title, body),
+    };
+
+    if let Err(e) = result {
+        log::warn!("No se pudo enviar la notificación ({:?}): {}", config.backend, e);
+    }
+}
+
+fn send_desktop(title: &str, body: &str) -> Result<(), String> {
+    Notification::new()
+        .summary(title)
+        .body(body)
+        .icon("dialog-information")
+        .show()
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// POST del resumen en JSON a `webhook_url` (con curl, como rsync: sin cliente HTTP propio)
+fn send_webhook(config: &NotificationsConfig, payload: &serde_json::Value) -> Result<(), String> {
+    let url = config
+        .webhook_url
+        .as_deref()
+        .ok_or("falta notifications.webhook_url")?;
+
+    let mut command = curl_command();
+    command.args(["-X", "POST", "-H", "Content-Type: application/json", "--data-binary", "@-", url]);
+    run_with_stdin(command, &payload.to_string())
+}
+
+/// Correo por SMTP usando curl (`smtp://` o `smtps://` en `smtp_url`)
+fn send_email(config: &NotificationsConfig, title: &str, body: &str) -> Result<(), String> {
+    let email = config.email.as_ref().ok_or("falta la sección [notifications.email]")?;
+    if email.to.is_empty() {
+        return Err("notifications.email.to está vacío".to_string());
+    }
+
+    let mut command = curl_command();
+    command.args(["--url", &email.smtp_url, "--mail-from", &email.from]);
+    for to in &email.to {
+        command.args(["--mail-rcpt", to]);
+    }
+
+    // Las credenciales nunca en la línea de órdenes (visible en ps): van en
+    // un archivo de configuración de curl solo legible por el usuario, ya
+    // que la entrada estándar lleva el mensaje
+    let credentials = match &email.username {
+        Some(username) => {
+            let password = email.password.as_deref().unwrap_or_default();
+            let path = write_curl_credentials(username, password).map_err(|e| e.to_string())?;
+            command.arg("-K").arg(&path);
+            Some(path)
+        }
+        None => None,
+    };
+    command.args(["-T", "-"]);
+
+    let result = run_with_stdin(command, &email_message(email, title, body));
+    if let Some(path) = credentials {
+        let _ = std::fs::remove_file(path);
+    }
+    result
+}
+
+/// curl con límites de tiempo: se notifica con el lock aún tomado, y un
+/// servidor que no responde no debe bloquear esta ni las siguientes ejecuciones
+fn curl_command() -> Command {
+    let mut command = Command::new("curl");
+    command.args(["-fsS", "--connect-timeout", "10", "--max-time", "30"]);
+    command
+}
+
+/// Archivo temporal (0600) con `user = "usuario:contraseña"` para `curl -K`
+fn write_curl_credentials(username: &str, password: &str) -> std::io::Result<PathBuf> {
+    let path = std::env::temp_dir().join(format!("syncb-curl-{}.conf", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&path)?;
+    file.write_all(curl_user_line(username, password).as_bytes())?;
+    Ok(path)
+}
+
+/// Línea de configuración de curl con el valor entre comillas escapado
+fn curl_user_line(username: &str, password: &str) -> String {
+    let value = format!("{}:{}", username, password)
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r");
+    format!("user = \"{}\"\n", value)
+}
+
+fn email_message(email: &EmailConfig, title: &str, body: &str) -> String {
+    format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}\r\n",
+        email.from,
+        email.to.join(", "),
+        encode_header(title),
+        body.replace('\n', "\r\n")
+    )
+}
+
+/// Valor de cabecera en ASCII imprimible tal cual; si no, como palabras
+/// codificadas RFC 2047 ("Q", UTF-8) de hasta 75 caracteres, plegadas en
+/// varias líneas. Un salto de línea nunca llega a la cabecera.
+fn encode_header(value: &str) -> String {
+    if value.chars().all(|c| c.is_ascii() && !c.is_ascii_control()) {
+        return value.to_string();
+    }
+
+    // 75 menos "=?UTF-8?Q?" y "?="
+    const MAX_ENCODED: usize = 63;
+    let mut words = Vec::new();
+    let mut current = String::new();
+    for c in value.chars() {
+        let mut bytes = [0; 4];
+        let encoded: String = c
+            .encode_utf8(&mut bytes)
+            .bytes()
+            .map(|byte| match byte {
+                b' ' => "_".to_string(),
+                b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' | b'!' | b'*' | b'+' | b'-' | b'/' => {
+                    (byte as char).to_string()
+                }
+                _ => format!("={:02X}", byte),
+            })
+            .collect();
+
+        if current.len() + encoded.len() > MAX_ENCODED {
+            words.push(std::mem::take(&mut current));
+        }
+        current.push_str(&encoded);
+    }
+    words.push(current);
+
+    words
+        .iter()
+        .map(|word| format!("=?UTF-8?Q?{}?=", word))
+        .collect::<Vec<_>>()
+        .join("\r\n ")
+}
+
+fn run_with_stdin(mut command: Command, input: &str) -> Result<(), String> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("no se pudo ejecutar curl: {}", e))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes()).map_err(|e| e.to_string())?;
+    }
+
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_email_message_headers() {
+        let email = EmailConfig {
+            smtp_url: "smtps://smtp.example.com:465".to_string(),
+            from: "syncb@example.com".to_string(),
+            to: vec!["a@example.com".to_string(), "b@example.com".to_string()],
+            username: None,
+            password: None,
+        };
+
+        let message = email_message(&email, "Sincronización syncb", "línea 1\nlínea 2");
+
+        assert!(message.starts_with("From: syncb@example.com\r\nTo: a@example.com, b@example.com\r\n"));
+        assert!(message.contains("Subject: =?UTF-8?Q?Sincronizaci=C3=B3n_syncb?=\r\n"));
+        assert!(message.ends_with("\r\n\r\nlínea 1\r\nlínea 2\r\n"));
+    }
+
+    #[test]
+    fn test_curl_command_has_timeouts() {
+        let command = curl_command();
+        let args: Vec<_> = command.get_args().map(|arg| arg.to_string_lossy().to_string()).collect();
+        assert_eq!(args, ["-fsS", "--connect-timeout", "10", "--max-time", "30"]);
+    }
+
+    #[test]
+    fn test_encode_header() {
+        assert_eq!(encode_header("syncb: OK"), "syncb: OK");
+        assert_eq!(encode_header("a\r\nBcc: x"), "=?UTF-8?Q?a=0D=0ABcc=3A_x?=");
+
+        let long = encode_header(&"ñ".repeat(40));
+        let words: Vec<&str> = long.split("\r\n ").collect();
+        assert_eq!(words.len(), 4);
+        assert!(words.iter().all(|word| word.len() <= 75 && word.starts_with("=?UTF-8?Q?")));
+    }
+
+    #[test]
+    fn test_curl_credentials_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        assert_eq!(curl_user_line("yo", "a\"b\\c"), "user = \"yo:a\\\"b\\\\c\"\n");
+
+        let path = write_curl_credentials("yo", "secreto").unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "user = \"yo:secreto\"\n");
+        std::fs::remove_file(path).unwrap();
+    }
+}
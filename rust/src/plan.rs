@@ -0,0 +1,204 @@
+use crate::error::{AppError, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Versión del formato; --plan-in rechaza planes de otra versión
+pub const PLAN_VERSION: u32 = 1;
+
+/// Plan de ejecución resuelto (--plan-out) que --plan-in ejecuta tal cual
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionPlan {
+    pub version: u32,
+    pub mode: String,
+    pub host: String,
+    pub remote: Option<String>,
+    pub items: Vec<PlannedItem>,
+}
+
+/// Un elemento del plan: sus rutas y los comandos rsync exactos, en orden
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlannedItem {
+    pub item: String,
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    pub timeout_minutes: u32,
+    /// Crear el directorio padre del destino antes de ejecutar (--snapshot)
+    pub create_destination_parent: bool,
+    /// Patrones de --exclude y archivos de --exclude-from, extraídos de los comandos
+    pub exclusions: Vec<String>,
+    /// Vectores de argumentos completos (programa incluido)
+    pub commands: Vec<Vec<String>>,
+}
+
+impl ExecutionPlan {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let plan: ExecutionPlan = serde_json::from_str(&contents)
+            .map_err(|e| AppError::Config(format!("Plan no válido en {:?}: {}", path, e)))?;
+
+        if plan.version != PLAN_VERSION {
+            return Err(AppError::Config(format!(
+                "Versión de plan no admitida: {} (se esperaba {})",
+                plan.version, PLAN_VERSION
+            )));
+        }
+        Ok(plan)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(std::io::Error::from)?;
+        std::fs::write(path, json + "\n")?;
+        Ok(())
+    }
+}
+
+/// Argumentos exactos de `command`; falla si alguno no es UTF-8 (no se
+/// podría guardar en JSON sin alterarlo)
+pub fn command_argv(command: &Command) -> Result<Vec<String>> {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| {
+            arg.to_str().map(str::to_string).ok_or_else(|| {
+                AppError::Validation(format!("Argumento no UTF-8 en el plan: {:?}", arg))
+            })
+        })
+        .collect()
+}
+
+/// Opciones con las que rsync ejecutaría otro programa o cambiaría de papel;
+/// un plan no puede traerlas (tampoco abreviadas, p. ej. `--rs`)
+const FORBIDDEN_OPTIONS: &[&str] = &[
+    "--rsh", "--rsync-path", "--remote-option", "--daemon", "--config", "--server",
+    "--sender", "--read-batch", "--write-batch", "--only-write-batch",
+];
+
+/// Opciones que syncb escribe con el valor en el argumento siguiente
+const OPTIONS_WITH_SEPARATE_VALUE: &[&str] = &["--exclude", "--exclude-from", "--include"];
+
+/// Comando de un plan guardado. Solo se ejecuta rsync, nunca otro programa
+/// que alguien haya escrito en el archivo: se rechazan las opciones que
+/// lanzan programas, y `-e` solo se admite con `rsh`, el ssh que usaría
+/// syncb para el remoto actual
+pub fn argv_command(argv: &[String], rsh: Option<&str>) -> Result<Command> {
+    let (program, args) = argv
+        .split_first()
+        .ok_or_else(|| AppError::Config("Comando vacío en el plan".to_string()))?;
+    if program != "rsync" {
+        return Err(AppError::Config(format!("Programa no permitido en el plan: {}", program)));
+    }
+
+    let not_allowed = |arg: &str| AppError::Config(format!("Opción no permitida en el plan: {}", arg));
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        if OPTIONS_WITH_SEPARATE_VALUE.contains(&arg.as_str()) {
+            rest.next();
+            continue;
+        }
+        if arg == "-e" {
+            match rest.next() {
+                Some(value) if Some(value.as_str()) == rsh => continue,
+                value => return Err(not_allowed(&format!("-e {}", value.map_or("", String::as_str)))),
+            }
+        }
+
+        let name = arg.split_once('=').map_or(arg.as_str(), |(name, _)| name);
+        let forbidden = match name.strip_prefix("--") {
+            Some(long) => !long.is_empty() && FORBIDDEN_OPTIONS.iter().any(|option| option.starts_with(name)),
+            None => name.strip_prefix('-').is_some_and(|flags| flags.contains(['e', 'M'])),
+        };
+        if forbidden {
+            return Err(not_allowed(arg));
+        }
+    }
+
+    let mut command = Command::new(program);
+    command.args(args);
+    Ok(command)
+}
+
+/// Si el comando borra en el destino (--delete, --delete-after, --del...)
+pub fn deletes(argv: &[String]) -> bool {
+    argv.iter().skip(1).any(|arg| arg.starts_with("--del"))
+}
+
+/// Valores de `--exclude X` y `--exclude-from X` en un vector de argumentos
+pub fn exclusions_in(argv: &[String]) -> Vec<String> {
+    argv.windows(2)
+        .filter(|pair| pair[0] == "--exclude" || pair[0] == "--exclude-from")
+        .map(|pair| pair[1].clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_plan_round_trip_keeps_argv() {
+        let mut command = Command::new("rsync");
+        command.args(["-av", "--exclude", "*.tmp", "--exclude-from", "/reglas", "/origen/", "/destino"]);
+        let argv = command_argv(&command).unwrap();
+        assert_eq!(argv[0], "rsync");
+        assert_eq!(exclusions_in(&argv), vec!["*.tmp", "/reglas"]);
+
+        let plan = ExecutionPlan {
+            version: PLAN_VERSION,
+            mode: "Upload".to_string(),
+            host: "equipo".to_string(),
+            remote: None,
+            items: vec![PlannedItem {
+                item: "Documentos".to_string(),
+                source: PathBuf::from("/origen"),
+                destination: PathBuf::from("/destino"),
+                timeout_minutes: 30,
+                create_destination_parent: false,
+                exclusions: exclusions_in(&argv),
+                commands: vec![argv.clone()],
+            }],
+        };
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("plan.json");
+        plan.save(&path).unwrap();
+        let loaded = ExecutionPlan::load(&path).unwrap();
+        assert_eq!(loaded, plan);
+
+        let replayed = argv_command(&loaded.items[0].commands[0], None).unwrap();
+        assert_eq!(command_argv(&replayed).unwrap(), argv);
+    }
+
+    #[test]
+    fn test_argv_command_only_runs_rsync() {
+        let argv = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+
+        assert!(argv_command(&argv(&["sh", "-c", "rm -rf ~"]), None).is_err());
+        assert!(argv_command(&argv(&["/tmp/rsync", "-av"]), None).is_err());
+        assert!(argv_command(&[], None).is_err());
+        assert!(argv_command(&argv(&["rsync", "-av", "/a/", "/b/"]), None).is_ok());
+
+        // Ni programas a través de rsync, ni abreviados ni en grupos de opciones cortas
+        for evil in [
+            &["rsync", "--rsh=sh -c 'rm -rf ~'", "/a/", "/b/"][..],
+            &["rsync", "--rs=sh", "/a/", "/b/"],
+            &["rsync", "--rsync-path=evil", "/a/", "host:/b/"],
+            &["rsync", "-ave", "sh", "/a/", "/b/"],
+            &["rsync", "-M--rsync-path=evil", "/a/", "/b/"],
+            &["rsync", "-e", "sh -c evil", "/a/", "/b/"],
+            &["rsync", "-e"],
+        ] {
+            assert!(argv_command(&argv(evil), Some("ssh")).is_err(), "{:?}", evil);
+        }
+
+        // -e solo con el ssh del remoto; los valores de --exclude no son opciones
+        let ssh = argv(&["rsync", "-av", "-e", "ssh -p 2222", "--exclude", "-eso", "/a/", "u@h:/b/"]);
+        assert!(argv_command(&ssh, Some("ssh -p 2222")).is_ok());
+        assert!(argv_command(&ssh, Some("ssh")).is_err());
+        assert!(argv_command(&ssh, None).is_err());
+
+        assert!(deletes(&argv(&["rsync", "-av", "--delete-after", "/a/", "/b/"])));
+        assert!(deletes(&argv(&["rsync", "--del", "/a/", "/b/"])));
+        assert!(!deletes(&argv(&["rsync", "-av", "/a/", "/b/"])));
+    }
+}
@@ -0,0 +1,205 @@
+use crate::error::Result;
+use serde::Serialize;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// Archivo presente en los dos lados cuyo contenido o fecha no coincide
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Difference {
+    pub path: String,
+    /// "checksum", "tamaño" y/o "fecha"
+    pub reasons: Vec<&'static str>,
+}
+
+/// Divergencias de un elemento entre el directorio local y pCloud
+/// (--compare-checksums), en los dos sentidos
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ItemDiff {
+    pub item: String,
+    pub different: Vec<Difference>,
+    pub only_local: Vec<String>,
+    pub only_remote: Vec<String>,
+}
+
+impl ItemDiff {
+    /// Salida de `rsync --itemize-changes --checksum --delete --dry-run
+    /// <local>/ <remoto>/`: lo que se crearía solo está en local, lo que se
+    /// borraría solo en el remoto y el resto difiere. Los cambios de
+    /// atributos de directorios, permisos o propietario no cuentan.
+    pub fn from_itemize_output(item: &str, output: &str) -> Self {
+        let mut diff = Self {
+            item: item.to_string(),
+            ..Default::default()
+        };
+
+        for line in output.lines() {
+            let Some((code, path)) = line.split_once(' ') else { continue };
+            let path = path.trim_start().to_string();
+
+            if code == "*deleting" {
+                diff.only_remote.push(path);
+                continue;
+            }
+
+            let mut chars = code.chars();
+            let (Some(kind), Some(file_type)) = (chars.next(), chars.next()) else { continue };
+            if code.len() < 3 || !matches!(kind, '<' | '>' | 'c' | 'h' | '.') {
+                continue;
+            }
+
+            let attributes = chars.as_str();
+            if attributes.chars().all(|c| c == '+') {
+                diff.only_local.push(path);
+            } else if file_type != 'd' {
+                let reasons = difference_reasons(attributes);
+                if !reasons.is_empty() {
+                    diff.different.push(Difference { path, reasons });
+                }
+            }
+        }
+
+        diff
+    }
+
+    /// Elemento que no existe en local: todo lo que hay en `remote` está
+    /// solo en el remoto, con las rutas que daría rsync (relativas al
+    /// elemento y los directorios con '/' final; un archivo, por su nombre)
+    pub fn from_remote_only(item: &str, remote: &Path) -> Result<Self> {
+        let mut diff = Self {
+            item: item.to_string(),
+            ..Default::default()
+        };
+
+        if !remote.is_dir() {
+            let name = remote.file_name().unwrap_or(remote.as_os_str());
+            diff.only_remote.push(name.to_string_lossy().into_owned());
+            return Ok(diff);
+        }
+
+        for entry in WalkDir::new(remote).min_depth(1).follow_links(false).sort_by_file_name() {
+            let entry = entry.map_err(std::io::Error::from)?;
+            let relative = entry.path().strip_prefix(remote).unwrap_or(entry.path());
+            let mut path = relative.to_string_lossy().into_owned();
+            if entry.file_type().is_dir() {
+                path.push('/');
+            }
+            diff.only_remote.push(path);
+        }
+
+        Ok(diff)
+    }
+
+    /// Rutas que no coinciden entre los dos lados
+    pub fn total(&self) -> usize {
+        self.different.len() + self.only_local.len() + self.only_remote.len()
+    }
+}
+
+/// Atributos de --itemize-changes tras el tipo ("cstpoguax"): solo
+/// contenido (c), tamaño (s) y fecha de modificación (t, o T si no se guarda)
+fn difference_reasons(attributes: &str) -> Vec<&'static str> {
+    let mut chars = attributes.chars();
+    let mut reasons = Vec::new();
+    if chars.next() == Some('c') {
+        reasons.push("checksum");
+    }
+    if chars.next() == Some('s') {
+        reasons.push("tamaño");
+    }
+    if matches!(chars.next(), Some('t' | 'T')) {
+        reasons.push("fecha");
+    }
+    reasons
+}
+
+/// Informe en tabla: una línea por elemento y, debajo, cada ruta marcada
+/// con `≠` (difiere), `<` (solo en local) o `>` (solo en el remoto)
+pub fn display(diffs: &[ItemDiff]) {
+    println!("Comparación por checksum (local <-> remoto):");
+    for diff in diffs {
+        if diff.total() == 0 {
+            println!("  {}: sincronizado", diff.item);
+            continue;
+        }
+
+        println!(
+            "  {}: {} distintos, {} solo en local, {} solo en el remoto",
+            diff.item,
+            diff.different.len(),
+            diff.only_local.len(),
+            diff.only_remote.len()
+        );
+        for difference in &diff.different {
+            println!("      ≠ {} ({})", difference.path, difference.reasons.join(", "));
+        }
+        for path in &diff.only_local {
+            println!("      < {}", path);
+        }
+        for path in &diff.only_remote {
+            println!("      > {}", path);
+        }
+    }
+
+    let total: usize = diffs.iter().map(ItemDiff::total).sum();
+    println!("Total de diferencias: {}", total);
+}
+
+/// Informe en JSON (con --json)
+pub fn display_json(diffs: &[ItemDiff]) {
+    match serde_json::to_string_pretty(diffs) {
+        Ok(json) => println!("{}", json),
+        Err(e) => log::error!("Error generando el informe JSON: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_item_diff_from_itemize_output() {
+        let output = "\
+sending incremental file list
+*deleting   docs/solo remoto.txt
+.d..t...... docs/
+cd+++++++++ docs/nuevo/
+>f+++++++++ docs/nuevo/a.txt
+>fcs....... docs/cambiado.txt
+.f..t...... docs/tocado.txt
+>f..T...... docs/reescrito.txt
+.f...p..... docs/permisos.txt
+
+sent 1,234 bytes  received 56 bytes  2,580.00 bytes/sec
+";
+        let diff = ItemDiff::from_itemize_output("docs", output);
+
+        assert_eq!(diff.only_remote, ["docs/solo remoto.txt"]);
+        assert_eq!(diff.only_local, ["docs/nuevo/", "docs/nuevo/a.txt"]);
+        assert_eq!(
+            diff.different,
+            [
+                Difference { path: "docs/cambiado.txt".to_string(), reasons: vec!["checksum", "tamaño"] },
+                Difference { path: "docs/tocado.txt".to_string(), reasons: vec!["fecha"] },
+                Difference { path: "docs/reescrito.txt".to_string(), reasons: vec!["fecha"] },
+            ]
+        );
+        assert_eq!(diff.total(), 6);
+        assert_eq!(serde_json::to_value(&diff).unwrap()["different"][0]["reasons"][1], "tamaño");
+    }
+
+    #[test]
+    fn test_item_diff_from_remote_only() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let remote = temp_dir.path().join("Documentos");
+        std::fs::create_dir_all(remote.join("sub")).unwrap();
+        std::fs::write(remote.join("sub/b.txt"), "b").unwrap();
+        std::fs::write(remote.join("a.txt"), "a").unwrap();
+
+        let diff = ItemDiff::from_remote_only("Documentos", &remote).unwrap();
+        assert_eq!(diff.only_remote, ["a.txt", "sub/", "sub/b.txt"]);
+        assert!(diff.only_local.is_empty() && diff.different.is_empty());
+
+        let diff = ItemDiff::from_remote_only(".bashrc", &remote.join("a.txt")).unwrap();
+        assert_eq!(diff.only_remote, ["a.txt"]);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::error::Result;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Elementos completados en la ejecución en curso, para que --resume-items
+/// pueda saltárselos si esta se interrumpe. Solo vale para una ejecución con
+/// los mismos parámetros (`run_key`) y reciente.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumeState {
+    run_key: String,
+    updated: DateTime<Local>,
+    completed: Vec<String>,
+    #[serde(skip)]
+    file: PathBuf,
+}
+
+impl ResumeState {
+    /// Estado vacío para una nueva ejecución
+    pub fn new(file: &Path, run_key: String) -> Self {
+        Self {
+            run_key,
+            updated: Local::now(),
+            completed: Vec::new(),
+            file: file.to_path_buf(),
+        }
+    }
+
+    /// Estado de una ejecución interrumpida con los mismos parámetros y
+    /// actualizado hace menos de `max_age`; None si no lo hay o no es legible
+    pub fn load(file: &Path, run_key: &str, max_age: chrono::Duration) -> Option<Self> {
+        let contents = std::fs::read_to_string(file).ok()?;
+        let mut state: ResumeState = serde_json::from_str(&contents).ok()?;
+        if state.run_key != run_key || Local::now() - state.updated > max_age {
+            return None;
+        }
+
+        state.file = file.to_path_buf();
+        Some(state)
+    }
+
+    pub fn completed(&self) -> &[String] {
+        &self.completed
+    }
+
+    pub fn updated(&self) -> DateTime<Local> {
+        self.updated
+    }
+
+    /// Anota un elemento terminado y guarda el estado de inmediato: debe
+    /// sobrevivir a una caída justo después
+    pub fn record(&mut self, item: &str) -> Result<()> {
+        if !self.completed.iter().any(|completed| completed == item) {
+            self.completed.push(item.to_string());
+        }
+        self.updated = Local::now();
+        self.save()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.file.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let temp = self.file.with_extension("json.tmp");
+        std::fs::write(&temp, serde_json::to_vec(self).map_err(std::io::Error::from)?)?;
+        std::fs::rename(&temp, &self.file)?;
+        Ok(())
+    }
+
+    /// La ejecución terminó bien: no queda nada que reanudar
+    pub fn clear(file: &Path) -> Result<()> {
+        match std::fs::remove_file(file) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Identificador de una ejecución a partir de los parámetros que determinan
+/// qué se transfiere (modo, equipo, configuración, elementos, filtros...)
+pub fn run_key(parts: &[String]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_resume_state_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("estado").join("resume_state.json");
+        let key = run_key(&["Upload".to_string(), "Documentos".to_string()]);
+        let day = chrono::Duration::hours(24);
+
+        assert!(ResumeState::load(&file, &key, day).is_none());
+
+        let mut state = ResumeState::new(&file, key.clone());
+        state.record("Documentos").unwrap();
+        state.record("Documentos").unwrap();
+
+        let loaded = ResumeState::load(&file, &key, day).unwrap();
+        assert_eq!(loaded.completed(), ["Documentos".to_string()]);
+
+        // Otros parámetros o un estado demasiado antiguo no se reanudan
+        let other = run_key(&["Download".to_string(), "Documentos".to_string()]);
+        assert!(ResumeState::load(&file, &other, day).is_none());
+        assert!(ResumeState::load(&file, &key, chrono::Duration::zero()).is_none());
+
+        ResumeState::clear(&file).unwrap();
+        ResumeState::clear(&file).unwrap();
+        assert!(ResumeState::load(&file, &key, day).is_none());
+    }
+}
@@ -0,0 +1,206 @@
+use crate::cli::Cli;
+use crate::config::AppConfig;
+use crate::error::{AppError, Result};
+use crate::stats::format_bytes;
+use crate::sync::SNAPSHOT_FORMAT;
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use walkdir::WalkDir;
+
+/// Instantánea del backup de solo lectura más antigua que la retención
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expired {
+    pub path: PathBuf,
+    pub date: DateTime<Local>,
+    pub bytes: u64,
+}
+
+/// Lo que hay que borrar bajo `root`: las instantáneas cuya fecha (la del
+/// nombre) es anterior a `now - max_age`, salvo la más reciente, que es la
+/// base de --link-dest. Nada más se toca: fuera de las instantáneas está la
+/// copia vigente de cada elemento, y su mtime es el del original (rsync -a
+/// lo conserva), así que un archivo estable parecería caducado.
+pub fn find_expired(root: &Path, max_age: Duration, now: DateTime<Local>) -> Result<Vec<Expired>> {
+    let limit = now - chrono::Duration::from_std(max_age).unwrap_or(chrono::Duration::MAX);
+    let mut snapshots = Vec::new();
+
+    for entry in std::fs::read_dir(root)? {
+        let entry = entry?;
+        let path = entry.path();
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        if let Some(date) = snapshot_date(&entry.file_name().to_string_lossy()) {
+            snapshots.push(Expired { bytes: tree_size(&path), path, date });
+        }
+    }
+
+    snapshots.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.path.cmp(&b.path)));
+    snapshots.pop();
+    snapshots.retain(|snapshot| snapshot.date < limit);
+    Ok(snapshots)
+}
+
+fn snapshot_date(name: &str) -> Option<DateTime<Local>> {
+    let naive = NaiveDateTime::parse_from_str(name, SNAPSHOT_FORMAT).ok()?;
+    Local.from_local_datetime(&naive).earliest()
+}
+
+fn tree_size(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Solo se poda `pcloud_backup_readonly`, y solo si está dentro de pCloud,
+/// no contiene ni el backup común ni los datos locales y no está dentro del
+/// backup común (sí puede estarlo en `local_dir`, que suele ser el home)
+fn backup_root(config: &AppConfig) -> Result<PathBuf> {
+    let general = &config.general;
+    let root = general.pcloud_backup_readonly.clone();
+    let local_dir = config.local_dir();
+
+    if !root.starts_with(&general.pcloud_mount_point) || root == general.pcloud_mount_point {
+        return Err(AppError::Validation(format!(
+            "--prune-older-than solo actúa dentro de pCloud y {:?} no lo está",
+            root
+        )));
+    }
+    let common = general.pcloud_backup_comun.as_path();
+    if common.starts_with(&root) || local_dir.starts_with(&root) || root.starts_with(common) {
+        return Err(AppError::Validation(format!(
+            "--prune-older-than: {:?} se solapa con datos en uso ({:?} o {:?})",
+            root, common, local_dir
+        )));
+    }
+    if !root.is_dir() {
+        return Err(AppError::PCloudNotMounted(format!("No existe el backup de solo lectura {:?}", root)));
+    }
+    Ok(root)
+}
+
+/// --prune-older-than: muestra lo que caduca, pide confirmación (salvo
+/// --yes) y lo borra. En --dry-run solo muestra. Devuelve cuántos
+/// elementos se borraron y los bytes liberados.
+pub fn prune(args: &Cli, config: &AppConfig, max_age: Duration) -> Result<(usize, u64)> {
+    let root = backup_root(config)?;
+    let expired = find_expired(&root, max_age, Local::now())?;
+    let total: u64 = expired.iter().map(|entry| entry.bytes).sum();
+
+    if expired.is_empty() {
+        println!("Nada anterior a {} en {}", humantime::format_duration(max_age), root.display());
+        return Ok((0, 0));
+    }
+
+    println!("Caducado en {} (más de {}):", root.display(), humantime::format_duration(max_age));
+    for entry in &expired {
+        println!(
+            "  {}  {:>10}  {}",
+            entry.date.format("%Y-%m-%d %H:%M"),
+            format_bytes(entry.bytes),
+            entry.path.strip_prefix(&root).unwrap_or(&entry.path).display()
+        );
+    }
+    println!("Total: {} elementos, {}", expired.len(), format_bytes(total));
+
+    if args.dry_run {
+        println!("SIMULACIÓN: no se ha borrado nada");
+        return Ok((0, 0));
+    }
+    if !args.yes {
+        println!("¿Borrar definitivamente? [s/N]: ");
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        if !input.trim().eq_ignore_ascii_case("s") {
+            return Err(AppError::Cancelled);
+        }
+    }
+
+    let (mut deleted, mut freed) = (0, 0);
+    for entry in &expired {
+        match std::fs::remove_dir_all(&entry.path) {
+            Ok(()) => {
+                log::info!("Borrado por retención: {}", entry.path.display());
+                deleted += 1;
+                freed += entry.bytes;
+            }
+            Err(e) => log::error!("No se pudo borrar {}: {}", entry.path.display(), e),
+        }
+    }
+
+    println!("Borrados {} elementos; liberados {}", deleted, format_bytes(freed));
+    if deleted < expired.len() {
+        return Err(AppError::Sync(format!("{} elementos no se pudieron borrar", expired.len() - deleted)));
+    }
+    Ok((deleted, freed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_config;
+    use std::time::SystemTime;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_backup_root_only_readonly_backup() {
+        let temp_dir = TempDir::new().unwrap();
+        let home = temp_dir.path();
+        let config = |readonly: &str| {
+            let mut config = test_config(home);
+            config.general.pcloud_backup_readonly = PathBuf::from(readonly);
+            config.config_path = home.join("config.toml");
+            config
+        };
+
+        let readonly = home.join("pCloudDrive/pCloud Backup/equipo");
+        std::fs::create_dir_all(&readonly).unwrap();
+        assert_eq!(backup_root(&config(&readonly.display().to_string())).unwrap(), readonly);
+
+        for wrong in ["pCloudDrive", "pCloudDrive/Backups", "pCloudDrive/Backups/Backup_Comun/x", "otro"] {
+            let path = home.join(wrong);
+            std::fs::create_dir_all(&path).unwrap();
+            assert!(backup_root(&config(&path.display().to_string())).is_err(), "{}", wrong);
+        }
+    }
+
+    #[test]
+    fn test_find_expired_only_prunes_old_snapshots() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let now = Local.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let old = SystemTime::from(now - chrono::Duration::days(40));
+
+        for name in ["2024-01-01_000000", "2024-02-01_000000", "2024-03-01_000000"] {
+            std::fs::create_dir_all(root.join(name)).unwrap();
+            std::fs::write(root.join(name).join("a.txt"), b"1234").unwrap();
+        }
+        std::fs::create_dir_all(root.join("Documentos")).unwrap();
+        for name in ["Documentos/viejo.txt", "Documentos/nuevo.txt", ".syncb_symlinks.meta"] {
+            std::fs::write(root.join(name), b"x").unwrap();
+        }
+        let set_mtime = |path: PathBuf| {
+            std::fs::File::options().write(true).open(path).unwrap().set_modified(old).unwrap();
+        };
+        set_mtime(root.join("Documentos/viejo.txt"));
+        set_mtime(root.join(".syncb_symlinks.meta"));
+
+        // Documentos/viejo.txt tiene el mtime antiguo del original, pero es
+        // la copia vigente de un archivo que sigue existiendo: no caduca
+        let expired = find_expired(root, Duration::from_secs(30 * 86400), now).unwrap();
+        let names: Vec<_> = expired.iter().map(|e| e.path.strip_prefix(root).unwrap().to_path_buf()).collect();
+        assert_eq!(names, [PathBuf::from("2024-01-01_000000"), PathBuf::from("2024-02-01_000000")]);
+        assert_eq!(expired[0].bytes, 4);
+        assert!(root.join("Documentos/viejo.txt").exists());
+
+        // Aunque todas caduquen, la última instantánea se conserva
+        let expired = find_expired(root, Duration::from_secs(1), now).unwrap();
+        assert!(!expired.iter().any(|e| e.path.ends_with("2024-03-01_000000")));
+    }
+}
@@ -0,0 +1,36 @@
+use lazy_static::lazy_static;
+use signal_hook::consts::{SIGINT, SIGTERM};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+lazy_static! {
+    /// Se activa al recibir SIGINT/SIGTERM; la sincronización lo consulta entre elementos
+    static ref SHUTDOWN: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+}
+
+/// Registra los manejadores de señales. La primera señal pide una parada
+/// ordenada; una segunda termina el proceso inmediatamente.
+pub fn setup_signal_handlers() {
+    for signal in [SIGINT, SIGTERM] {
+        // El orden importa: la salida condicional solo actúa si el flag ya estaba activo
+        let _ = signal_hook::flag::register_conditional_shutdown(signal, 130, Arc::clone(&SHUTDOWN));
+        let _ = signal_hook::flag::register(signal, Arc::clone(&SHUTDOWN));
+    }
+}
+
+/// Pide la misma parada ordenada que una señal (p. ej. al agotar --max-runtime)
+pub fn request() {
+    SHUTDOWN.store(true, Ordering::Relaxed);
+}
+
+pub fn is_requested() -> bool {
+    SHUTDOWN.load(Ordering::Relaxed)
+}
+
+/// Futuro que se completa cuando se ha solicitado la parada
+pub async fn requested() {
+    while !is_requested() {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+}
@@ -1,19 +1,286 @@
-use std::time::{Duration, Instant};
-use notify_rust::Notification;
+use bytesize::ByteSize;
+use serde::Serialize;
+use crate::config::NotificationsConfig;
+use crate::error::{describe_rsync_exit, AppError};
+use crate::notifications;
+use std::collections::{BTreeSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Cambios de un elemento según la salida --itemize-changes de rsync
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ItemReport {
+    pub item: String,
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    pub deleted: Vec<String>,
+    /// Desaparecidos del origen durante la transferencia (rsync código 24)
+    pub vanished: Vec<String>,
+    /// Omitidos por --max-size (o por no caber con --exclude-larger-than-free)
+    pub oversized: Vec<String>,
+    /// Rutas que no caben en la muestra de `report_sample_size`
+    #[serde(skip_serializing_if = "ReportOmitted::is_empty")]
+    pub omitted: ReportOmitted,
+}
+
+/// Cuántas rutas de cada lista de un `ItemReport` se descartaron
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize)]
+pub struct ReportOmitted {
+    pub created: usize,
+    pub updated: usize,
+    pub deleted: usize,
+}
+
+impl ReportOmitted {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl ItemReport {
+    pub fn from_itemize_output(item: &str, output: &str) -> Self {
+        let mut parser = ItemizeParser::new(item, None);
+        for line in output.lines() {
+            parser.push_line(line);
+        }
+        parser.finish()
+    }
+
+    pub fn created_count(&self) -> usize {
+        self.created.len() + self.omitted.created
+    }
+
+    pub fn updated_count(&self) -> usize {
+        self.updated.len() + self.omitted.updated
+    }
+
+    pub fn deleted_count(&self) -> usize {
+        self.deleted.len() + self.omitted.deleted
+    }
+}
+
+/// Primeras y últimas `limit` rutas de una lista (todas sin límite)
+#[derive(Debug, Default)]
+struct PathSample {
+    limit: Option<usize>,
+    head: Vec<String>,
+    tail: VecDeque<String>,
+    omitted: usize,
+}
+
+impl PathSample {
+    fn push(&mut self, path: String) {
+        match self.limit {
+            Some(limit) if self.head.len() >= limit => {
+                self.tail.push_back(path);
+                if self.tail.len() > limit {
+                    self.tail.pop_front();
+                    self.omitted += 1;
+                }
+            }
+            _ => self.head.push(path),
+        }
+    }
+
+    fn finish(mut self) -> (Vec<String>, usize) {
+        self.head.extend(self.tail);
+        (self.head, self.omitted)
+    }
+}
+
+/// Lee la salida --itemize-changes de rsync línea a línea. Con `sample`
+/// solo guarda las primeras y últimas N rutas de cada lista y cuenta el
+/// resto, así la memoria no crece con el tamaño del árbol
+#[derive(Debug)]
+pub struct ItemizeParser {
+    item: String,
+    created: PathSample,
+    updated: PathSample,
+    deleted: PathSample,
+    /// Archivos regulares recibidos (`>f`)
+    pub files_transferred: usize,
+    /// Directorios (relativos al destino) de los que rsync borró algo; no
+    /// se recorta como las muestras, es lo que mira --prune-empty-dirs
+    pub deleted_from: BTreeSet<PathBuf>,
+}
+
+impl ItemizeParser {
+    pub fn new(item: &str, sample: Option<usize>) -> Self {
+        let list = || PathSample { limit: sample, ..Default::default() };
+        Self {
+            item: item.to_string(),
+            created: list(),
+            updated: list(),
+            deleted: list(),
+            files_transferred: 0,
+            deleted_from: BTreeSet::new(),
+        }
+    }
+
+    /// Procesa una línea; `false` si no es de --itemize-changes
+    pub fn push_line(&mut self, line: &str) -> bool {
+        let Some((code, path)) = line.split_once(' ') else { return false };
+        let path = path.trim_start().to_string();
+
+        if code == "*deleting" {
+            if let Some(parent) = Path::new(path.trim_end_matches('/')).parent() {
+                if !parent.as_os_str().is_empty() {
+                    self.deleted_from.insert(parent.to_path_buf());
+                }
+            }
+            self.deleted.push(path);
+            return true;
+        }
+
+        let mut chars = code.chars();
+        let (Some(kind), Some(file_type)) = (chars.next(), chars.next()) else { return false };
+        if code.len() < 3 || !matches!(kind, '<' | '>' | 'c' | 'h') {
+            return false;
+        }
+
+        if kind == '>' && file_type == 'f' {
+            self.files_transferred += 1;
+        }
+        if chars.as_str().chars().all(|c| c == '+') {
+            self.created.push(path);
+        } else {
+            self.updated.push(path);
+        }
+        true
+    }
+
+    pub fn finish(self) -> ItemReport {
+        let (created, omitted_created) = self.created.finish();
+        let (updated, omitted_updated) = self.updated.finish();
+        let (deleted, omitted_deleted) = self.deleted.finish();
+        ItemReport {
+            item: self.item,
+            created,
+            updated,
+            deleted,
+            vanished: Vec::new(),
+            oversized: Vec::new(),
+            omitted: ReportOmitted {
+                created: omitted_created,
+                updated: omitted_updated,
+                deleted: omitted_deleted,
+            },
+        }
+    }
+}
+
+/// Elemento que terminó con error y, si falló rsync, su código de salida
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedItem {
+    pub item: String,
+    pub exit_code: Option<i32>,
+    pub error: String,
+}
+
+/// Estadísticas de un solo elemento: `sync_item` las devuelve y
+/// `SyncStats::merge` las acumula en el total de la ejecución
+#[derive(Debug, Default)]
+pub struct ItemStats {
+    pub files_transferred: u32,
+    pub bytes_transferred: u64,
+    pub files_hardlinked: u32,
+    pub dirs_pruned: u32,
+    pub two_way_conflicts: u32,
+    pub files_skipped_size: u32,
+    /// No transferidos por coincidir con la referencia de --compare-dest
+    pub files_compare_dest: u32,
+    pub verification_failures: u32,
+    /// Transferidos en la pasada con checksum de --force-checksum-on
+    pub files_checksum_forced: u32,
+    /// Archivos regulares revisados que ya estaban al día (--stats de rsync)
+    pub files_unchanged: u32,
+    /// Grupos de nombres que solo difieren en mayúsculas (--check-case)
+    pub case_collisions: u32,
+    pub reports: Vec<ItemReport>,
+}
+
+/// Suma los contadores que `ItemStats` y `SyncStats` tienen en común; un
+/// contador nuevo de `ItemStats` se añade aquí y vale para los dos `merge`
+macro_rules! add_item_counters {
+    ($target:expr, $source:expr) => {
+        $target.files_transferred += $source.files_transferred;
+        $target.bytes_transferred += $source.bytes_transferred;
+        $target.files_hardlinked += $source.files_hardlinked;
+        $target.dirs_pruned += $source.dirs_pruned;
+        $target.two_way_conflicts += $source.two_way_conflicts;
+        $target.files_skipped_size += $source.files_skipped_size;
+        $target.files_compare_dest += $source.files_compare_dest;
+        $target.verification_failures += $source.verification_failures;
+        $target.files_checksum_forced += $source.files_checksum_forced;
+        $target.case_collisions += $source.case_collisions;
+        $target.files_unchanged += $source.files_unchanged;
+    };
+}
+
+impl ItemStats {
+    /// Acumula otra pasada del mismo elemento
+    pub fn merge(&mut self, other: ItemStats) {
+        add_item_counters!(self, other);
+        self.reports.extend(other.reports);
+    }
+
+    pub fn record_files_transferred(&mut self, count: usize) {
+        self.files_transferred += count as u32;
+    }
+
+    pub fn record_bytes_transferred(&mut self, bytes: u64) {
+        self.bytes_transferred += bytes;
+    }
+
+    pub fn record_verification_failures(&mut self, count: usize) {
+        self.verification_failures += count as u32;
+    }
+
+    pub fn record_item_report(&mut self, report: ItemReport) {
+        self.reports.push(report);
+    }
+}
 
 #[derive(Debug, Default)]
 pub struct SyncStats {
     pub start_time: Option<Instant>,
     pub items_processed: u32,
+    pub items_missing: u32,
+    /// Elementos que el usuario decidió omitir con --confirm-each
+    pub items_skipped: u32,
+    /// Elementos ya completados por una ejecución interrumpida (--resume-items)
+    pub items_resumed: u32,
     pub files_transferred: u32,
+    /// Revisados sin cambios; junto a los transferidos, lo que rsync miró
+    pub files_unchanged: u32,
     pub crypto_files_transferred: u32,
+    pub bytes_transferred: u64,
+    /// Bytes a transferir estimados por la pasada previa de --progress-total
+    pub estimated_total_bytes: u64,
+    /// Mayor velocidad de una ventana de muestreo (solo con --progress-total)
+    pub peak_bytes_per_sec: u64,
+    pub files_hardlinked: u32,
+    pub dirs_pruned: u32,
+    pub two_way_conflicts: u32,
+    pub files_skipped_size: u32,
+    pub files_compare_dest: u32,
+    pub files_checksum_forced: u32,
+    pub case_collisions: u32,
     pub files_deleted: u32,
+    /// Desaparecidos del origen mientras rsync trabajaba: aviso, no error
+    pub files_vanished: u32,
     pub symbolic_links_created: u32,
     pub symbolic_links_existing: u32,
     pub symbolic_links_errors: u32,
     pub symbolic_links_detected: u32,
     pub sync_errors: u32,
-    pub total_duration: Duration,
+    pub verification_failures: u32,
+    pub item_reports: Vec<ItemReport>,
+    pub failed_items: Vec<FailedItem>,
+    /// --dry-run: los "transferidos" son los que se transferirían
+    pub dry_run: bool,
+    /// --preview-deletions: lo que --delete borraría, por elemento
+    pub preview_deletions: Option<Vec<(String, Vec<String>)>>,
 }
 
 impl SyncStats {
@@ -28,14 +295,43 @@ impl SyncStats {
         self.items_processed += 1;
     }
     
-    pub fn record_files_transferred(&mut self, count: usize) {
-        self.files_transferred += count as u32;
-    }
-    
     pub fn record_error(&mut self) {
         self.sync_errors += 1;
     }
+
+    /// Cuenta el error y guarda el elemento (con el código de rsync) para el resumen
+    pub fn record_failed_item(&mut self, item: &str, error: &AppError) {
+        self.record_error();
+        self.failed_items.push(FailedItem {
+            item: item.to_string(),
+            exit_code: error.rsync_exit_code(),
+            error: error.to_string(),
+        });
+    }
     
+    /// Acumula las estadísticas de un elemento (los borrados salen de sus informes)
+    pub fn merge(&mut self, other: ItemStats) {
+        add_item_counters!(self, other);
+        for report in other.reports {
+            self.files_deleted += report.deleted_count() as u32;
+            self.files_vanished += report.vanished.len() as u32;
+            self.item_reports.push(report);
+        }
+    }
+    
+    /// Velocidad media en MiB/s; `None` sin datos o sin duración
+    pub fn average_mib_per_sec(&self) -> Option<f64> {
+        let secs = self.start_time.map(|t| t.elapsed()).unwrap_or_default().as_secs_f64();
+        if self.bytes_transferred == 0 || secs <= 0.0 {
+            return None;
+        }
+        Some(self.bytes_transferred as f64 / MIB / secs)
+    }
+
+    pub fn peak_mib_per_sec(&self) -> Option<f64> {
+        (self.peak_bytes_per_sec > 0).then(|| self.peak_bytes_per_sec as f64 / MIB)
+    }
+
     pub fn display_summary(&self) {
         let duration = self.start_time.map(|t| t.elapsed()).unwrap_or_default();
         
@@ -44,38 +340,398 @@ impl SyncStats {
         println!("RESUMEN DE SINCRONIZACIÓN");
         println!("==========================================");
         println!("Elementos procesados: {}", self.items_processed);
-        println!("Archivos transferidos: {}", self.files_transferred);
+        if self.items_missing > 0 {
+            println!("Elementos omitidos (no existen): {}", self.items_missing);
+        }
+        if self.items_skipped > 0 {
+            println!("Elementos omitidos por el usuario: {}", self.items_skipped);
+        }
+        if self.items_resumed > 0 {
+            println!("Elementos ya completados antes de la interrupción: {}", self.items_resumed);
+        }
+        if self.dry_run {
+            println!("Archivos que se transferirían (simulación): {}", self.files_transferred);
+        } else {
+            println!("Archivos transferidos: {}", self.files_transferred);
+        }
+        println!("Archivos sin cambios: {}", self.files_unchanged);
+        if self.files_checksum_forced > 0 {
+            println!(
+                "  - De ellos, por la pasada con checksum (--force-checksum-on): {}",
+                self.files_checksum_forced
+            );
+        }
         println!("Archivos Crypto transferidos: {}", self.crypto_files_transferred);
+        println!("Datos transferidos: {}", format_bytes(self.bytes_transferred));
+        if let Some(average) = self.average_mib_per_sec() {
+            println!("Velocidad media: {:.2} MiB/s", average);
+        }
+        if let Some(peak) = self.peak_mib_per_sec() {
+            println!("Velocidad máxima: {:.2} MiB/s", peak);
+        }
+        if self.estimated_total_bytes > 0 {
+            println!("Total estimado (pasada previa): {}", format_bytes(self.estimated_total_bytes));
+        }
         println!("Archivos borrados: {}", self.files_deleted);
+        if self.files_hardlinked > 0 {
+            println!("Archivos enlazados de la instantánea anterior: {}", self.files_hardlinked);
+        }
+        if self.dirs_pruned > 0 {
+            println!("Directorios vacíos eliminados: {}", self.dirs_pruned);
+        }
+        if self.two_way_conflicts > 0 {
+            println!("Conflictos sin resolver (--two-way): {}", self.two_way_conflicts);
+        }
+        if self.files_skipped_size > 0 {
+            println!("Archivos omitidos por tamaño: {}", self.files_skipped_size);
+            let oversized: Vec<&String> = self.item_reports.iter().flat_map(|report| &report.oversized).collect();
+            for path in oversized.iter().take(OVERSIZED_LISTED) {
+                println!("  - {}", path);
+            }
+            if oversized.len() > OVERSIZED_LISTED {
+                println!("  ... y {} más", oversized.len() - OVERSIZED_LISTED);
+            }
+        }
+        if self.files_compare_dest > 0 {
+            println!("Archivos omitidos por coincidir con --compare-dest: {}", self.files_compare_dest);
+        }
+        if self.files_vanished > 0 {
+            println!("Archivos desaparecidos del origen durante la sincronización: {}", self.files_vanished);
+        }
+        if self.case_collisions > 0 {
+            println!("Colisiones de mayúsculas/minúsculas: {}", self.case_collisions);
+        }
         println!("Enlaces simbólicos:");
         println!("  - Detectados/guardados: {}", self.symbolic_links_detected);
         println!("  - Creados: {}", self.symbolic_links_created);
         println!("  - Existentes: {}", self.symbolic_links_existing);
         println!("  - Errores: {}", self.symbolic_links_errors);
         println!("Errores de sincronización: {}", self.sync_errors);
+        for failed in &self.failed_items {
+            match failed.exit_code {
+                Some(_) => println!("  - {}: rsync {}", failed.item, describe_rsync_exit(failed.exit_code)),
+                None => println!("  - {}: {}", failed.item, failed.error),
+            }
+        }
+        if self.verification_failures > 0 {
+            println!("Fallos de verificación: {}", self.verification_failures);
+        }
         println!("Tiempo total: {:.2?}", duration);
         println!("==========================================");
     }
     
-    pub fn send_notification(&self) {
+    /// Informe agrupado por elemento; con `verbose` lista cada ruta
+    pub fn display_item_report(&self, verbose: bool) {
+        println!();
+        println!("Cambios por elemento:");
+        for report in &self.item_reports {
+            println!(
+                "  {}: {} nuevos, {} actualizados, {} borrados",
+                report.item,
+                report.created_count(),
+                report.updated_count(),
+                report.deleted_count()
+            );
+            if !report.vanished.is_empty() {
+                println!("      ({} desaparecidos del origen durante la sincronización)", report.vanished.len());
+            }
+
+            if verbose {
+                print_sample("+", &report.created, report.omitted.created);
+                print_sample("~", &report.updated, report.omitted.updated);
+                print_sample("-", &report.deleted, report.omitted.deleted);
+                for path in &report.vanished {
+                    println!("      ? {}", path);
+                }
+            }
+        }
+    }
+    
+    /// Resumen en JSON (para --json)
+    pub fn display_json(&self) {
+        match serde_json::to_string_pretty(&self.to_json()) {
+            Ok(json) => println!("{}", json),
+            Err(e) => log::error!("Error generando el resumen JSON: {}", e),
+        }
+    }
+
+    /// Resumen completo en JSON (--json y notificaciones por webhook)
+    pub fn to_json(&self) -> serde_json::Value {
         let duration = self.start_time.map(|t| t.elapsed()).unwrap_or_default();
-        
+        let mut json = serde_json::json!({
+            "items_processed": self.items_processed,
+            "items_missing": self.items_missing,
+            "items_skipped": self.items_skipped,
+            "items_resumed": self.items_resumed,
+            "dry_run": self.dry_run,
+            "files_transferred": self.files_transferred,
+            "files_unchanged": self.files_unchanged,
+            "crypto_files_transferred": self.crypto_files_transferred,
+            "bytes_transferred": self.bytes_transferred,
+            "estimated_total_bytes": self.estimated_total_bytes,
+            "average_mib_per_sec": self.average_mib_per_sec(),
+            "peak_mib_per_sec": self.peak_mib_per_sec(),
+            "files_deleted": self.files_deleted,
+            "files_vanished": self.files_vanished,
+            "files_hardlinked": self.files_hardlinked,
+            "dirs_pruned": self.dirs_pruned,
+            "two_way_conflicts": self.two_way_conflicts,
+            "files_skipped_size": self.files_skipped_size,
+            "files_compare_dest": self.files_compare_dest,
+            "files_checksum_forced": self.files_checksum_forced,
+            "case_collisions": self.case_collisions,
+            "symbolic_links": {
+                "detected": self.symbolic_links_detected,
+                "created": self.symbolic_links_created,
+                "existing": self.symbolic_links_existing,
+                "errors": self.symbolic_links_errors,
+            },
+            "sync_errors": self.sync_errors,
+            "verification_failures": self.verification_failures,
+            "duration_secs": duration.as_secs_f64(),
+            "items": self.item_reports,
+            "failed_items": self.failed_items,
+        });
+
+        if let Some(deletions) = &self.preview_deletions {
+            json["preview_deletions"] = deletions
+                .iter()
+                .map(|(item, paths)| serde_json::json!({ "item": item, "paths": paths }))
+                .collect();
+        }
+        json
+    }
+    
+    /// Métricas en formato textfile de Prometheus (--stats-file). Si la
+    /// ejecución falló se conserva el `last_success_timestamp` anterior.
+    pub fn to_prometheus(&self, now: i64, success: bool, last_success: Option<i64>) -> String {
+        let duration = self.start_time.map(|t| t.elapsed()).unwrap_or_default();
+        let last_success = if success { Some(now) } else { last_success };
+
+        let mut gauges: Vec<(&str, &str, String)> = vec![
+            ("last_run_timestamp", "Fin de la última ejecución (epoch)", now.to_string()),
+            ("success", "1 si la última ejecución terminó sin errores", u8::from(success).to_string()),
+            ("errors", "Elementos con errores en la última ejecución", self.sync_errors.to_string()),
+            ("verification_failures", "Diferencias tras --verify o --manifest", self.verification_failures.to_string()),
+            ("items_processed", "Elementos sincronizados", self.items_processed.to_string()),
+            ("items_missing", "Elementos que no existen en origen", self.items_missing.to_string()),
+            ("files_transferred", "Archivos transferidos", self.files_transferred.to_string()),
+            ("files_unchanged", "Archivos revisados sin cambios", self.files_unchanged.to_string()),
+            ("files_deleted", "Archivos borrados en destino", self.files_deleted.to_string()),
+            ("bytes_transferred", "Bytes transferidos", self.bytes_transferred.to_string()),
+            ("duration_seconds", "Duración de la última ejecución", format!("{:.3}", duration.as_secs_f64())),
+        ];
+        if let Some(last_success) = last_success {
+            gauges.insert(1, ("last_success_timestamp", "Fin de la última ejecución correcta (epoch)", last_success.to_string()));
+        }
+
+        gauges
+            .iter()
+            .map(|(name, help, value)| {
+                format!("# HELP syncb_{name} {help}\n# TYPE syncb_{name} gauge\nsyncb_{name} {value}\n")
+            })
+            .collect()
+    }
+
+    /// Escribe `to_prometheus` en `path` de forma atómica (temporal + rename)
+    /// para que el recolector nunca lea un archivo a medias
+    pub fn write_stats_file(&self, path: &Path, success: bool) -> std::io::Result<()> {
+        let last_success = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| parse_last_success(&contents));
+        let contents = self.to_prometheus(chrono::Utc::now().timestamp(), success, last_success);
+
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        // En el mismo directorio para que rename no cruce sistemas de archivos
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(format!(".{}.tmp", std::process::id()));
+        std::fs::write(&temp, contents)?;
+        std::fs::rename(&temp, path)
+    }
+
+    pub fn send_notification(&self, config: &NotificationsConfig) {
+        let duration = self.start_time.map(|t| t.elapsed()).unwrap_or_default();
+        if !config.should_notify(self.sync_errors > 0, duration) {
+            log::debug!("Notificación omitida (notify_on = {:?}, {:.2?})", config.notify_on, duration);
+            return;
+        }
+
         let summary = if self.sync_errors == 0 {
             format!(
-                "Sincronización completada con éxito\n• Elementos: {}\n• Transferidos: {}\n• Tiempo: {:.2?}",
-                self.items_processed, self.files_transferred, duration
+                "Sincronización completada con éxito\n• Elementos: {}\n• Transferidos: {} ({})\n• Tiempo: {:.2?}",
+                self.items_processed, self.files_transferred, format_bytes(self.bytes_transferred), duration
             )
         } else {
+            let first_failed = match self.failed_items.first() {
+                Some(failed) => format!("\n• Primer fallo: {} ({})", failed.item, failed.error),
+                None => String::new(),
+            };
             format!(
-                "Sincronización completada con errores\n• Errores: {}\n• Elementos: {}\n• Tiempo: {:.2?}",
-                self.sync_errors, self.items_processed, duration
+                "Sincronización completada con errores\n• Errores: {}{}\n• Elementos: {}\n• Tiempo: {:.2?}",
+                self.sync_errors, first_failed, self.items_processed, duration
             )
         };
         
-        let _ = Notification::new()
-            .summary("Sincronización syncb")
-            .body(&summary)
-            .icon("dialog-information")
-            .show();
+        notifications::send(config, "Sincronización syncb", &summary, &self.to_json());
+    }
+}
+
+/// Lista de rutas del informe; si se recortó, `omitted` va entre las
+/// primeras y las últimas (las dos mitades de la muestra)
+fn print_sample(mark: &str, paths: &[String], omitted: usize) {
+    let split = if omitted > 0 { paths.len() / 2 } else { paths.len() };
+    for path in &paths[..split] {
+        println!("      {} {}", mark, path);
+    }
+    if omitted > 0 {
+        println!("      {} ... ({} rutas más omitidas del informe; véase report_sample_size)", mark, omitted);
+    }
+    for path in &paths[split..] {
+        println!("      {} {}", mark, path);
     }
-}
\ No newline at end of file
+}
+
+fn parse_last_success(contents: &str) -> Option<i64> {
+    contents
+        .lines()
+        .find_map(|line| line.strip_prefix("syncb_last_success_timestamp "))
+        .and_then(|value| value.trim().parse().ok())
+}
+
+const MIB: f64 = 1024.0 * 1024.0;
+
+/// Archivos omitidos por tamaño que se listan en el resumen
+const OVERSIZED_LISTED: usize = 20;
+
+/// Formatea un número de bytes con unidades binarias (KiB/MiB/GiB)
+pub fn format_bytes(bytes: u64) -> String {
+    ByteSize(bytes).to_string_as(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_item_report_from_itemize_output() {
+        let output = "\
+sending incremental file list
+cd+++++++++ docs/nuevo/
+>f+++++++++ docs/nuevo/a b.txt
+>f.st...... docs/cambiado.txt
+.d..t...... docs/
+*deleting   docs/viejo.txt
+
+sent 1,234 bytes  received 56 bytes  2,580.00 bytes/sec
+";
+        let report = ItemReport::from_itemize_output("docs", output);
+
+        assert_eq!(report.created, vec!["docs/nuevo/", "docs/nuevo/a b.txt"]);
+        assert_eq!(report.updated, vec!["docs/cambiado.txt"]);
+        assert_eq!(report.deleted, vec!["docs/viejo.txt"]);
+    }
+
+    #[test]
+    fn test_itemize_parser_keeps_bounded_sample() {
+        let mut parser = ItemizeParser::new("docs", Some(2));
+        for n in 0..10 {
+            assert!(parser.push_line(&format!(">f+++++++++ docs/{}.txt", n)));
+        }
+        assert!(parser.push_line("*deleting   docs/viejo.txt"));
+        assert!(parser.push_line("*deleting   docs/antiguo/sub/"));
+        assert!(!parser.push_line("sent 1,234 bytes  received 56 bytes  2,580.00 bytes/sec"));
+        assert_eq!(parser.files_transferred, 10);
+        assert_eq!(parser.deleted_from, BTreeSet::from(["docs".into(), "docs/antiguo".into()]));
+
+        let report = parser.finish();
+        assert_eq!(report.created, ["docs/0.txt", "docs/1.txt", "docs/8.txt", "docs/9.txt"]);
+        assert_eq!(report.omitted, ReportOmitted { created: 6, updated: 0, deleted: 0 });
+        assert_eq!((report.created_count(), report.deleted_count()), (10, 2));
+        assert_eq!(serde_json::to_value(&report).unwrap()["omitted"]["created"], 6);
+
+        let mut stats = SyncStats::new();
+        let mut item = ItemStats::default();
+        item.record_item_report(ItemReport { omitted: ReportOmitted { deleted: 5, ..Default::default() }, ..report });
+        stats.merge(item);
+        assert_eq!(stats.files_deleted, 7);
+    }
+
+    #[test]
+    fn test_merge_item_stats() {
+        let mut stats = SyncStats::new();
+        for deleted in [1, 2] {
+            let mut item = ItemStats::default();
+            item.record_files_transferred(3);
+            item.record_bytes_transferred(100);
+            item.record_item_report(ItemReport {
+                item: "docs".to_string(),
+                deleted: vec!["x".to_string(); deleted],
+                ..Default::default()
+            });
+            stats.merge(item);
+        }
+
+        assert_eq!(stats.files_transferred, 6);
+        assert_eq!(stats.bytes_transferred, 200);
+        assert_eq!(stats.files_deleted, 3);
+        assert_eq!(stats.item_reports.len(), 2);
+    }
+
+    #[test]
+    fn test_stats_file_keeps_last_success_on_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metricas/syncb.prom");
+        let mut stats = SyncStats::new();
+        stats.files_transferred = 3;
+
+        stats.write_stats_file(&path, true).unwrap();
+        let first = std::fs::read_to_string(&path).unwrap();
+        assert!(first.contains("# TYPE syncb_errors gauge\nsyncb_errors 0\n"));
+        assert!(first.contains("syncb_files_transferred 3\n"));
+        assert!(first.contains("syncb_success 1\n"));
+        let last_success = parse_last_success(&first).unwrap();
+
+        stats.record_error();
+        stats.write_stats_file(&path, false).unwrap();
+        let second = std::fs::read_to_string(&path).unwrap();
+        assert!(second.contains("syncb_success 0\n"));
+        assert!(second.contains("syncb_errors 1\n"));
+        assert_eq!(parse_last_success(&second), Some(last_success));
+        assert_eq!(std::fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+
+        let never = stats.to_prometheus(10, false, None);
+        assert!(!never.contains("last_success_timestamp"));
+    }
+
+    #[test]
+    fn test_transfer_rates_avoid_division_by_zero() {
+        let mut stats = SyncStats::default();
+        assert_eq!(stats.average_mib_per_sec(), None);
+        assert_eq!(stats.peak_mib_per_sec(), None);
+
+        stats.bytes_transferred = 10 * 1024 * 1024;
+        assert_eq!(stats.average_mib_per_sec(), None);
+
+        stats.start_time = Some(Instant::now() - Duration::from_secs(5));
+        let average = stats.average_mib_per_sec().unwrap();
+        assert!(average > 1.9 && average <= 2.0, "{}", average);
+
+        stats.peak_bytes_per_sec = 3 * 1024 * 1024;
+        assert_eq!(stats.peak_mib_per_sec(), Some(3.0));
+        assert_eq!(stats.to_json()["peak_mib_per_sec"], 3.0);
+    }
+
+    #[test]
+    fn test_json_includes_preview_deletions() {
+        let mut stats = SyncStats::default();
+        assert!(stats.to_json().get("preview_deletions").is_none());
+
+        stats.preview_deletions = Some(vec![("Documentos".to_string(), vec!["viejo.txt".to_string()])]);
+        let json = stats.to_json();
+        assert_eq!(json["preview_deletions"][0]["item"], "Documentos");
+        assert_eq!(json["preview_deletions"][0]["paths"][0], "viejo.txt");
+    }
+}
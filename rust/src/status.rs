@@ -0,0 +1,79 @@
+use crate::config::AppConfig;
+use crate::error::Result;
+use crate::history;
+use crate::lock::{self, LockGuard};
+use crate::sync::{get_available_space_mb, is_directory_mounted};
+use chrono::{Local, TimeZone};
+use std::path::Path;
+
+/// Muestra el estado del sistema (montaje, lock, espacio y última ejecución).
+/// Solo lectura: nunca adquiere el lock ni modifica archivos.
+pub fn show_status(config: &AppConfig) -> Result<()> {
+    let mount_point = &config.general.pcloud_mount_point;
+    let local_dir = config.local_dir();
+
+    println!("==========================================");
+    println!("ESTADO DE syncb");
+    println!("==========================================");
+
+    match &config.ssh {
+        Some(ssh) => println!("Remoto SSH: {}", ssh.remote_path(mount_point).to_string_lossy()),
+        None => {
+            let mounted = mount_point.exists() && is_directory_mounted(mount_point);
+            println!(
+                "pCloud montado: {} ({})",
+                if mounted { "SÍ" } else { "NO" },
+                mount_point.display()
+            );
+        }
+    }
+
+    let lock_file = lock::lock_path(config);
+    match LockGuard::read_owner(lock_file) {
+        Some(owner) => {
+            let state = if LockGuard::is_process_running(owner.pid) {
+                "activo"
+            } else {
+                "obsoleto"
+            };
+            let since = owner
+                .timestamp
+                .and_then(|ts| Local.timestamp_opt(ts as i64, 0).single())
+                .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_else(|| "desconocido".to_string());
+            println!(
+                "Lock: {} (PID {}, host {}, último latido {})",
+                state, owner.pid, owner.hostname, since
+            );
+        }
+        None if lock_file.exists() => println!("Lock: ilegible ({})", lock_file.display()),
+        None => println!("Lock: libre"),
+    }
+
+    println!("Espacio libre:");
+    print_free_space("Local", &local_dir);
+    if config.ssh.is_none() {
+        print_free_space("pCloud", mount_point);
+    }
+
+    match history::read_last(&config.general.history_file, 1)?.pop() {
+        Some(last) => println!(
+            "Última ejecución: {} ({} en {}, {} errores)",
+            last.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            last.mode,
+            last.host,
+            last.sync_errors
+        ),
+        None => println!("Última ejecución: sin registros"),
+    }
+    println!("==========================================");
+
+    Ok(())
+}
+
+fn print_free_space(label: &str, path: &Path) {
+    match get_available_space_mb(path) {
+        Ok(available) => println!("  - {}: {}MB ({})", label, available, path.display()),
+        Err(e) => println!("  - {}: no disponible ({}: {})", label, path.display(), e),
+    }
+}
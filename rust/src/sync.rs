@@ -1,27 +1,209 @@
-use crate::cli::{Cli, SyncMode, BackupDirMode};
-use crate::config::AppConfig;
-use crate::error::{AppError, Result};
-use crate::stats::SyncStats;
-use std::path::{Path, PathBuf};
-use std::process::Command;
+use crate::cli::{Cli, SyncMode, BackupDirMode, SymlinkPolicy};
+use crate::case_collisions;
+use crate::checksum_cache::ChecksumCache;
+use crate::config::{AppConfig, NotificationBackend, SshTarget};
+use crate::crypto::CryptoManager;
+use crate::error::{describe_rsync_exit, AppError, Result};
+use crate::ignore_files;
+use crate::links::SymbolicLinks;
+use crate::manifest;
+use crate::plan::{self, ExecutionPlan, PlannedItem};
+use crate::reconcile::ItemDiff;
+use crate::resume::{self, ResumeState};
+use crate::shutdown;
+use crate::stats::{format_bytes, ItemReport, ItemStats, ItemizeParser, SyncStats};
+use crate::two_way;
+use crate::utils::{absolute_path, command_line, normalize_path};
+use crate::watch_fs::{ChangeFilter, FsWatcher};
+use lazy_static::lazy_static;
+use std::collections::BTreeSet;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::task::JoinSet;
 use tokio::time::{timeout, Duration};
-use fs2::available_space;
 
+/// Transferencia decidida con la cache de checksums (--checksum + `checksum_cache_dir`)
+struct ChecksumPlan {
+    cache: ChecksumCache,
+    changed: Vec<(PathBuf, Option<String>)>,
+    files_from: PathBuf,
+}
+
+impl ChecksumPlan {
+    /// Guarda la cache; si se transfirió, el destino pasa a tener el checksum del origen
+    fn finish(mut self, destination: &Path, transferred: bool) -> Result<()> {
+        if transferred {
+            for (relative, checksum) in &self.changed {
+                if let Some(checksum) = checksum {
+                    let key = format!("destino/{}", relative.to_string_lossy());
+                    self.cache.record(&key, &destination.join(relative), checksum.clone())?;
+                }
+            }
+        }
+
+        let _ = std::fs::remove_file(&self.files_from);
+        self.cache.save()
+    }
+}
+
+/// Comprobaciones del montaje de pCloud antes de darlo por no montado
+const MOUNT_CHECK_ATTEMPTS: u32 = 4;
+const MOUNT_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Formato del nombre de cada instantánea dentro de `pcloud_backup_readonly`
+pub const SNAPSHOT_FORMAT: &str = "%Y-%m-%d_%H%M%S";
+
+/// Metadatos de control de versiones que excluye --exclude-vcs: los
+/// directorios de rsync -C (CVS, RCS, SCCS, .svn, .git, .hg, .bzr) más los de
+/// sistemas modernos. Sin barra final para cubrir también el `.git` archivo de
+/// submódulos y worktrees. Los archivos de configuración del proyecto
+/// (`.gitignore`, `.gitattributes`, `.hgignore`...) no se excluyen.
+pub const VCS_EXCLUSIONS: &[&str] = &[
+    "CVS", "RCS", "SCCS", ".svn", ".git", ".hg", ".bzr", "_darcs", ".pijul", ".jj",
+    ".fslckout", "_FOSSIL_",
+];
+
+#[derive(Clone)]
 pub struct SyncManager {
     config: AppConfig,
     args: Cli,
+    snapshot: Option<Snapshot>,
+    progress: Option<Arc<ProgressTotal>>,
+    checksum_pass: Option<ChecksumPass>,
+    /// --exclude-larger-than-free: tamaño máximo por archivo según el espacio libre
+    free_space_cap: Option<u64>,
+}
+
+/// Pasadas de --force-checksum-on: la rápida excluye los patrones y la
+/// segunda, con --checksum, solo incluye esos patrones
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ChecksumPass {
+    Fast,
+    Strong,
+}
+
+/// Progreso global de --progress-total, compartido por todos los rsync
+#[derive(Debug)]
+struct ProgressTotal {
+    total_bytes: u64,
+    done_bytes: AtomicU64,
+    last_report: std::sync::Mutex<std::time::Instant>,
+    /// Inicio de la ventana de muestreo y bytes hechos entonces
+    last_sample: std::sync::Mutex<(std::time::Instant, u64)>,
+    /// Mayor velocidad observada en una ventana, en bytes/s
+    peak_rate: AtomicU64,
+}
+
+impl ProgressTotal {
+    const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+    const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+
+    fn new(total_bytes: u64) -> Self {
+        Self {
+            total_bytes,
+            done_bytes: AtomicU64::new(0),
+            last_report: std::sync::Mutex::new(std::time::Instant::now()),
+            last_sample: std::sync::Mutex::new((std::time::Instant::now(), 0)),
+            peak_rate: AtomicU64::new(0),
+        }
+    }
+
+    /// Cierra la ventana de muestreo si ha pasado `SAMPLE_INTERVAL` y
+    /// actualiza la velocidad máxima
+    fn sample(&self, in_progress: u64) {
+        let Ok(mut last_sample) = self.last_sample.try_lock() else { return };
+        let (started, bytes_then) = *last_sample;
+        let elapsed = started.elapsed();
+        if elapsed < Self::SAMPLE_INTERVAL {
+            return;
+        }
+
+        let bytes_now = self.done_bytes.load(Ordering::Relaxed) + in_progress;
+        let rate = bytes_now.saturating_sub(bytes_then) as f64 / elapsed.as_secs_f64();
+        self.peak_rate.fetch_max(rate as u64, Ordering::Relaxed);
+        *last_sample = (std::time::Instant::now(), bytes_now);
+    }
+
+    fn peak_rate(&self) -> u64 {
+        self.peak_rate.load(Ordering::Relaxed)
+    }
+
+    fn file_done(&self, bytes: u64) {
+        self.done_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Registra el porcentaje como mucho cada `REPORT_INTERVAL`
+    fn maybe_report(&self, in_progress: u64) {
+        let Ok(mut last_report) = self.last_report.try_lock() else { return };
+        if last_report.elapsed() < Self::REPORT_INTERVAL || self.total_bytes == 0 {
+            return;
+        }
+        *last_report = std::time::Instant::now();
+
+        let done = (self.done_bytes.load(Ordering::Relaxed) + in_progress).min(self.total_bytes);
+        log::info!(
+            "Progreso total: {}% ({} de {})",
+            done * 100 / self.total_bytes,
+            format_bytes(done),
+            format_bytes(self.total_bytes)
+        );
+    }
+}
+
+/// Instantánea en curso (--snapshot) y la anterior a la que se enlazan los archivos sin cambios
+#[derive(Debug, Clone)]
+struct Snapshot {
+    current: PathBuf,
+    previous: Option<PathBuf>,
 }
 
 impl SyncManager {
     pub fn new(config: AppConfig, args: Cli) -> Self {
-        Self { config, args }
+        Self { config, args, snapshot: None, progress: None, checksum_pass: None, free_space_cap: None }
+    }
+
+    /// Decide el directorio de la nueva instantánea y localiza la anterior
+    fn plan_snapshot(&self) -> Result<Option<Snapshot>> {
+        if !self.args.snapshot {
+            return Ok(None);
+        }
+
+        if self.args.get_mode() != SyncMode::Upload
+            || self.args.get_backup_dir_mode() != BackupDirMode::ReadOnly
+        {
+            return Err(AppError::Validation(
+                "--snapshot solo se admite con --subir --backup-dir".to_string(),
+            ));
+        }
+
+        let root = self.get_pcloud_dir();
+        let current = root.join(chrono::Local::now().format(SNAPSHOT_FORMAT).to_string());
+        let previous = latest_snapshot(&root);
+
+        log::info!("Instantánea: {}", current.display());
+        match &previous {
+            Some(previous) => log::info!("Enlazando con la instantánea anterior: {}", previous.display()),
+            None => log::info!("No hay instantánea anterior: se copiará todo"),
+        }
+
+        Ok(Some(Snapshot { current, previous }))
     }
 
     pub async fn perform_sync(&self, stats: &mut SyncStats) -> Result<()> {
         log::info!("Iniciando proceso de sincronización en modo: {:?}", self.args.get_mode());
 
-        // Verificar precondiciones
-        self.verify_preconditions().await?;
+        // Las precondiciones ya las verificó el llamador (`verify_preconditions`)
+        // antes de pedir confirmación; repetirlas duplicaría la pasada previa de espacio
+        if self.args.crypto_only {
+            // El montaje de Crypto lo comprueba `sync_crypto` antes de copiar
+            log::info!("--crypto-only: se omiten los elementos y los enlaces simbólicos");
+            self.sync_crypto(stats).await?;
+            log::info!("Sincronización completada");
+            return Ok(());
+        }
 
         // Procesar elementos principales
         self.sync_main_items(stats).await?;
@@ -38,23 +220,69 @@ impl SyncManager {
         Ok(())
     }
 
+    /// En --dry-run, que pCloud no esté montado o falte espacio solo se
+    /// advierte: se puede previsualizar desde un equipo sin montaje, pero que la
+    /// simulación termine bien no garantiza que la ejecución real lo haga.
     async fn verify_preconditions(&self) -> Result<()> {
-        // Verificar pCloud montado
-        self.verify_pcloud_mounted().await?;
+        let mut downgraded = false;
+
+        if let Some(ssh) = &self.config.ssh {
+            // Sin montaje: basta con que ssh conecte; el espacio del otro equipo no se ve
+            let reachable = verify_ssh_reachable(ssh).await;
+            downgraded |= self.tolerate_in_dry_run(reachable)?;
+            log::info!("Remoto SSH: no se comprueba el espacio libre");
+        } else {
+            // Verificar pCloud montado
+            let mounted = self.verify_pcloud_mounted().await;
+            downgraded |= self.tolerate_in_dry_run(mounted)?;
+
+            // Verificar que se puede escribir en pCloud antes de recorrer nada
+            if self.args.verify_mount_writable {
+                self.verify_pcloud_writable()?;
+            }
+
+            // Verificar conectividad
+            self.verify_connectivity().await?;
 
-        // Verificar conectividad
-        self.verify_connectivity().await?;
+            // Verificar espacio en disco (con `estimate_transfer_size`, la
+            // estimación recorre los elementos)
+            if !self.args.crypto_only {
+                let disk_space = self.verify_disk_space().await;
+                downgraded |= self.tolerate_in_dry_run(disk_space)?;
+            }
+        }
 
-        // Verificar espacio en disco
-        self.verify_disk_space().await?;
+        if downgraded {
+            log::warn!(
+                "SIMULACIÓN: hay comprobaciones previas que fallarían; la ejecución real no se completará tal cual"
+            );
+        }
 
         // Verificar elementos de configuración
-        self.verify_config_items().await?;
+        if !self.args.crypto_only {
+            self.verify_config_items().await?;
+        }
 
         Ok(())
     }
 
+    /// Convierte el error de una comprobación en advertencia si es --dry-run.
+    /// Devuelve si se ha rebajado un error.
+    fn tolerate_in_dry_run(&self, check: Result<()>) -> Result<bool> {
+        match check {
+            Err(e) if self.args.dry_run => {
+                log::warn!("SIMULACIÓN: se continúa pese a que falla una comprobación previa: {}", e);
+                Ok(true)
+            }
+            other => other.map(|_| false),
+        }
+    }
+
     async fn verify_pcloud_mounted(&self) -> Result<()> {
+        if self.config.ssh.is_some() {
+            return Ok(());
+        }
+
         let mount_point = Path::new(&self.config.general.pcloud_mount_point);
 
         if !mount_point.exists() {
@@ -63,285 +291,3611 @@ impl SyncManager {
             ));
         }
 
-        // Verificar si está realmente montado
-        if !self.is_directory_mounted(mount_point).await? {
-            return Err(AppError::PCloudNotMounted(
-                format!("pCloud no está montado en: {:?}", mount_point)
-            ));
+        // Justo tras montar (arranque, reanudación) el FUSE de pCloud puede
+        // no estar aún o verse vacío un momento: se reintenta antes de concluir
+        let mut os_state = None;
+        for attempt in 1..=MOUNT_CHECK_ATTEMPTS {
+            os_state = os_mount_state(mount_point);
+            if os_state != Some(false) && directory_has_entries(mount_point) {
+                log::info!("Verificación de pCloud montado: OK");
+                return Ok(());
+            }
+            if attempt < MOUNT_CHECK_ATTEMPTS {
+                log::debug!("pCloud no parece listo en {:?}; reintentando", mount_point);
+                tokio::time::sleep(MOUNT_CHECK_INTERVAL).await;
+            }
         }
 
-        log::info!("Verificación de pCloud montado: OK");
-        Ok(())
+        // El sistema manda sobre la heurística del directorio vacío
+        if os_state == Some(true) {
+            log::warn!("pCloud está montado en {:?} pero se ve vacío", mount_point);
+            return Ok(());
+        }
+        Err(AppError::PCloudNotMounted(format!("pCloud no está montado en: {:?}", mount_point)))
     }
 
-    async fn is_directory_mounted(&self, path: &Path) -> Result<bool> {
-        // Implementación simplificada - en producción usarías métodos específicos del SO
-        match std::fs::read_dir(path) {
-            Ok(mut entries) => Ok(entries.next().is_some()),
-            Err(_) => Ok(false),
+    /// --verify-mount-writable: crea y borra un archivo en el directorio de
+    /// backup (o su ancestro existente más cercano). Detecta montajes de solo
+    /// lectura o con la sesión caducada. Solo si se va a escribir en pCloud.
+    fn verify_pcloud_writable(&self) -> Result<()> {
+        if self.args.dry_run || self.args.get_mode() == SyncMode::Download {
+            return Ok(());
         }
+
+        // Se prueba en el directorio de backup o en su ancestro existente más
+        // cercano, pero nunca por encima del punto de montaje: fuera de él se
+        // escribiría en el disco local
+        let pcloud_dir = self.get_pcloud_dir();
+        let mount_point = &self.config.general.pcloud_mount_point;
+        let Some(dir) = pcloud_dir
+            .ancestors()
+            .take_while(|dir| dir.starts_with(mount_point))
+            .find(|dir| dir.is_dir())
+        else {
+            return Err(AppError::PCloudNotMounted(format!(
+                "No existe {:?} ni ningún directorio suyo dentro de {:?}",
+                pcloud_dir, mount_point
+            )));
+        };
+
+        let probe = dir.join(format!(".syncb_write_test.{}", std::process::id()));
+        std::fs::write(&probe, b"syncb")
+            .and_then(|_| std::fs::remove_file(&probe))
+            .map_err(|e| {
+                log::error!("No se puede escribir en {:?} (¿solo lectura o sesión caducada?): {}", dir, e);
+                AppError::NotWritable(dir.to_path_buf())
+            })?;
+
+        log::info!("Verificación de escritura en pCloud: OK ({:?})", dir);
+        Ok(())
     }
 
     async fn verify_connectivity(&self) -> Result<()> {
-        // Verificar conectividad a pCloud
-        let status = Command::new("curl")
-            .args(["-s", "--max-time", "5", "https://www.pcloud.com/"])
-            .status();
+        if self.args.skip_connectivity_check {
+            log::info!("Verificación de conectividad omitida");
+            return Ok(());
+        }
+
+        let url = &self.config.general.connectivity_check_url;
+        let Some((host, port)) = connectivity_target(url) else {
+            log::warn!("URL de comprobación de conectividad no válida: {}", url);
+            return Ok(());
+        };
 
-        match status {
-            Ok(exit_status) if exit_status.success() => {
+        // Basta con abrir una conexión TCP, no hace falta cliente HTTP
+        let probe = timeout(
+            Duration::from_secs(5),
+            tokio::net::TcpStream::connect((host.as_str(), port)),
+        )
+        .await;
+
+        match probe {
+            Ok(Ok(_)) => {
                 log::info!("Verificación de conectividad pCloud: OK");
                 Ok(())
             }
             _ => {
-                log::warn!("No se pudo verificar la conectividad con pCloud");
+                log::warn!("No se pudo verificar la conectividad con {}:{}", host, port);
                 Ok(()) // No fatal, solo advertencia
             }
         }
     }
 
+    /// Comprueba cada lado que recibirá datos (ambos con --two-way) contra lo
+    /// que estima una pasada --dry-run --stats, más `min_free_space_mb` de reserva
     async fn verify_disk_space(&self) -> Result<()> {
-        let required_space_mb = 500; // 500 MB mínimo
-
-        let path = match self.args.get_mode() {
-            SyncMode::Upload => Path::new(&self.config.general.pcloud_mount_point),
-            SyncMode::Download => Path::new(&self.config.general.local_dir),
-        };
-
-        let available_space = self.get_available_space(path).await?;
+        let reserve_mb = self.config.general.min_free_space_mb;
+
+        for (path, estimated_bytes) in self.estimate_incoming_bytes().await? {
+            let available_mb = get_available_space_mb(&path)?;
+            let needed_mb = estimated_bytes.div_ceil(1024 * 1024) + reserve_mb;
+
+            if available_mb < needed_mb && self.args.exclude_larger_than_free && available_mb > reserve_mb {
+                log::warn!(
+                    "Espacio insuficiente en {:?} para {} ({}MB disponibles): se omitirán los archivos que no quepan",
+                    path,
+                    format_bytes(estimated_bytes),
+                    available_mb
+                );
+                continue;
+            }
+            if available_mb < needed_mb {
+                return Err(AppError::InsufficientSpace(format!(
+                    "Espacio insuficiente en {:?}: se estiman {} a transferir (+{}MB de reserva) y hay {}MB disponibles",
+                    path,
+                    format_bytes(estimated_bytes),
+                    reserve_mb,
+                    available_mb
+                )));
+            }
 
-        if available_space < required_space_mb {
-            return Err(AppError::InsufficientSpace(
-                format!("Espacio insuficiente en {:?}. Disponible: {}MB, Necesario: {}MB",
-                       path, available_space, required_space_mb)
-            ));
+            if self.config.general.estimate_transfer_size {
+                log::info!(
+                    "Verificación de espacio en {:?}: OK ({}MB disponibles, {} estimados)",
+                    path,
+                    available_mb,
+                    format_bytes(estimated_bytes)
+                );
+            } else {
+                log::info!("Verificación de espacio en {:?}: OK ({}MB disponibles)", path, available_mb);
+            }
         }
 
-        log::info!("Verificación de espacio en disco: OK ({}MB disponibles)", available_space);
         Ok(())
     }
 
-    async fn get_available_space(&self, path: &Path) -> Result<u64> {
-        let available = available_space(path)? / (1024 * 1024); // Convertir a MB
-        Ok(available)
+    /// Lados que recibirán datos según el modo, con los bytes estimados para
+    /// cada uno; sin `estimate_transfer_size` no se estima (0) y solo cuenta la reserva
+    async fn estimate_incoming_bytes(&self) -> Result<Vec<(PathBuf, u64)>> {
+        let items = self.resolve_items()?;
+        let mut sides = Vec::new();
+        for (path, reverse) in self.receiving_sides() {
+            let estimated = if self.config.general.estimate_transfer_size {
+                self.estimate_total_bytes(&items, reverse).await?
+            } else {
+                0
+            };
+            sides.push((path, estimated));
+        }
+        Ok(sides)
     }
 
-    async fn verify_config_items(&self) -> Result<()> {
-        let host_config = self.config.get_current_host_config()?;
+    /// Lados que reciben datos según el modo; `true` si es el sentido inverso
+    /// de --two-way (de pCloud al directorio local)
+    fn receiving_sides(&self) -> Vec<(PathBuf, bool)> {
+        let local_side = self.config.local_dir();
+        let remote_side = self.config.general.pcloud_mount_point.clone();
 
-        for item in &host_config.sync_items {
-            let full_path = Path::new(&self.config.general.local_dir).join(item);
-            if !full_path.exists() {
-                log::warn!("El elemento de configuración no existe: {:?}", full_path);
-            }
+        match self.args.get_mode() {
+            SyncMode::Upload => vec![(remote_side, false)],
+            SyncMode::Download => vec![(local_side, false)],
+            SyncMode::TwoWay => vec![(remote_side, false), (local_side, true)],
+        }
+    }
+
+    /// --exclude-larger-than-free: el mayor archivo que cabe en el lado
+    /// receptor con menos espacio, dejando la reserva de `min_free_space_mb`.
+    /// Es un tope por archivo: si hay muchos, rsync puede quedarse igualmente sin sitio.
+    fn free_space_cap(&self) -> Result<Option<u64>> {
+        if self.config.ssh.is_some() {
+            log::warn!("Remoto SSH: --exclude-larger-than-free no puede ver su espacio libre");
+            return Ok(None);
         }
 
-        Ok(())
+        let reserve_mb = self.config.general.min_free_space_mb;
+        let mut cap_mb = u64::MAX;
+        for (path, _) in self.receiving_sides() {
+            cap_mb = cap_mb.min(get_available_space_mb(&path)?.saturating_sub(reserve_mb));
+        }
+        if cap_mb == 0 {
+            return Err(AppError::InsufficientSpace(format!(
+                "No queda espacio por encima de la reserva ({}MB): no cabe ningún archivo",
+                reserve_mb
+            )));
+        }
+
+        let cap = cap_mb * 1024 * 1024;
+        log::info!("Se omitirán los archivos mayores de {} (espacio libre)", format_bytes(cap));
+        Ok(Some(cap))
     }
 
-    async fn sync_main_items(&self, stats: &mut SyncStats) -> Result<()> {
-        let host_config = self.config.get_current_host_config()?;
-        let items_to_sync = if let Some(cli_items) = &self.args.items {
-            cli_items
-        } else {
-            &host_config.sync_items
-        };
+    /// Los elementos deben existir en el origen: en local al subir y en
+    /// pCloud al bajar (por SSH no se puede comprobar desde aquí)
+    async fn verify_config_items(&self) -> Result<()> {
+        if self.remote_source() {
+            return Ok(());
+        }
+        let mut missing = Vec::new();
 
-        for item in items_to_sync {
-            if let Err(e) = self.sync_item(item, stats).await {
-                log::error!("Error sincronizando {}: {}", item, e);
-                stats.record_error();
-            } else {
-                stats.record_successful_item();
+        for item in self.resolve_items()? {
+            let (source, _) = self.get_sync_paths(&item)?;
+            if !source.exists() {
+                log::warn!("El elemento de configuración no existe: {:?}", source);
+                missing.push(item);
             }
         }
 
+        if self.config.general.strict_items && !missing.is_empty() {
+            return Err(AppError::Validation(format!(
+                "Elementos configurados que no existen (strict_items): {}",
+                missing.join(", ")
+            )));
+        }
+
         Ok(())
     }
 
-    async fn sync_item(&self, item: &str, stats: &mut SyncStats) -> Result<()> {
-        log::info!("Sincronizando: {}", item);
-
-        let (source, destination) = self.get_sync_paths(item)?;
+    /// Al bajar por SSH el origen está en el remoto: no se puede mirar desde
+    /// aquí y es el remoto quien envía (rsync cuenta los datos como
+    /// recibidos; en local el proceso lanzado siempre es el emisor)
+    fn remote_source(&self) -> bool {
+        self.config.ssh.is_some() && self.args.get_mode() == SyncMode::Download
+    }
 
-        // Construir comando rsync
-        let mut command = self.build_rsync_command(&source, &destination)?;
+    /// Al subir, un origen inexistente casi siempre es un error de configuración
+    fn is_missing_source(&self, item: &str) -> bool {
+        self.args.get_mode() == SyncMode::Upload && !self.config.local_dir().join(item).exists()
+    }
 
-        // Ejecutar con timeout
-        let timeout_duration = Duration::from_secs(
-            self.args.timeout.unwrap_or(self.config.general.default_timeout_minutes) as u64 * 60
-        );
+    fn resolve_items(&self) -> Result<Vec<String>> {
+        let items = self.resolve_selected_items()?;
+        for item in &items {
+            validate_item(item)?;
+        }
+        Ok(items
+            .into_iter()
+            .filter(|item| !is_skipped(item, &self.args.skip_item))
+            .collect())
+    }
 
-        match timeout(timeout_duration, self.execute_rsync(&mut command)).await {
-            Ok(Ok(output)) => {
-                self.process_rsync_output(&output, stats);
-                Ok(())
-            }
-            Ok(Err(e)) => Err(e),
-            Err(_) => Err(AppError::Timeout(format!("Operación excedió el tiempo límite para: {}", item))),
+    /// Elementos de --item o del host, antes de aplicar --skip-item
+    fn resolve_selected_items(&self) -> Result<Vec<String>> {
+        if let Some(cli_items) = self.args.selected_items()? {
+            return Ok(cli_items);
         }
+
+        let host_config = self.config.get_current_host_config()?;
+        Ok(host_config.sync_items.clone())
     }
 
-    fn get_sync_paths(&self, item: &str) -> Result<(PathBuf, PathBuf)> {
-        let pcloud_dir = self.get_pcloud_dir();
+    /// --confirm-each: muestra la simulación de cada elemento y pregunta si
+    /// sincronizarlo, omitirlo o abortar. Devuelve los elementos aceptados.
+    async fn confirm_each_item(&self, items: Vec<String>, stats: &mut SyncStats) -> Result<Vec<String>> {
+        let mut accepted = Vec::new();
 
-        match self.args.get_mode() {
-            SyncMode::Upload => {
-                let source = Path::new(&self.config.general.local_dir).join(item);
-                let destination = pcloud_dir.join(item);
-                Ok((source, destination))
+        for item in items {
+            // Los que no existen en origen los omite después el bucle principal
+            if self.is_missing_source(&item) {
+                accepted.push(item);
+                continue;
             }
-            SyncMode::Download => {
-                let source = pcloud_dir.join(item);
-                let destination = Path::new(&self.config.general.local_dir).join(item);
-                Ok((source, destination))
+
+            match self.preview_item(&item).await {
+                Ok(report) => {
+                    println!();
+                    println!(
+                        "{}: {} nuevos, {} actualizados, {} borrados",
+                        item,
+                        report.created_count(),
+                        report.updated_count(),
+                        report.deleted_count()
+                    );
+                    for path in &report.created {
+                        println!("      + {}", path);
+                    }
+                    for path in &report.updated {
+                        println!("      ~ {}", path);
+                    }
+                    for path in &report.deleted {
+                        println!("      - {}", path);
+                    }
+                }
+                Err(AppError::Cancelled) => return Err(AppError::Cancelled),
+                Err(e) => println!("{}: no se pudo simular ({})", item, e),
+            }
+
+            loop {
+                println!("¿Sincronizar {}? [s]í / [o]mitir / [a]bortar: ", item);
+                let mut input = String::new();
+                std::io::stdin().read_line(&mut input)?;
+
+                match parse_item_answer(&input) {
+                    Some(ItemAnswer::Sync) => accepted.push(item),
+                    Some(ItemAnswer::Skip) => {
+                        log::info!("Elemento omitido por el usuario: {}", item);
+                        stats.items_skipped += 1;
+                    }
+                    Some(ItemAnswer::Abort) => return Err(AppError::Cancelled),
+                    None => continue,
+                }
+                break;
             }
         }
+
+        Ok(accepted)
     }
 
-    fn get_pcloud_dir(&self) -> PathBuf {
-        match self.args.get_backup_dir_mode() {
-            BackupDirMode::Common => PathBuf::from(&self.config.general.pcloud_backup_comun),
-            BackupDirMode::ReadOnly => PathBuf::from(&self.config.general.pcloud_backup_readonly),
+    /// Simulación de un elemento con --itemize-changes
+    async fn preview_item(&self, item: &str) -> Result<ItemReport> {
+        let simulation = SyncManager::new(
+            self.config.clone(),
+            Cli {
+                dry_run: true,
+                ..self.args.clone()
+            },
+        );
+        let (source, destination) = simulation.get_sync_paths(item)?;
+        let command = simulation.build_rsync_command(item, &source, &destination, None)?;
+        let output = self.execute_rsync(command).await?;
+
+        if !rsync_completed(&output.status) {
+            return Err(AppError::Rsync {
+                item: item.to_string(),
+                code: output.status.code(),
+                message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            });
         }
+        Ok(ItemReport::from_itemize_output(item, &String::from_utf8_lossy(&output.stdout)))
     }
 
-    fn build_rsync_command(&self, source: &Path, destination: &Path) -> Result<Command> {
-        let mut command = Command::new("rsync");
+    fn log_skipped_items(&self) -> Result<()> {
+        let selected = self.resolve_selected_items()?;
 
-        // Opciones base
-        command.args(["-av", "--progress", "--itemize-changes"]);
+        for skip in &self.args.skip_item {
+            if selected.iter().any(|item| is_skipped(item, std::slice::from_ref(skip))) {
+                log::info!("Elemento omitido por --skip-item: {}", skip);
+            } else {
+                log::warn!("--skip-item {} no coincide con ningún elemento a sincronizar", skip);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parámetros que identifican una ejecución para --resume-items
+    fn resume_key(&self, items: &[String]) -> String {
+        let args = &self.args;
+        let mut parts = vec![
+            args.get_mode().name(),
+            AppConfig::get_hostname(),
+            self.config.remote_name.clone().unwrap_or_default(),
+            self.config.config_path.display().to_string(),
+            format!("{:?} delete={} checksum={}", args.get_backup_dir_mode(), args.delete, args.checksum),
+        ];
+        parts.extend(items.iter().map(|item| format!("item={}", item)));
+        parts.extend(args.exclude.iter().map(|pattern| format!("exclude={}", pattern)));
+        parts.extend(args.include.iter().map(|pattern| format!("include={}", pattern)));
+        parts.extend(args.filter.iter().map(|rule| format!("filter={}", rule)));
+        resume::run_key(&parts)
+    }
+
+    /// Busca una ejecución interrumpida con los mismos parámetros. Con
+    /// --resume-items se quitan de `items` los ya completados; sin la opción
+    /// solo se avisa. Devuelve el estado donde anotar el progreso (ninguno
+    /// en simulación) y los elementos pendientes.
+    fn prepare_resume(&self, items: Vec<String>, stats: &mut SyncStats) -> (Option<ResumeState>, Vec<String>) {
+        let file = &self.config.general.resume_state_file;
+        let key = self.resume_key(&items);
+        let max_age = chrono::Duration::hours(self.config.general.resume_max_age_hours as i64);
+
+        let (state, items) = match ResumeState::load(file, &key, max_age) {
+            Some(state) if self.args.resume_items => {
+                let pending: Vec<String> = items
+                    .into_iter()
+                    .filter(|item| !state.completed().contains(item))
+                    .collect();
+                stats.items_resumed = state.completed().len() as u32;
+                log::info!(
+                    "Reanudando la ejecución interrumpida de {}: se omiten {} elementos ya completados",
+                    state.updated().format("%Y-%m-%d %H:%M:%S"),
+                    state.completed().len()
+                );
+                (state, pending)
+            }
+            Some(state) => {
+                if !state.completed().is_empty() {
+                    log::info!(
+                        "Una ejecución interrumpida con los mismos parámetros ya completó {} elementos; use --resume-items para omitirlos",
+                        state.completed().len()
+                    );
+                }
+                (ResumeState::new(file, key), items)
+            }
+            None => (ResumeState::new(file, key), items),
+        };
 
-        // Opciones condicionales
         if self.args.dry_run {
-            command.arg("--dry-run");
+            return (None, items);
         }
+        (Some(state), items)
+    }
 
-        if self.args.delete {
-            command.arg("--delete-delay");
+    async fn sync_main_items(&self, stats: &mut SyncStats) -> Result<()> {
+        self.log_skipped_items()?;
+        let (mut resume, mut items_to_sync) = self.prepare_resume(self.resolve_items()?, stats);
+        if self.args.confirm_each {
+            items_to_sync = self.confirm_each_item(items_to_sync, stats).await?;
         }
+        let parallel = self.args.parallel.max(1);
 
-        if !self.args.overwrite {
-            command.arg("--update");
+        if parallel > 1 {
+            log::info!("Sincronizando hasta {} elementos en paralelo", parallel);
         }
 
-        if self.args.checksum {
-            command.arg("--checksum");
+        // Cada tarea trabaja con sus propias estadísticas, que se fusionan al terminar
+        let mut manager = self.clone();
+        if self.args.progress_total && !self.args.dry_run {
+            let total = self.estimate_total_bytes(&items_to_sync, false).await?;
+            log::info!("Total estimado a transferir: {}", format_bytes(total));
+            stats.estimated_total_bytes = total;
+            manager.progress = Some(Arc::new(ProgressTotal::new(total)));
         }
+        if self.args.exclude_larger_than_free {
+            manager.free_space_cap = self.free_space_cap()?;
+        }
+        let manager = Arc::new(manager);
+        let mut pending = items_to_sync.into_iter();
+        let mut tasks = JoinSet::new();
+        let mut all_succeeded = true;
+
+        loop {
+            // Tras una señal de parada no se lanzan más elementos
+            while tasks.len() < parallel && !shutdown::is_requested() {
+                let Some(item) = pending.next() else { break };
+                if manager.is_missing_source(&item) {
+                    log::warn!("Omitido (no existe en origen): {}", item);
+                    stats.items_missing += 1;
+                    continue;
+                }
+                let manager = Arc::clone(&manager);
+                tasks.spawn(async move {
+                    let (item_stats, result) = manager.sync_item(&item).await;
+                    (item, result, item_stats)
+                });
+            }
 
-        if let Some(bwlimit) = self.args.bwlimit {
-            command.args(["--bwlimit", &bwlimit.to_string()]);
+            let Some(joined) = tasks.join_next().await else { break };
+            match joined {
+                Ok((item, result, item_stats)) => {
+                    stats.merge(item_stats);
+                    if let Err(e) = result {
+                        log::error!("Error sincronizando {}: {}", item, e);
+                        stats.record_failed_item(&item, &e);
+                        all_succeeded = false;
+                    } else {
+                        stats.record_successful_item();
+                        if let Some(Err(e)) = resume.as_mut().map(|state| state.record(&item)) {
+                            log::warn!("No se pudo guardar el progreso para --resume-items: {}", e);
+                        }
+                    }
+                }
+                Err(e) => {
+                    log::error!("Error en tarea de sincronización: {}", e);
+                    stats.record_error();
+                    all_succeeded = false;
+                }
+            }
         }
 
-        // Exclusiones
-        let host_config = self.config.get_current_host_config()?;
-        for exclusion in &host_config.exclusions {
-            command.args(["--exclude", exclusion]);
+        if let Some(progress) = &manager.progress {
+            stats.peak_bytes_per_sec = progress.peak_rate();
         }
 
-        for exclusion in &self.args.exclude {
-            command.args(["--exclude", exclusion]);
+        if shutdown::is_requested() {
+            log::warn!("Sincronización interrumpida: quedan elementos sin procesar");
+            return Err(AppError::Cancelled);
         }
 
-        // Rutas
-        command.arg(source);
-        command.arg(destination);
+        if resume.is_some() && all_succeeded {
+            if let Err(e) = ResumeState::clear(&self.config.general.resume_state_file) {
+                log::warn!("No se pudo borrar el progreso de --resume-items: {}", e);
+            }
+        }
 
-        Ok(command)
+        Ok(())
     }
 
-    async fn execute_rsync(&self, command: &mut Command) -> Result<std::process::Output> {
-        let output = command.output().map_err(|e| {
-            AppError::Sync(format!("Error ejecutando rsync: {}", e))
-        })?;
-
-        Ok(output)
+    /// Sincroniza un elemento y devuelve sus estadísticas, también las
+    /// parciales si falla a medias (p. ej. un sentido de --two-way)
+    async fn sync_item(&self, item: &str) -> (ItemStats, Result<()>) {
+        let mut stats = ItemStats::default();
+        let result = self.transfer_item(item, &mut stats).await;
+        (stats, result)
     }
 
-    fn process_rsync_output(&self, output: &std::process::Output, stats: &mut SyncStats) {
-        if output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let files_transferred = stdout.matches(">f").count();
-            stats.record_files_transferred(files_transferred);
-            log::info!("Sincronización completada: {} archivos transferidos", files_transferred);
-        } else {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            log::error!("Error en rsync: {}", stderr);
+    /// --check-case/--resolve-case: avisa de los nombres del origen que solo
+    /// difieren en mayúsculas y, con --resolve-case, los renombra antes de rsync
+    fn check_case_collisions(&self, item: &str, source: &Path, stats: &mut ItemStats) -> Result<()> {
+        if !source.is_dir() {
+            return Ok(());
+        }
+
+        let collisions = case_collisions::find(source, &self.config.general.partial_dir)?;
+        stats.case_collisions += collisions.len() as u32;
+
+        for collision in &collisions {
+            log::warn!(
+                "{}: nombres que solo difieren en mayúsculas en {}: {}",
+                item,
+                collision.dir.display(),
+                collision.names.join(", ")
+            );
+            if !self.args.resolve_case {
+                continue;
+            }
+
+            for (from, to) in case_collisions::renames(collision, self.config.general.case_policy)? {
+                if self.args.dry_run {
+                    println!("Se renombraría (simulación): {} -> {}", from.display(), to.display());
+                } else {
+                    std::fs::rename(&from, &to)?;
+                    log::info!("Renombrado por colisión de mayúsculas: {} -> {}", from.display(), to.display());
+                }
+            }
         }
-    }
 
-    async fn sync_crypto(&self, _stats: &mut SyncStats) -> Result<()> {
-        log::info!("Sincronizando directorio Crypto");
-        // Implementación específica para Crypto
         Ok(())
     }
 
-    async fn handle_symbolic_links(&self, _stats: &mut SyncStats) -> Result<()> {
-        match self.args.get_mode() {
-            SyncMode::Upload => self.backup_symbolic_links(_stats).await,
-            SyncMode::Download => self.restore_symbolic_links(_stats).await,
+    async fn transfer_item(&self, item: &str, stats: &mut ItemStats) -> Result<()> {
+        log::info!("Sincronizando: {}", item);
+
+        // Nunca lanzar rsync sobre sí mismo (p. ej. local_dir igual al punto de
+        // montaje); con un remoto SSH cada ruta es de un equipo distinto
+        let (source, destination) = self.get_sync_paths(item)?;
+        if self.config.ssh.is_none() {
+            ensure_distinct_paths(&source, &destination)?;
         }
-    }
 
-    async fn backup_symbolic_links(&self, _stats: &mut SyncStats) -> Result<()> {
-        log::info!("Realizando backup de enlaces simbólicos");
-        Ok(())
+        if self.args.check_case || self.args.resolve_case {
+            self.check_case_collisions(item, &source, stats)?;
+        }
+
+        if self.args.get_mode() == SyncMode::TwoWay {
+            return self.sync_item_two_way(item, stats).await;
+        }
+
+        // Con la cache de checksums solo se pasan a rsync los archivos que difieren
+        let checksum_plan = match self.checksum_cache_dir(&source) {
+            Some(cache_dir) => {
+                let plan = self.plan_checksum_transfer(item, cache_dir, &source, &destination).await?;
+                if plan.changed.is_empty() {
+                    log::info!("Sin diferencias por checksum (cache): {}", item);
+                    return plan.finish(&destination, false);
+                }
+                Some(plan)
+            }
+            None => None,
+        };
+
+        // --force-checksum-on: primero la pasada rápida sin los patrones críticos
+        let forced_checksum = checksum_plan.is_none() && !self.forced_checksum_patterns(&source).is_empty();
+        let first_pass = match forced_checksum {
+            true => self.with_checksum_pass(ChecksumPass::Fast),
+            false => self.clone(),
+        };
+
+        // Construir comando rsync
+        let files_from = checksum_plan.as_ref().map(|plan| plan.files_from.as_path());
+        let command = first_pass.build_rsync_command(item, &source, &destination, files_from)?;
+
+        // La instantánea nueva empieza vacía: rsync solo crea el último nivel
+        if self.snapshot.is_some() && !self.args.dry_run {
+            if let Some(parent) = destination.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+
+        // Ejecutar con timeout
+        let timeout_duration = Duration::from_secs(self.item_timeout_minutes(item)? as u64 * 60);
+
+        match timeout(timeout_duration, self.execute_transfer(item, command)).await {
+            Ok(Ok((output, itemized))) => {
+                let rsync_result = self.process_rsync_output(item, &output, itemized, stats);
+
+                if let Some(plan) = checksum_plan {
+                    plan.finish(&destination, !self.args.dry_run && output.status.success())?;
+                }
+                let deleted_from = rsync_result?;
+
+                if forced_checksum {
+                    self.run_strong_checksum_pass(item, &source, &destination, timeout_duration, stats)
+                        .await?;
+                }
+
+                // Directorios que el borrado de rsync dejó vacíos; con un remoto
+                // SSH el destino puede ser del otro equipo y no se toca
+                let completed = rsync_completed(&output.status);
+                let prune = self.args.prune_empty_dirs && !self.args.dry_run && completed;
+                if prune && self.config.ssh.is_some() {
+                    log::debug!("{}: con un remoto SSH, --prune-empty-dirs solo se pasa a rsync", item);
+                } else if prune && destination.is_dir() {
+                    let pruned = prune_emptied_dirs(&source, &destination, &deleted_from)?;
+                    if pruned > 0 {
+                        log::info!("{}: {} directorios vacíos eliminados", item, pruned);
+                    }
+                    stats.dirs_pruned += pruned;
+                }
+
+                // Verificación posterior por checksum (no tiene sentido en simulación)
+                if self.args.verify && !self.args.dry_run && completed {
+                    self.verify_item(item, &source, &destination, timeout_duration, stats).await?;
+                }
+
+                if self.args.manifest && !self.args.dry_run && completed {
+                    self.process_manifest(item, &destination, stats).await?;
+                }
+
+                Ok(())
+            }
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(AppError::Timeout(format!("Operación excedió el tiempo límite para: {}", item))),
+        }
     }
 
-    async fn restore_symbolic_links(&self, _stats: &mut SyncStats) -> Result<()> {
-        log::info!("Restaurando enlaces simbólicos");
-        Ok(())
+    /// Patrones de --force-checksum-on y `force_checksum_on`; vacío si no
+    /// aplica (--checksum ya lo compara todo, o el elemento es un archivo)
+    fn forced_checksum_patterns(&self, source: &Path) -> Vec<&String> {
+        if self.args.checksum || !source.is_dir() {
+            return Vec::new();
+        }
+        self.config
+            .general
+            .force_checksum_on
+            .iter()
+            .chain(&self.args.force_checksum_on)
+            .collect()
     }
-}
 
-// Funciones públicas para uso desde main
-pub fn show_banner(args: &Cli, _config: &AppConfig) {
-    println!("==========================================");
-    println!("Sincronización Bidireccional - syncb");
-    println!("Modo: {:?}", args.get_mode());
-    println!("Host: {}", AppConfig::get_hostname());  // Ahora es pública
-    println!("==========================================");
-}
+    fn with_checksum_pass(&self, pass: ChecksumPass) -> SyncManager {
+        SyncManager {
+            args: Cli {
+                checksum: pass == ChecksumPass::Strong,
+                ..self.args.clone()
+            },
+            checksum_pass: Some(pass),
+            ..self.clone()
+        }
+    }
 
-pub fn verify_dependencies() -> Result<()> {
-    // Verificar que rsync está disponible
-    let status = Command::new("rsync")
-        .arg("--version")
-        .status()
-        .map_err(|e| AppError::Sync(format!("rsync no está disponible: {}", e)))?;
+    /// Segunda pasada de --force-checksum-on; sus archivos se cuentan aparte
+    async fn run_strong_checksum_pass(
+        &self,
+        item: &str,
+        source: &Path,
+        destination: &Path,
+        timeout_duration: Duration,
+        stats: &mut ItemStats,
+    ) -> Result<()> {
+        let strong = self.with_checksum_pass(ChecksumPass::Strong);
+        let command = strong.build_rsync_command(item, source, destination, None)?;
+
+        let (output, itemized) = match timeout(timeout_duration, strong.execute_transfer(item, command)).await {
+            Ok(output) => output?,
+            Err(_) => {
+                return Err(AppError::Timeout(format!(
+                    "La pasada con checksum excedió el tiempo límite para: {}",
+                    item
+                )))
+            }
+        };
 
-    if !status.success() {
-        return Err(AppError::Sync("rsync no está funcionando correctamente".to_string()));
+        let mut pass_stats = ItemStats::default();
+        let result = strong.process_rsync_output(item, &output, itemized, &mut pass_stats).map(drop);
+        log::info!(
+            "{}: pasada con checksum (--force-checksum-on): {} archivos transferidos",
+            item,
+            pass_stats.files_transferred
+        );
+        stats.files_checksum_forced += pass_stats.files_transferred;
+        stats.merge(pass_stats);
+        result
     }
 
-    log::info!("Dependencias verificadas: OK");
-    Ok(())
-}
+    /// --two-way: compara ambos árboles y ejecuta rsync una vez por sentido
+    /// con la lista de archivos que debe viajar en cada uno
+    async fn sync_item_two_way(&self, item: &str, stats: &mut ItemStats) -> Result<()> {
+        let (local, remote) = self.get_sync_paths(item)?;
+        if !local.is_dir() && !remote.is_dir() {
+            return Err(AppError::Validation(format!(
+                "--two-way solo admite directorios y {} no lo es en ningún lado",
+                item
+            )));
+        }
 
-pub async fn verify_preconditions(config: &AppConfig) -> Result<()> {
-    // Crear un Cli por defecto
-    let args = Cli {
-        subir: true,
-        ..Default::default()
-    };
-    let temp_manager = SyncManager::new(config.clone(), args);
-    temp_manager.verify_preconditions().await
-}
+        let state_file = &self.config.general.two_way_state_file;
+        let key = remote.display().to_string();
+        let mut baselines = two_way::Baselines::load(state_file);
 
-pub fn confirm_execution() -> Result<()> {
-    println!("¿Desea continuar con la sincronización? [s/N]: ");
+        let plan = two_way::plan(
+            &two_way::scan_tree(&local)?,
+            &two_way::scan_tree(&remote)?,
+            baselines.get(&key),
+            self.args.conflict_strategy,
+        );
 
-    let mut input = String::new();
-    std::io::stdin().read_line(&mut input)?;
+        log::info!(
+            "{}: {} archivos hacia pCloud, {} hacia local, {} conflictos",
+            item,
+            plan.to_remote.len(),
+            plan.to_local.len(),
+            plan.conflicts.len()
+        );
+        for path in &plan.conflicts {
+            log::warn!("Conflicto sin resolver en {}: {} (ver --conflict-strategy)", item, path);
+        }
+        stats.two_way_conflicts += plan.conflicts.len() as u32;
+
+        let timeout_duration = Duration::from_secs(self.item_timeout_minutes(item)? as u64 * 60);
+        self.transfer_listed(item, &local, &remote, &plan.to_remote, timeout_duration, stats).await?;
+        self.transfer_listed(item, &remote, &local, &plan.to_local, timeout_duration, stats).await?;
+
+        if !self.args.dry_run {
+            let next = two_way::next_baseline(
+                &two_way::scan_tree(&local)?,
+                &two_way::scan_tree(&remote)?,
+                baselines.get(&key),
+                &plan.conflicts,
+            );
+            baselines.set(key, next);
+            baselines.save()?;
+        }
 
-    if input.trim().eq_ignore_ascii_case("s") {
         Ok(())
-    } else {
-        Err(AppError::Validation("Operación cancelada por el usuario".to_string()))
     }
-}
 
-pub async fn perform_sync(args: &Cli, config: &AppConfig, stats: &mut SyncStats) -> Result<()> {
-    let manager = SyncManager::new(config.clone(), args.clone());
-    manager.perform_sync(stats).await
+    /// Copia solo `files` (relativos a `source`) con --files-from
+    async fn transfer_listed(
+        &self,
+        item: &str,
+        source: &Path,
+        destination: &Path,
+        files: &[String],
+        timeout_duration: Duration,
+        stats: &mut ItemStats,
+    ) -> Result<()> {
+        if files.is_empty() {
+            return Ok(());
+        }
+
+        let files_from = std::env::temp_dir().join(format!(
+            "syncb-{}-{}.files",
+            std::process::id(),
+            destination.display().to_string().replace(std::path::MAIN_SEPARATOR, "_")
+        ));
+        std::fs::write(&files_from, files.iter().map(|f| format!("{}\n", f)).collect::<String>())?;
+
+        if !self.args.dry_run {
+            std::fs::create_dir_all(destination)?;
+        }
+
+        // El sentido ya está decidido: --update no debe descartar nada
+        let manager = SyncManager {
+            args: Cli {
+                overwrite: true,
+                ..self.args.clone()
+            },
+            ..self.clone()
+        };
+        let command = manager.build_rsync_command(item, source, destination, Some(&files_from))?;
+        let result = timeout(timeout_duration, self.execute_transfer(item, command)).await;
+        let _ = std::fs::remove_file(&files_from);
+
+        let (output, itemized) = match result {
+            Ok(output) => output?,
+            Err(_) => {
+                return Err(AppError::Timeout(format!(
+                    "Operación excedió el tiempo límite para: {}",
+                    item
+                )))
+            }
+        };
+
+        self.process_rsync_output(item, &output, itemized, stats)?;
+        Ok(())
+    }
+
+    /// Directorio de la cache de checksums si aplica: --checksum sobre un
+    /// directorio, con `checksum_cache_dir` configurado y sin --delete (con
+    /// --files-from rsync no borraría lo que falta en el origen)
+    fn checksum_cache_dir(&self, source: &Path) -> Option<&Path> {
+        let cache_dir = self.config.general.checksum_cache_dir.as_deref()?;
+        // Con un remoto SSH el destino no se puede leer para calcular checksums
+        if !self.args.checksum || !source.is_dir() || self.config.ssh.is_some() {
+            return None;
+        }
+        if self.args.delete {
+            log::debug!("--delete activo: no se usa la cache de checksums");
+            return None;
+        }
+        Some(cache_dir)
+    }
+
+    async fn plan_checksum_transfer(
+        &self,
+        item: &str,
+        cache_dir: &Path,
+        source: &Path,
+        destination: &Path,
+    ) -> Result<ChecksumPlan> {
+        let mode = self.args.get_mode();
+        if self.args.rebuild_cache {
+            log::info!("Reconstruyendo la cache de checksums de {}", item);
+            ChecksumCache::invalidate(cache_dir, mode, item)?;
+        }
+
+        let (cache_dir, item_owned) = (cache_dir.to_path_buf(), item.to_string());
+        let (source, destination) = (source.to_path_buf(), destination.to_path_buf());
+        let (cache, changed) = tokio::task::spawn_blocking(move || -> Result<_> {
+            let mut cache = ChecksumCache::load(&cache_dir, mode, &item_owned);
+            let changed = cache.changed_files(&source, &destination)?;
+            Ok((cache, changed))
+        })
+        .await
+        .map_err(|e| AppError::Sync(format!("Error calculando checksums: {}", e)))??;
+
+        log::info!("{}: {} archivos con diferencias según la cache de checksums", item, changed.len());
+
+        let files_from = cache.files_from_path();
+        if let Some(parent) = files_from.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let list: String = changed
+            .iter()
+            .map(|(path, _)| format!("{}\n", path.display()))
+            .collect();
+        std::fs::write(&files_from, list)?;
+
+        Ok(ChecksumPlan { cache, changed, files_from })
+    }
+
+    /// Timeout del elemento: `item_timeouts` del host, luego --timeout y por último el global
+    fn item_timeout_minutes(&self, item: &str) -> Result<u32> {
+        let host_config = self.config.get_current_host_config()?;
+        let (minutes, origin) = match host_config.item_timeouts.get(item) {
+            Some(&minutes) => (minutes, "item_timeouts"),
+            None => match self.args.timeout {
+                Some(minutes) => (minutes, "--timeout"),
+                None => (self.config.general.default_timeout_minutes, "default_timeout_minutes"),
+            },
+        };
+
+        log::info!("Timeout para {}: {} minutos ({})", item, minutes, origin);
+        Ok(minutes)
+    }
+
+    /// Compara origen y destino por checksum sin transferir nada; cualquier
+    /// diferencia restante cuenta como fallo de verificación
+    async fn verify_item(
+        &self,
+        item: &str,
+        source: &Path,
+        destination: &Path,
+        timeout_duration: Duration,
+        stats: &mut ItemStats,
+    ) -> Result<()> {
+        log::info!("Verificando por checksum: {}", item);
+
+        let command = self.build_verify_command(source, destination)?;
+        let output = match timeout(timeout_duration, self.execute_rsync(command)).await {
+            Ok(result) => result?,
+            Err(_) => {
+                return Err(AppError::Timeout(format!(
+                    "La verificación excedió el tiempo límite para: {}",
+                    item
+                )))
+            }
+        };
+
+        if !rsync_completed(&output.status) {
+            return Err(AppError::Sync(format!(
+                "Error en la verificación de {}: {}",
+                item,
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let differences: Vec<&str> = stdout.lines().filter(|l| is_itemized_change(l)).collect();
+
+        if differences.is_empty() {
+            log::info!("Verificación correcta: {}", item);
+        } else {
+            for line in &differences {
+                log::error!("Diferencia tras sincronizar {}: {}", item, line);
+            }
+            stats.record_verification_failures(differences.len());
+        }
+
+        Ok(())
+    }
+
+    /// Pasada --checksum --dry-run de --verify. Con --update (sin
+    /// --overwrite) la transferencia respetó los archivos más recientes del
+    /// destino: la verificación también, o contarían como diferencias
+    fn build_verify_command(&self, source: &Path, destination: &Path) -> Result<Command> {
+        let mut command = Command::new("rsync");
+        self.add_base_options(&mut command, false);
+        command.args(["--checksum", "--dry-run"]);
+        if !self.args.overwrite {
+            command.arg("--update");
+        }
+        self.add_checksum_choice(&mut command);
+        self.add_exclusions(&mut command, source)?;
+        self.add_paths(&mut command, source, destination);
+        Ok(command)
+    }
+
+    /// --manifest: al subir guarda en pCloud el SHA256SUMS de lo que quedó
+    /// en destino; al bajar comprueba con él los archivos recién recibidos
+    async fn process_manifest(&self, item: &str, destination: &Path, stats: &mut ItemStats) -> Result<()> {
+        let file = manifest::manifest_path(&self.get_pcloud_dir(), item);
+        let root = destination.to_path_buf();
+
+        match self.args.get_mode() {
+            SyncMode::Upload => {
+                log::info!("Generando manifiesto SHA-256: {}", item);
+                let partial_dir = self.config.general.partial_dir.clone();
+                let entries = tokio::task::spawn_blocking(move || manifest::generate(&root, &partial_dir))
+                    .await
+                    .map_err(|e| AppError::Sync(format!("Error generando el manifiesto: {}", e)))??;
+                manifest::write(&file, &entries)?;
+                log::info!("{}: manifiesto de {} archivos en {:?}", item, entries.len(), file);
+            }
+            SyncMode::Download => {
+                if !file.is_file() {
+                    log::warn!("{}: no hay manifiesto en {:?}; no se verifica", item, file);
+                    return Ok(());
+                }
+
+                log::info!("Verificando con el manifiesto SHA-256: {}", item);
+                let entries = manifest::read(&file)?;
+                let (mismatched, missing) = tokio::task::spawn_blocking(move || manifest::verify(&root, &entries))
+                    .await
+                    .map_err(|e| AppError::Sync(format!("Error verificando el manifiesto: {}", e)))??;
+
+                for name in &missing {
+                    log::warn!("{}: {} figura en el manifiesto pero no está en local", item, name);
+                }
+                for name in &mismatched {
+                    log::error!("{}: {} no coincide con el manifiesto", item, name);
+                }
+                if mismatched.is_empty() {
+                    log::info!("Manifiesto verificado: {}", item);
+                }
+                stats.record_verification_failures(mismatched.len());
+            }
+            SyncMode::TwoWay => {}
+        }
+
+        Ok(())
+    }
+
+    fn get_sync_paths(&self, item: &str) -> Result<(PathBuf, PathBuf)> {
+        validate_item(item)?;
+        let pcloud_dir = self.get_pcloud_dir();
+
+        match self.args.get_mode() {
+            SyncMode::Upload | SyncMode::TwoWay => {
+                let source = self.config.local_dir().join(item);
+                let destination = pcloud_dir.join(item);
+                Ok((source, destination))
+            }
+            SyncMode::Download => {
+                let source = pcloud_dir.join(item);
+                let destination = self.config.local_dir().join(item);
+                Ok((source, destination))
+            }
+        }
+    }
+
+    fn get_pcloud_dir(&self) -> PathBuf {
+        if let Some(snapshot) = &self.snapshot {
+            return snapshot.current.clone();
+        }
+
+        match self.args.get_backup_dir_mode() {
+            BackupDirMode::Common => PathBuf::from(&self.config.general.pcloud_backup_comun),
+            BackupDirMode::ReadOnly => PathBuf::from(&self.config.general.pcloud_backup_readonly),
+        }
+    }
+
+    /// Con `files_from` solo se transfieren esos archivos (ya sabemos que
+    /// difieren), así que se omite --checksum y se fuerza con --ignore-times
+    fn build_rsync_command(
+        &self,
+        item: &str,
+        source: &Path,
+        destination: &Path,
+        files_from: Option<&Path>,
+    ) -> Result<Command> {
+        let mut command = Command::new("rsync");
+
+        // Opciones base
+        self.add_base_options(&mut command, true);
+
+        // Enlaces simbólicos (-a ya implica --links, -rt no)
+        let base_options = &self.config.general.rsync_base_options;
+        match self.args.symlink_policy.unwrap_or(self.config.general.symlink_policy) {
+            SymlinkPolicy::Preserve => {
+                if !has_rsync_option(base_options, "--links", 'l') && !has_rsync_option(base_options, "--archive", 'a') {
+                    command.arg("--links");
+                }
+            }
+            SymlinkPolicy::Follow => {
+                command.arg("--copy-links");
+            }
+            SymlinkPolicy::Skip => {
+                command.arg("--no-links");
+            }
+        }
+
+        // Enlaces que salen del árbol transferido
+        if self.args.safe_links {
+            command.arg("--safe-links");
+        } else if self.args.copy_unsafe_links {
+            command.arg("--copy-unsafe-links");
+        }
+
+        // Opciones condicionales
+        if self.args.dry_run {
+            command.arg("--dry-run");
+        }
+
+        if self.args.delete {
+            let policy = self.args.delete_policy.unwrap_or(self.config.general.delete_policy);
+            command.arg(policy.flag());
+            if self.args.delete_excluded {
+                command.arg("--delete-excluded");
+            }
+        }
+
+        if self.args.prune_empty_dirs {
+            command.arg("--prune-empty-dirs");
+        }
+
+        if !self.args.overwrite {
+            command.arg("--update");
+        }
+
+        match files_from {
+            Some(files_from) => {
+                command.arg("--ignore-times");
+                command.arg(format!("--files-from={}", files_from.display()));
+            }
+            None if self.args.checksum => {
+                command.arg("--checksum");
+                self.add_checksum_choice(&mut command);
+            }
+            None => {}
+        }
+
+        if self.args.resume {
+            command.arg("--partial");
+            command.arg(format!("--partial-dir={}", self.config.general.partial_dir));
+        }
+
+        // Límites de tamaño: la línea de comandos prevalece sobre el host y el
+        // tope de --exclude-larger-than-free se aplica si es menor.
+        // --info=skip1 hace que rsync informe de lo que deja fuera por tamaño
+        let host_config = self.config.get_current_host_config()?;
+        let max_size = [self.args.max_filesize.or(host_config.max_filesize), self.free_space_cap]
+            .into_iter()
+            .flatten()
+            .min();
+        let min_size = self.args.min_filesize.or(host_config.min_filesize);
+        if let Some(max_size) = max_size {
+            command.arg(format!("--max-size={}", max_size));
+        }
+        if let Some(min_size) = min_size {
+            command.arg(format!("--min-size={}", min_size));
+        }
+        if max_size.is_some() || min_size.is_some() {
+            command.arg("--info=skip1");
+        }
+
+        // Compresión: la línea de comandos prevalece sobre la configuración
+        let general = &self.config.general;
+        let compress_level = self.args.compress_level.or(general.compress_level);
+        if self.args.compress || general.compress || compress_level.is_some() {
+            command.arg("--compress");
+            if let Some(level) = compress_level {
+                command.arg(format!("--compress-level={}", level));
+            }
+            if let Some(skip) = self.args.skip_compress.as_ref().or(general.skip_compress.as_ref()) {
+                command.arg(format!("--skip-compress={}", skip));
+            }
+        }
+
+        // Repartir el límite entre los procesos rsync simultáneos
+        command.args(self.args.rsync_bwlimit_args(self.args.parallel));
+
+        // --stats: los archivos revisados sin cambios solo salen en el resumen
+        command.arg("--stats");
+
+        // Instantánea: los archivos sin cambios se enlazan con la anterior
+        if let Some(snapshot) = &self.snapshot {
+            if let (Some(previous), Ok(relative)) =
+                (&snapshot.previous, destination.strip_prefix(&snapshot.current))
+            {
+                command.arg(format!("--link-dest={}", previous.join(relative).display()));
+            }
+        }
+
+        // Referencia de solo lectura: lo que coincide con ella no se transfiere.
+        // Un elemento que es un archivo se compara dentro de su directorio
+        if let Some(reference) = &self.args.compare_dest {
+            let reference = absolute_path(&normalize_path(reference)).join(item);
+            let reference = match source.is_dir() {
+                true => reference,
+                false => reference.parent().map(Path::to_path_buf).unwrap_or(reference),
+            };
+            command.arg(format!("--compare-dest={}", reference.display()));
+        }
+
+        // Opciones propias del elemento (validadas al cargar la configuración)
+        if let Some(options) = host_config.item_options.get(item) {
+            command.args(options);
+        }
+
+        self.add_exclusions(&mut command, source)?;
+
+        // Rutas
+        self.add_paths(&mut command, source, destination);
+
+        Ok(command)
+    }
+
+    /// Origen y destino; con un remoto SSH el lado de pCloud es
+    /// `usuario@host:ruta` y rsync usa ssh como transporte
+    fn add_paths(&self, command: &mut Command, source: &Path, destination: &Path) {
+        match &self.config.ssh {
+            Some(ssh) => {
+                command.args(["-e", &ssh.rsh()]);
+                command.args(ssh_rsync_paths(ssh, self.args.get_mode(), source, destination));
+            }
+            None => {
+                command.args(rsync_paths(source, destination));
+            }
+        }
+    }
+
+    /// `rsync_base_options` y, si faltan, --itemize-changes: de su salida
+    /// salen los recuentos de cambios. Sin `progress` se omite --progress.
+    fn add_base_options(&self, command: &mut Command, progress: bool) {
+        let base_options = &self.config.general.rsync_base_options;
+        command.args(base_options.iter().filter(|option| progress || *option != "--progress"));
+        if !has_rsync_option(base_options, "--itemize-changes", 'i') {
+            command.arg("--itemize-changes");
+        }
+    }
+
+    fn add_exclusions(&self, command: &mut Command, source: &Path) -> Result<()> {
+        // Exclusiones (el directorio de transferencias parciales nunca es dato real)
+        command.args(["--exclude", &format!("{}/", self.config.general.partial_dir)]);
+
+        // Reglas de filtro explícitas (línea de comandos y después host) antes
+        // que las generadas: en rsync gana la primera regla que coincide
+        let host_config = self.config.get_current_host_config()?;
+        for rule in self.args.filter.iter().chain(&host_config.filters) {
+            command.arg(format!("--filter={}", rule));
+        }
+
+        // --include va antes que cualquier exclusión
+        for inclusion in &self.args.include {
+            command.args(["--include", inclusion]);
+        }
+
+        for exclusion in &host_config.exclusions {
+            command.args(["--exclude", exclusion]);
+        }
+
+        for exclusion in &self.args.exclude {
+            command.args(["--exclude", exclusion]);
+        }
+
+        if self.args.exclude_vcs {
+            for exclusion in VCS_EXCLUSIONS {
+                command.args(["--exclude", exclusion]);
+            }
+        }
+
+        // Archivos de exclusiones: el de la configuración y el de línea de comandos
+        if let Some(exclude_from) = &self.config.general.exclude_from {
+            command.arg("--exclude-from").arg(exclude_from);
+        }
+
+        if let Some(exclude_from) = &self.args.exclude_from {
+            command
+                .arg("--exclude-from")
+                .arg(absolute_path(&normalize_path(exclude_from)));
+        }
+
+        // Archivos tipo .gitignore del origen, traducidos a reglas de rsync
+        // (solo si el origen es local: al bajar por SSH está en el remoto)
+        let remote_source = self.remote_source();
+        let ignore_names: Vec<String> = self
+            .config
+            .general
+            .ignore_files
+            .iter()
+            .chain(&self.args.ignore_file)
+            .cloned()
+            .collect();
+        if remote_source && !ignore_names.is_empty() {
+            log::warn!("Los archivos de exclusión ({}) no se aplican al bajar por SSH", ignore_names.join(", "));
+        } else {
+            for rule in ignore_files::collect(source, &ignore_names)? {
+                command.arg(format!("--filter={}", rule));
+            }
+        }
+
+        // Ocultos: detrás de las reglas explícitas para que --include pueda rescatar algo
+        if self.args.no_hidden {
+            command.args(["--exclude", ".*"]);
+        } else if self.args.only_hidden {
+            // Los ocultos con todo su contenido; del resto solo se recorren directorios
+            command.args(["--include", ".*", "--include", ".*/**", "--include", "*/"]);
+            command.args(["--exclude", "*", "--prune-empty-dirs"]);
+        }
+
+        // --force-checksum-on: van detrás de las exclusiones para que estas sigan
+        // mandando (en rsync gana la primera regla que coincide)
+        let patterns = &self.config.general.force_checksum_on;
+        let patterns = patterns.iter().chain(&self.args.force_checksum_on);
+        match self.checksum_pass {
+            Some(ChecksumPass::Fast) => {
+                for pattern in patterns {
+                    command.args(["--exclude", pattern]);
+                }
+            }
+            Some(ChecksumPass::Strong) => {
+                command.args(["--include", "*/"]);
+                for pattern in patterns {
+                    command.args(["--include", pattern]);
+                }
+                command.args(["--exclude", "*", "--prune-empty-dirs"]);
+            }
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    /// --checksum-choice (o `checksum_choice`) si el rsync instalado lo admite
+    fn add_checksum_choice(&self, command: &mut Command) {
+        let requested = self.args.checksum_choice.as_ref().or(self.config.general.checksum_choice.as_ref());
+        if let Some(choice) = requested.and_then(|choice| supported_checksum_choice(choice, RSYNC_CHECKSUMS.as_deref())) {
+            command.arg(format!("--checksum-choice={}", choice));
+        }
+    }
+
+    async fn execute_rsync(&self, command: Command) -> Result<std::process::Output> {
+        Ok(self.run_rsync(command, None).await?.0)
+    }
+
+    /// `execute_rsync` de una transferencia: con `report_sample_size` el
+    /// informe se resume mientras rsync escribe, y el stdout devuelto ya no
+    /// contiene las líneas de --itemize-changes ni las de --progress
+    async fn execute_transfer(&self, item: &str, command: Command) -> Result<(std::process::Output, Option<ItemizeParser>)> {
+        let itemized = self
+            .config
+            .general
+            .report_sample_size
+            .map(|size| ItemizeParser::new(item, Some(size)));
+        self.run_rsync(command, itemized).await
+    }
+
+    async fn run_rsync(
+        &self,
+        command: Command,
+        itemized: Option<ItemizeParser>,
+    ) -> Result<(std::process::Output, Option<ItemizeParser>)> {
+        let line = command_line(&command);
+        if self.args.trace_commands {
+            log::info!("Ejecutando: {}", line);
+        } else {
+            log::trace!("Ejecutando: {}", line);
+        }
+
+        // Proceso asíncrono para no bloquear el runtime; se mata si vence el timeout
+        let mut command = tokio::process::Command::from(command);
+        command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        let mut child = command.spawn().map_err(|e| {
+            AppError::Sync(format!("Error ejecutando rsync: {}", e))
+        })?;
+
+        let stdout = tokio::spawn(read_stdout(child.stdout.take(), self.progress.clone(), itemized));
+        let stderr = tokio::spawn(read_pipe(child.stderr.take()));
+
+        let status = tokio::select! {
+            status = child.wait() => Some(status),
+            _ = shutdown::requested() => None,
+        };
+
+        let status = match status {
+            Some(status) => status?,
+            None => {
+                log::warn!("Parada solicitada: deteniendo rsync");
+                terminate_child(&child);
+                child.wait().await?;
+                return Err(AppError::Cancelled);
+            }
+        };
+
+        let (stdout, itemized) = stdout.await.unwrap_or_default();
+        let output = std::process::Output {
+            status,
+            stdout,
+            stderr: stderr.await.unwrap_or_default(),
+        };
+        Ok((output, itemized))
+    }
+
+    /// Registra las estadísticas de una ejecución de rsync (con el informe ya
+    /// resumido en `itemized` si se leyó así) y devuelve los directorios de
+    /// los que borró algo; si falló, devuelve `AppError::Rsync` con su
+    /// código de salida
+    fn process_rsync_output(
+        &self,
+        item: &str,
+        output: &std::process::Output,
+        itemized: Option<ItemizeParser>,
+        stats: &mut ItemStats,
+    ) -> Result<BTreeSet<PathBuf>> {
+        if rsync_completed(&output.status) {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let mut itemized = itemized.unwrap_or_else(|| {
+                let mut parser = ItemizeParser::new(item, None);
+                for line in stdout.lines() {
+                    parser.push_line(line);
+                }
+                parser
+            });
+            let files_transferred = itemized.files_transferred;
+            let deleted_from = std::mem::take(&mut itemized.deleted_from);
+            let mut report = itemized.finish();
+            report.oversized = parse_oversized_files(&stdout);
+            // Código 24: se hizo todo lo posible; lo desaparecido solo se avisa
+            report.vanished = parse_vanished_files(&String::from_utf8_lossy(&output.stderr));
+            if !report.vanished.is_empty() {
+                log::warn!(
+                    "{}: {} archivos desaparecieron del origen durante la sincronización: {}",
+                    item,
+                    report.vanished.len(),
+                    report.vanished.join(", ")
+                );
+            }
+            stats.record_item_report(report);
+            stats.record_files_transferred(files_transferred);
+            let bytes_transferred = parse_rsync_transferred_bytes(&stdout, self.remote_source()).unwrap_or(0);
+            stats.record_bytes_transferred(bytes_transferred);
+            let skipped_by_size = count_size_skips(&stdout);
+            if skipped_by_size > 0 {
+                log::warn!("{}: {} archivos omitidos por tamaño", item, skipped_by_size);
+                stats.files_skipped_size += skipped_by_size;
+            }
+            // Lo no transferido son los enlazados con la instantánea anterior,
+            // los que coinciden con --compare-dest o, si no, los que ya estaban al día
+            if let Some(untransferred) = parse_rsync_untransferred_files(&stdout) {
+                if self.snapshot.as_ref().is_some_and(|s| s.previous.is_some()) {
+                    stats.files_hardlinked += untransferred;
+                } else if self.args.compare_dest.is_some() {
+                    stats.files_compare_dest += untransferred;
+                } else {
+                    stats.files_unchanged += untransferred;
+                }
+            }
+            log::info!(
+                "Sincronización completada: {} archivos transferidos ({})",
+                files_transferred,
+                format_bytes(bytes_transferred)
+            );
+            Ok(deleted_from)
+        } else {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let code = output.status.code();
+            log::error!("Error en rsync ({}): {}", describe_rsync_exit(code), stderr);
+            Err(AppError::Rsync {
+                item: item.to_string(),
+                code,
+                message: stderr.lines().last().unwrap_or_default().trim().to_string(),
+            })
+        }
+    }
+
+    /// Plan resuelto para --plan-out: los mismos comandos que lanzaría
+    /// `transfer_item` (sin la cache de checksums, que depende del momento)
+    fn build_plan(&self) -> Result<ExecutionPlan> {
+        if self.args.get_mode() == SyncMode::TwoWay {
+            return Err(AppError::Validation(
+                "--plan-out no admite --two-way: las listas de archivos se deciden al ejecutar".to_string(),
+            ));
+        }
+
+        let mut items = Vec::new();
+        for item in self.resolve_items()? {
+            if self.is_missing_source(&item) {
+                log::warn!("Omitido del plan (no existe en origen): {}", item);
+                continue;
+            }
+
+            let (source, destination) = self.get_sync_paths(&item)?;
+            let commands = if self.forced_checksum_patterns(&source).is_empty() {
+                vec![self.build_rsync_command(&item, &source, &destination, None)?]
+            } else {
+                vec![
+                    self.with_checksum_pass(ChecksumPass::Fast).build_rsync_command(&item, &source, &destination, None)?,
+                    self.with_checksum_pass(ChecksumPass::Strong).build_rsync_command(&item, &source, &destination, None)?,
+                ]
+            };
+            let commands = commands.iter().map(plan::command_argv).collect::<Result<Vec<_>>>()?;
+
+            items.push(PlannedItem {
+                timeout_minutes: self.item_timeout_minutes(&item)?,
+                create_destination_parent: self.snapshot.is_some() && !self.args.dry_run,
+                exclusions: plan::exclusions_in(&commands[0]),
+                item,
+                source,
+                destination,
+                commands,
+            });
+        }
+
+        Ok(ExecutionPlan {
+            version: plan::PLAN_VERSION,
+            mode: format!("{:?}", self.args.get_mode()),
+            host: AppConfig::get_hostname(),
+            remote: self.config.remote_name.clone(),
+            items,
+        })
+    }
+
+    /// --plan-in: ejecuta los comandos del plan tal cual, sin resolver nada
+    async fn run_plan(&self, plan: &ExecutionPlan, stats: &mut SyncStats) -> Result<()> {
+        for planned in &plan.items {
+            if shutdown::is_requested() {
+                return Err(AppError::Cancelled);
+            }
+            log::info!("Sincronizando (plan): {}", planned.item);
+
+            let mut item_stats = ItemStats::default();
+            let result = self.run_planned_item(planned, &mut item_stats).await;
+            stats.merge(item_stats);
+            match result {
+                Ok(()) => stats.record_successful_item(),
+                Err(AppError::Cancelled) => return Err(AppError::Cancelled),
+                Err(e) => {
+                    log::error!("Error sincronizando {}: {}", planned.item, e);
+                    stats.record_failed_item(&planned.item, &e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn run_planned_item(&self, planned: &PlannedItem, stats: &mut ItemStats) -> Result<()> {
+        if planned.create_destination_parent {
+            if let Some(parent) = planned.destination.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+
+        let timeout_duration = Duration::from_secs(planned.timeout_minutes as u64 * 60);
+        for argv in &planned.commands {
+            let command = plan::argv_command(argv, self.config.ssh.as_ref().map(SshTarget::rsh).as_deref())?;
+            let (output, itemized) = match timeout(timeout_duration, self.execute_transfer(&planned.item, command)).await {
+                Ok(output) => output?,
+                Err(_) => {
+                    return Err(AppError::Timeout(format!(
+                        "Operación excedió el tiempo límite para: {}",
+                        planned.item
+                    )))
+                }
+            };
+            self.process_rsync_output(&planned.item, &output, itemized, stats)?;
+        }
+
+        Ok(())
+    }
+
+    /// Pasada previa (--dry-run --stats) que suma los bytes que se transferirían;
+    /// con `reverse` estima el sentido contrario (destino -> origen, para --two-way)
+    async fn estimate_total_bytes(&self, items: &[String], reverse: bool) -> Result<u64> {
+        let simulation = SyncManager::new(
+            self.config.clone(),
+            Cli {
+                dry_run: true,
+                ..self.args.clone()
+            },
+        );
+        let mut total = 0;
+
+        for item in items {
+            let (mut source, mut destination) = simulation.get_sync_paths(item)?;
+            if reverse {
+                std::mem::swap(&mut source, &mut destination);
+            }
+            if !source.exists() {
+                continue;
+            }
+
+            let command = simulation.build_rsync_command(item, &source, &destination, None)?;
+            match self.execute_rsync(command).await {
+                Ok(output) if rsync_completed(&output.status) => {
+                    total += parse_rsync_total_size(&String::from_utf8_lossy(&output.stdout)).unwrap_or(0);
+                }
+                Err(AppError::Cancelled) => return Err(AppError::Cancelled),
+                _ => log::warn!("No se pudo estimar el tamaño de {}", item),
+            }
+        }
+
+        Ok(total)
+    }
+
+    /// Pasada previa en simulación con los archivos que --delete borraría en
+    /// cada elemento (solo las líneas `*deleting` de rsync)
+    async fn pending_deletions(&self) -> Result<Vec<(String, Vec<String>)>> {
+        let timeout_duration =
+            Duration::from_secs(self.config.general.default_timeout_minutes as u64 * 60);
+        let mut deletions = Vec::new();
+
+        for item in self.resolve_items()? {
+            // Tampoco se sincronizará: no hay nada que borrar por él
+            if self.is_missing_source(&item) {
+                continue;
+            }
+            let (source, destination) = self.get_sync_paths(&item)?;
+            let command = self.build_rsync_command(&item, &source, &destination, None)?;
+            let deleted = self.deletions_in(&item, command, timeout_duration).await?;
+            deletions.push((item, deleted));
+        }
+
+        Ok(deletions)
+    }
+
+    /// Como `pending_deletions`, con los comandos de un plan guardado que
+    /// borran (--delete*), cada uno repetido con --dry-run
+    async fn planned_deletions(&self, saved_plan: &ExecutionPlan) -> Result<Vec<(String, Vec<String>)>> {
+        let mut deletions = Vec::new();
+
+        for planned in &saved_plan.items {
+            let timeout_duration = Duration::from_secs(planned.timeout_minutes as u64 * 60);
+            let mut deleted = Vec::new();
+            for argv in planned.commands.iter().filter(|argv| plan::deletes(argv)) {
+                let mut simulated = argv.clone();
+                simulated.insert(1.min(simulated.len()), "--dry-run".to_string());
+                let command = plan::argv_command(&simulated, self.config.ssh.as_ref().map(SshTarget::rsh).as_deref())?;
+                deleted.extend(self.deletions_in(&planned.item, command, timeout_duration).await?);
+            }
+            deletions.push((planned.item.clone(), deleted));
+        }
+
+        Ok(deletions)
+    }
+
+    /// Rutas `*deleting` de un rsync en simulación. Sin el recuento no se
+    /// puede aplicar el límite de borrados: cualquier fallo aborta.
+    async fn deletions_in(&self, item: &str, command: Command, timeout_duration: Duration) -> Result<Vec<String>> {
+        let output = match timeout(timeout_duration, self.execute_rsync(command)).await {
+            Ok(Ok(output)) => output,
+            Ok(Err(AppError::Cancelled)) => return Err(AppError::Cancelled),
+            Ok(Err(e)) => {
+                return Err(AppError::Sync(format!("No se pudieron obtener los borrados de {}: {}", item, e)))
+            }
+            Err(_) => {
+                return Err(AppError::Sync(format!(
+                    "No se pudieron obtener los borrados de {}: tiempo agotado",
+                    item
+                )))
+            }
+        };
+        if !rsync_completed(&output.status) {
+            return Err(AppError::Sync(format!(
+                "No se pudieron obtener los borrados de {} ({}): {}",
+                item,
+                describe_rsync_exit(output.status.code()),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(ItemReport::from_itemize_output(item, &stdout).deleted)
+    }
+
+    /// --compare-checksums: un rsync --checksum --delete --dry-run por
+    /// elemento, de local a pCloud, sin transferir nada
+    async fn compare_items(&self) -> Result<Vec<ItemDiff>> {
+        let timeout_duration =
+            Duration::from_secs(self.config.general.default_timeout_minutes as u64 * 60);
+        let mut diffs = Vec::new();
+
+        for item in self.resolve_items()? {
+            let (source, destination) = self.get_sync_paths(&item)?;
+
+            // Sin origen local todo lo del remoto está solo allí (por SSH no
+            // se puede recorrer desde aquí)
+            if self.is_missing_source(&item) {
+                if self.config.ssh.is_some() {
+                    log::warn!("Omitido de la comparación (no existe en local ni se puede listar por SSH): {}", item);
+                } else if destination.exists() {
+                    diffs.push(ItemDiff::from_remote_only(&item, &destination)?);
+                } else {
+                    log::warn!("Omitido de la comparación (no existe en ningún lado): {}", item);
+                }
+                continue;
+            }
+
+            let mut command = Command::new("rsync");
+            self.add_base_options(&mut command, false);
+            command.args(["--checksum", "--delete", "--dry-run"]);
+            self.add_checksum_choice(&mut command);
+            self.add_exclusions(&mut command, &source)?;
+            self.add_paths(&mut command, &source, &destination);
+
+            let output = match timeout(timeout_duration, self.execute_rsync(command)).await {
+                Ok(result) => result?,
+                Err(_) => {
+                    return Err(AppError::Timeout(format!(
+                        "La comparación excedió el tiempo límite para: {}",
+                        item
+                    )))
+                }
+            };
+            if !rsync_completed(&output.status) {
+                return Err(AppError::Sync(format!(
+                    "Error comparando {}: {}",
+                    item,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+
+            diffs.push(ItemDiff::from_itemize_output(&item, &String::from_utf8_lossy(&output.stdout)));
+        }
+
+        Ok(diffs)
+    }
+
+    pub fn list_items(&self) -> Result<()> {
+        let items = self.resolve_items()?;
+        let mut missing = 0;
+
+        // Un origen en un remoto SSH no se puede comprobar desde aquí
+        let remote_source = self.remote_source();
+
+        println!("Elementos a sincronizar ({}):", items.len());
+        for item in &items {
+            let (source, destination) = self.get_sync_paths(item)?;
+            if remote_source || source.exists() {
+                println!("  {} -> {}", source.display(), destination.display());
+            } else {
+                missing += 1;
+                println!("  {} -> {} [NO EXISTE]", source.display(), destination.display());
+            }
+        }
+
+        if missing > 0 {
+            println!("Elementos sin origen existente: {}", missing);
+        }
+
+        Ok(())
+    }
+
+    async fn sync_crypto(&self, stats: &mut SyncStats) -> Result<()> {
+        CryptoManager::new(self.config.clone(), self.args.clone())
+            .sync_crypto(stats)
+            .await
+    }
+
+    async fn handle_symbolic_links(&self, stats: &mut SyncStats) -> Result<()> {
+        // El manifiesto suple que pCloud no guarda enlaces; un equipo por SSH
+        // sí los guarda y rsync los transfiere como tales
+        if self.config.ssh.is_some() {
+            log::debug!("Remoto SSH: sin manifiesto de enlaces simbólicos");
+            return Ok(());
+        }
+
+        match self.args.get_mode() {
+            SyncMode::Upload | SyncMode::TwoWay => self.backup_symbolic_links(stats).await,
+            SyncMode::Download => self.restore_symbolic_links(stats).await,
+        }
+    }
+
+    fn symbolic_links(&self) -> SymbolicLinks {
+        SymbolicLinks::new(
+            self.config.local_dir(),
+            self.config.general.symlinks_file.clone(),
+            self.args.dry_run,
+            self.config.general.symlink_scan_max_depth,
+        )
+    }
+
+    async fn backup_symbolic_links(&self, stats: &mut SyncStats) -> Result<()> {
+        log::info!("Realizando backup de enlaces simbólicos");
+        let items = self.resolve_items()?;
+        self.symbolic_links()
+            .generar_archivo_enlaces(&items, None, &self.get_pcloud_dir(), stats)
+    }
+
+    async fn restore_symbolic_links(&self, stats: &mut SyncStats) -> Result<()> {
+        log::info!("Restaurando enlaces simbólicos");
+        self.symbolic_links()
+            .recrear_enlaces_desde_archivo(&self.get_pcloud_dir(), stats)
+    }
+}
+
+async fn read_pipe<R: AsyncRead + Unpin>(pipe: Option<R>) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    if let Some(mut pipe) = pipe {
+        let _ = pipe.read_to_end(&mut buffer).await;
+    }
+    buffer
+}
+
+/// Stdout de rsync línea a línea. Con `progress` sigue las líneas de
+/// --progress: el byte actual del archivo en curso y, al terminar cada archivo
+/// (xfr#/to-chk), su total. Con `itemized` las líneas de --itemize-changes se
+/// resumen ahí y, como las de progreso, no se guardan en lo devuelto
+async fn read_stdout<R: AsyncRead + Unpin>(
+    pipe: Option<R>,
+    progress: Option<Arc<ProgressTotal>>,
+    mut itemized: Option<ItemizeParser>,
+) -> (Vec<u8>, Option<ItemizeParser>) {
+    let mut buffer = Vec::new();
+    let Some(mut pipe) = pipe else { return (buffer, itemized) };
+    let mut chunk = [0u8; 8192];
+    let mut pending = Vec::new();
+    let mut current = 0;
+
+    while let Ok(read) = pipe.read(&mut chunk).await {
+        if read == 0 {
+            break;
+        }
+        pending.extend_from_slice(&chunk[..read]);
+
+        let mut line_start = 0;
+        while let Some(offset) = pending[line_start..].iter().position(|b| *b == b'\r' || *b == b'\n') {
+            let line_end = line_start + offset + 1;
+            let line = String::from_utf8_lossy(&pending[line_start..line_end - 1]);
+            let progress_bytes = parse_progress_bytes(&line);
+            if let (Some(progress), Some(bytes)) = (&progress, progress_bytes) {
+                current = bytes;
+                if line.contains("xfr#") || line.contains("to-chk=") {
+                    progress.file_done(bytes);
+                    current = 0;
+                }
+            }
+            let keep = match &mut itemized {
+                Some(parser) => progress_bytes.is_none() && !parser.push_line(&line),
+                None => true,
+            };
+            if keep {
+                buffer.extend_from_slice(&pending[line_start..line_end]);
+            }
+            line_start = line_end;
+        }
+        pending.drain(..line_start);
+
+        if let Some(progress) = &progress {
+            progress.maybe_report(current);
+            progress.sample(current);
+        }
+    }
+
+    // Última línea sin salto final
+    if !itemized.as_mut().is_some_and(|parser| parser.push_line(&String::from_utf8_lossy(&pending))) {
+        buffer.extend_from_slice(&pending);
+    }
+    (buffer, itemized)
+}
+
+/// Bytes de una línea de --progress ("  1,234,567  45%  10.5MB/s  0:00:03")
+fn parse_progress_bytes(line: &str) -> Option<u64> {
+    let mut words = line.split_whitespace();
+    let bytes = words.next()?;
+    if !words.next()?.ends_with('%') {
+        return None;
+    }
+    bytes.replace(',', "").parse().ok()
+}
+
+/// "Total transferred file size: 1,234 bytes" de --stats
+fn parse_rsync_total_size(output: &str) -> Option<u64> {
+    let line = output
+        .lines()
+        .find_map(|l| l.trim_start().strip_prefix("Total transferred file size:"))?;
+    let digits: String = line
+        .split_whitespace()
+        .next()?
+        .chars()
+        .filter(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
+}
+
+/// Envía SIGTERM al proceso hijo para que rsync termine limpiamente
+fn terminate_child(child: &tokio::process::Child) {
+    if let Some(pid) = child.id() {
+        let _ = nix::sys::signal::kill(
+            nix::unistd::Pid::from_raw(pid as i32),
+            nix::sys::signal::Signal::SIGTERM,
+        );
+    }
+}
+
+/// Si `path` está montado según el sistema; si no se puede consultar, si
+/// tiene contenido (un punto de montaje sin montar suele estar vacío)
+pub fn is_directory_mounted(path: &Path) -> bool {
+    os_mount_state(path).unwrap_or_else(|| directory_has_entries(path))
+}
+
+fn directory_has_entries(path: &Path) -> bool {
+    match std::fs::read_dir(path) {
+        Ok(mut entries) => entries.next().is_some(),
+        Err(_) => false,
+    }
+}
+
+/// Si `path` es un punto de montaje según la tabla de montajes del sistema
+/// (la misma que consulta `findmnt`); None si no se puede consultar
+#[cfg(target_os = "linux")]
+fn os_mount_state(path: &Path) -> Option<bool> {
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo").ok()?;
+    let path = path.canonicalize().ok()?;
+    Some(mountinfo_has_mount_point(&mountinfo, &path))
+}
+
+/// macOS y BSD: salida de `mount` ("<origen> on <punto> (<opciones>)")
+#[cfg(all(unix, not(target_os = "linux")))]
+fn os_mount_state(path: &Path) -> Option<bool> {
+    let output = Command::new("mount").output().ok().filter(|output| output.status.success())?;
+    let needle = format!(" on {} (", path.canonicalize().ok()?.display());
+    Some(String::from_utf8_lossy(&output.stdout).lines().any(|line| line.contains(&needle)))
+}
+
+#[cfg(not(unix))]
+fn os_mount_state(_path: &Path) -> Option<bool> {
+    None
+}
+
+/// El quinto campo de cada línea de mountinfo es el punto de montaje, con
+/// espacios, tabuladores, saltos de línea y barras invertidas en octal
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn mountinfo_has_mount_point(mountinfo: &str, path: &Path) -> bool {
+    mountinfo
+        .lines()
+        .filter_map(|line| line.split(' ').nth(4))
+        .map(|field| {
+            field
+                .replace("\\040", " ")
+                .replace("\\011", "\t")
+                .replace("\\012", "\n")
+                .replace("\\134", "\\")
+        })
+        .any(|mount_point| Path::new(&mount_point) == path)
+}
+
+/// Espacio libre (en MB) del sistema de archivos que contiene `path`
+#[cfg(unix)]
+pub fn get_available_space_mb(path: &Path) -> Result<u64> {
+    let stat = nix::sys::statvfs::statvfs(path).map_err(std::io::Error::from)?;
+    let available = stat.blocks_available() as u64 * stat.fragment_size() as u64;
+    Ok(available / (1024 * 1024)) // Convertir a MB
+}
+
+/// En Windows fs2 llama a GetDiskFreeSpaceExW: bytes libres para el usuario
+/// actual (respeta cuotas), no un valor fijo
+#[cfg(windows)]
+pub fn get_available_space_mb(path: &Path) -> Result<u64> {
+    Ok(fs2::available_space(path)? / (1024 * 1024)) // Convertir a MB
+}
+
+/// Error si origen y destino son el mismo sitio o uno contiene al otro
+fn ensure_distinct_paths(source: &Path, destination: &Path) -> Result<()> {
+    let source_real = canonicalize_lenient(source);
+    let destination_real = canonicalize_lenient(destination);
+
+    if source_real.starts_with(&destination_real) || destination_real.starts_with(&source_real) {
+        return Err(AppError::Validation(format!(
+            "Origen y destino se solapan: {} -> {}",
+            source_real.display(),
+            destination_real.display()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Como `canonicalize`, pero admite rutas que aún no existen: resuelve el
+/// ancestro existente más cercano y le añade el resto
+fn canonicalize_lenient(path: &Path) -> PathBuf {
+    let path = absolute_path(path);
+    let mut existing = path.as_path();
+    let mut rest = Vec::new();
+
+    loop {
+        if let Ok(real) = existing.canonicalize() {
+            return rest.iter().rev().fold(real, |acc, part| acc.join(part));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => return path.clone(),
+        }
+    }
+}
+
+/// Elimina los directorios de `destination` que quedaron vacíos porque rsync
+/// borró su contenido (`deleted_from`, relativos al destino) y, hacia arriba,
+/// los padres que se vacían con ellos. Devuelve cuántos se borraron. Nunca
+/// se eliminan la raíz, lo que ya estaba vacío ni lo que sigue siendo un
+/// directorio en `source`.
+fn prune_emptied_dirs(source: &Path, destination: &Path, deleted_from: &BTreeSet<PathBuf>) -> std::io::Result<u32> {
+    // Los más profundos primero, así sus padres ya están vacíos al llegar
+    let mut dirs: Vec<&Path> = deleted_from
+        .iter()
+        .filter(|dir| dir.components().all(|c| matches!(c, Component::Normal(_))))
+        .map(PathBuf::as_path)
+        .collect();
+    dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+
+    let mut pruned = 0;
+    for dir in dirs {
+        let mut relative = Some(dir);
+        while let Some(dir) = relative.filter(|dir| !dir.as_os_str().is_empty()) {
+            let path = destination.join(dir);
+            let is_dir = path.symlink_metadata().is_ok_and(|metadata| metadata.is_dir());
+            if !is_dir || source.join(dir).is_dir() || std::fs::read_dir(&path)?.next().is_some() {
+                break;
+            }
+            std::fs::remove_dir(&path)?;
+            pruned += 1;
+            relative = dir.parent();
+        }
+    }
+    Ok(pruned)
+}
+
+/// Un directorio se pasa con barra final para sincronizar su contenido y no
+/// crear `destino/elemento/elemento`
+pub fn rsync_paths(source: &Path, destination: &Path) -> [std::ffi::OsString; 2] {
+    if source.is_dir() {
+        [with_slash(source.into()), with_slash(destination.into())]
+    } else {
+        [source.into(), destination.into()]
+    }
+}
+
+/// Barra final: rsync copia entonces el contenido y no el directorio
+fn with_slash(mut path: std::ffi::OsString) -> std::ffi::OsString {
+    if !path.to_string_lossy().ends_with('/') {
+        path.push("/");
+    }
+    path
+}
+
+/// Con un remoto SSH no se puede mirar si el origen remoto es un directorio:
+/// al bajar manda el destino local. Si ya es un directorio se copia con
+/// barra final en los dos lados, como en local; si no existe (o es un
+/// archivo), sin barras, y rsync crea el destino con el contenido del origen
+fn ssh_rsync_paths(ssh: &SshTarget, mode: SyncMode, source: &Path, destination: &Path) -> [std::ffi::OsString; 2] {
+    match mode {
+        SyncMode::Download => {
+            if destination.is_dir() {
+                [with_slash(ssh.remote_path(source)), with_slash(destination.into())]
+            } else {
+                [ssh.remote_path(source), destination.into()]
+            }
+        }
+        SyncMode::Upload | SyncMode::TwoWay => {
+            let [source, destination] = rsync_paths(source, destination);
+            [source, ssh.remote_path(Path::new(&destination))]
+        }
+    }
+}
+
+/// `ssh` sin interacción (sin pedir contraseña ni confirmar la clave del
+/// equipo) que solo ejecuta `true`: comprueba red y autenticación
+fn ssh_probe_args(ssh: &SshTarget) -> Vec<String> {
+    let mut args: Vec<String> = ["-o", "BatchMode=yes", "-o", "ConnectTimeout=10"].map(String::from).to_vec();
+    if let Some(port) = ssh.port {
+        args.extend(["-p".to_string(), port.to_string()]);
+    }
+    args.extend([ssh.destination(), "true".to_string()]);
+    args
+}
+
+/// Un elemento es relativo a `local_dir` y al directorio de pCloud: una ruta
+/// absoluta reemplazaría la base en `join` y `..` saldría de ella
+fn validate_item(item: &str) -> Result<()> {
+    use std::path::Component;
+
+    let path = Path::new(item);
+    let escapes = path
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir));
+    if item.is_empty() || escapes {
+        return Err(AppError::PathTraversal(path.to_path_buf()));
+    }
+    Ok(())
+}
+
+fn is_skipped(item: &str, skip_items: &[String]) -> bool {
+    let item = item.trim_end_matches('/');
+    skip_items.iter().any(|skip| skip.trim_end_matches('/') == item)
+}
+
+/// Si `options` incluye la opción larga `long` o la corta `short`, sola o
+/// agrupada con otras (`-rtvi`)
+pub fn has_rsync_option(options: &[String], long: &str, short: char) -> bool {
+    options.iter().any(|option| {
+        option == long
+            || option
+                .strip_prefix('-')
+                .is_some_and(|flags| !flags.starts_with('-') && flags.contains(short))
+    })
+}
+
+/// Indica si una línea de --itemize-changes supone una transferencia,
+/// creación o borrado (las que empiezan por '.' solo cambian atributos)
+fn is_itemized_change(line: &str) -> bool {
+    if line.starts_with("*deleting") {
+        return true;
+    }
+
+    let mut chars = line.chars();
+    matches!(
+        (chars.next(), chars.next()),
+        (Some('<' | '>' | 'c' | 'h'), Some('f' | 'd' | 'L' | 'D' | 'S'))
+    )
+}
+
+/// Obtiene host y puerto de una URL ("https://host[:puerto]/ruta")
+fn connectivity_target(url: &str) -> Option<(String, u16)> {
+    let (scheme, rest) = url.split_once("://").unwrap_or(("https", url));
+    let authority = rest.split('/').next().filter(|a| !a.is_empty())?;
+    let default_port = if scheme.eq_ignore_ascii_case("http") { 80 } else { 443 };
+
+    match authority.rsplit_once(':') {
+        Some((host, port)) => Some((host.to_string(), port.parse().ok()?)),
+        None => Some((authority.to_string(), default_port)),
+    }
+}
+
+/// Extrae los bytes transferidos del resumen final de rsync ("sent 1,234
+/// bytes  received 56 bytes  ..."): los enviados o, si el que envía es el
+/// otro extremo (`received`), los recibidos
+fn parse_rsync_transferred_bytes(output: &str, received: bool) -> Option<u64> {
+    let line = output.lines().rev().find(|l| l.trim_start().starts_with("sent "))?;
+    let label = if received { "received" } else { "sent" };
+    let mut words = line.split_whitespace().skip_while(|word| *word != label);
+    words.next()?;
+    let digits: String = words.next()?.chars().filter(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+/// Archivos que rsync dejó fuera por --max-size/--min-size (--info=skip1)
+fn count_size_skips(output: &str) -> u32 {
+    output
+        .lines()
+        .filter(|line| line.ends_with("is over max-size") || line.ends_with("is under min-size"))
+        .count() as u32
+}
+
+/// Rutas que rsync dejó fuera por superar --max-size
+fn parse_oversized_files(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| line.strip_suffix(" is over max-size"))
+        .map(String::from)
+        .collect()
+}
+
+lazy_static! {
+    /// Algoritmos de checksum del rsync instalado; `None` si no se pudo ejecutar
+    static ref RSYNC_CHECKSUMS: Option<Vec<String>> = Command::new("rsync")
+        .arg("--version")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| parse_rsync_checksum_list(&String::from_utf8_lossy(&output.stdout)));
+}
+
+/// Algoritmos de la sección "Checksum list:" de `rsync --version` (3.2+),
+/// sin los comentarios entre paréntesis; vacío en un rsync sin --checksum-choice
+fn parse_rsync_checksum_list(version: &str) -> Vec<String> {
+    version
+        .lines()
+        .skip_while(|line| line.trim() != "Checksum list:")
+        .skip(1)
+        .take_while(|line| line.starts_with(char::is_whitespace))
+        .flat_map(str::split_whitespace)
+        .filter(|word| !word.starts_with('('))
+        .map(String::from)
+        .collect()
+}
+
+/// `choice` si rsync admite cada algoritmo (uno, o dos separados por coma:
+/// transferencia y --checksum); si no, se avisa y se omite. Sin saber qué
+/// admite (rsync no ejecutable) se pasa tal cual y que rsync decida.
+fn supported_checksum_choice<'a>(choice: &'a str, available: Option<&[String]>) -> Option<&'a str> {
+    let Some(available) = available else { return Some(choice) };
+    let unsupported: Vec<&str> = choice
+        .split(',')
+        .filter(|algorithm| *algorithm != "auto" && !available.iter().any(|a| a == algorithm))
+        .collect();
+    if unsupported.is_empty() {
+        return Some(choice);
+    }
+
+    if available.is_empty() {
+        log::warn!("El rsync instalado no admite --checksum-choice (requiere 3.2); se usa su algoritmo por defecto");
+    } else {
+        log::warn!(
+            "rsync no admite el checksum {} (disponibles: {}); se usa su algoritmo por defecto",
+            unsupported.join(", "),
+            available.join(" ")
+        );
+    }
+    None
+}
+
+/// rsync termina con 24 si algún archivo del origen desapareció entre el
+/// recorrido y la copia (habitual en un home en uso): el resto se transfirió
+const RSYNC_VANISHED: i32 = 24;
+
+fn rsync_completed(status: &std::process::ExitStatus) -> bool {
+    status.success() || status.code() == Some(RSYNC_VANISHED)
+}
+
+/// Rutas de las líneas `file has vanished: "<ruta>"` del stderr de rsync
+/// (con o sin el prefijo `rsync: [sender] `)
+fn parse_vanished_files(stderr: &str) -> Vec<String> {
+    stderr
+        .lines()
+        .filter_map(|line| line.split_once("file has vanished: ").map(|(_, path)| path.trim()))
+        .map(|path| path.strip_prefix('"').and_then(|p| p.strip_suffix('"')).unwrap_or(path).to_string())
+        .collect()
+}
+
+/// Archivos regulares no transferidos según --stats: con --link-dest son los
+/// enlazados y con --compare-dest, los que coinciden con la referencia (más
+/// los que ya estuvieran al día en el destino)
+fn parse_rsync_untransferred_files(output: &str) -> Option<u32> {
+    let stat = |prefix: &str| -> Option<&str> {
+        output.lines().find_map(|l| l.trim_start().strip_prefix(prefix))
+    };
+    let number = |text: &str| -> Option<u32> {
+        let digits: String = text
+            .trim_start()
+            .chars()
+            .take_while(|c| c.is_ascii_digit() || *c == ',' || *c == '.')
+            .filter(|c| c.is_ascii_digit())
+            .collect();
+        digits.parse().ok()
+    };
+
+    let files = stat("Number of files:")?;
+    let regular = number(files.split_once("reg:")?.1)?;
+    let transferred = number(stat("Number of regular files transferred:")?)?;
+    Some(regular.saturating_sub(transferred))
+}
+
+/// Instantánea más reciente (por nombre) dentro de `root`
+fn latest_snapshot(root: &Path) -> Option<PathBuf> {
+    std::fs::read_dir(root)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            chrono::NaiveDateTime::parse_from_str(&name, SNAPSHOT_FORMAT).ok()?;
+            Some((name, entry.path()))
+        })
+        .max_by(|a, b| a.0.cmp(&b.0))
+        .map(|(_, path)| path)
+}
+
+// Funciones públicas para uso desde main
+pub fn show_banner(args: &Cli, config: &AppConfig) {
+    println!("==========================================");
+    println!("Sincronización Bidireccional - syncb");
+    println!("Modo: {:?}", args.get_mode());
+    println!("Host: {}", AppConfig::get_hostname());  // Ahora es pública
+    if let Some(remote) = &config.remote_name {
+        let location = match &config.ssh {
+            Some(ssh) => ssh.remote_path(&config.general.pcloud_mount_point),
+            None => config.general.pcloud_mount_point.clone().into_os_string(),
+        };
+        println!("Remoto: {} ({})", remote, location.to_string_lossy());
+    }
+    println!("==========================================");
+}
+
+/// Programas externos que necesita esta configuración: rsync siempre, ssh
+/// con un remoto SSH y curl solo si se notifica por webhook o correo
+fn required_dependencies(config: &AppConfig) -> Vec<&'static str> {
+    let mut programs = vec!["rsync"];
+    if config.ssh.is_some() {
+        programs.push("ssh");
+    }
+    if matches!(
+        config.notifications.backend,
+        NotificationBackend::Webhook | NotificationBackend::Email
+    ) {
+        programs.push("curl");
+    }
+    programs
+}
+
+/// Orden sugerida para instalar un programa en esta plataforma
+fn install_hint(program: &str) -> String {
+    if cfg!(target_os = "macos") {
+        format!("brew install {}", program)
+    } else if cfg!(target_os = "windows") {
+        format!("winget install {} (o desde WSL/Cygwin)", program)
+    } else if cfg!(target_os = "freebsd") {
+        format!("pkg install {}", program)
+    } else {
+        format!("sudo apt install {0} / sudo dnf install {0} / sudo pacman -S {0}", program)
+    }
+}
+
+pub fn verify_dependencies(config: &AppConfig) -> Result<()> {
+    for program in required_dependencies(config) {
+        // OpenSSH no admite --version
+        let status = Command::new(program)
+            .arg(if program == "ssh" { "-V" } else { "--version" })
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+
+        let problem = match status {
+            Ok(status) if status.success() => continue,
+            Ok(_) => "no funciona correctamente",
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => "no está instalado",
+            Err(_) => "no se puede ejecutar",
+        };
+        return Err(AppError::DependencyMissing(format!(
+            "{} {}; instálelo con: {}",
+            program,
+            problem,
+            install_hint(program)
+        )));
+    }
+
+    log::info!("Dependencias verificadas: OK");
+    Ok(())
+}
+
+async fn verify_ssh_reachable(ssh: &SshTarget) -> Result<()> {
+    let mut command = tokio::process::Command::new("ssh");
+    command.args(ssh_probe_args(ssh)).stdin(Stdio::null()).kill_on_drop(true);
+
+    match timeout(Duration::from_secs(30), command.output()).await {
+        Ok(Ok(output)) if output.status.success() => {
+            log::info!("Verificación del remoto SSH {}: OK", ssh.destination());
+            Ok(())
+        }
+        Ok(Ok(output)) => Err(AppError::Network(format!(
+            "No se pudo conectar por SSH con {}: {}",
+            ssh.destination(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))),
+        Ok(Err(e)) => Err(e.into()),
+        Err(_) => Err(AppError::Network(format!("Tiempo agotado conectando por SSH con {}", ssh.destination()))),
+    }
+}
+
+pub async fn verify_preconditions(args: &Cli, config: &AppConfig) -> Result<()> {
+    let temp_manager = SyncManager::new(config.clone(), args.clone());
+    temp_manager.verify_preconditions().await
+}
+
+/// Respuesta a la pregunta de --confirm-each
+#[derive(Debug, PartialEq)]
+enum ItemAnswer {
+    Sync,
+    Skip,
+    Abort,
+}
+
+fn parse_item_answer(input: &str) -> Option<ItemAnswer> {
+    match input.trim().to_lowercase().as_str() {
+        "s" | "si" | "sí" => Some(ItemAnswer::Sync),
+        "o" | "omitir" => Some(ItemAnswer::Skip),
+        "a" | "abortar" => Some(ItemAnswer::Abort),
+        _ => None,
+    }
+}
+
+/// --confirm-each necesita responder por teclado
+pub fn ensure_interactive() -> Result<()> {
+    use std::io::IsTerminal;
+
+    if std::io::stdin().is_terminal() {
+        Ok(())
+    } else {
+        Err(AppError::Validation(
+            "--confirm-each requiere una terminal interactiva en la entrada estándar".to_string(),
+        ))
+    }
+}
+
+pub fn confirm_execution() -> Result<()> {
+    println!("¿Desea continuar con la sincronización? [s/N]: ");
+
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+
+    if input.trim().eq_ignore_ascii_case("s") {
+        Ok(())
+    } else {
+        Err(AppError::Validation("Operación cancelada por el usuario".to_string()))
+    }
+}
+
+/// Con --delete, cuenta los borrados en simulación: sin --yes pide confirmación
+/// si superan `delete_confirm_threshold`; con --yes aborta si superan `delete_abort_threshold`
+pub async fn guard_deletions(args: &Cli, config: &AppConfig) -> Result<()> {
+    let simulation = Cli {
+        dry_run: true,
+        ..args.clone()
+    };
+    let manager = SyncManager::new(config.clone(), simulation);
+    let deletions = manager.pending_deletions().await?;
+    confirm_deletions(args, config, &deletions)
+}
+
+/// `guard_deletions` para --plan-in: los comandos del plan pueden borrar
+/// aunque esta ejecución no lleve --delete
+pub async fn guard_plan_deletions(args: &Cli, config: &AppConfig, saved_plan: &ExecutionPlan) -> Result<()> {
+    if !saved_plan.items.iter().flat_map(|planned| &planned.commands).any(|argv| plan::deletes(argv)) {
+        return Ok(());
+    }
+
+    let simulation = Cli {
+        dry_run: true,
+        ..args.clone()
+    };
+    let manager = SyncManager::new(config.clone(), simulation);
+    let deletions = manager.planned_deletions(saved_plan).await?;
+    confirm_deletions(args, config, &deletions)
+}
+
+fn confirm_deletions(args: &Cli, config: &AppConfig, deletions: &[(String, Vec<String>)]) -> Result<()> {
+    let deletions: usize = deletions.iter().map(|(_, paths)| paths.len()).sum();
+    log::info!("Borrados previstos por --delete: {}", deletions);
+
+    if args.yes {
+        if deletions > config.general.delete_abort_threshold {
+            return Err(AppError::Validation(format!(
+                "--delete borraría {} archivos (límite {}); abortando",
+                deletions, config.general.delete_abort_threshold
+            )));
+        }
+    } else if deletions > config.general.delete_confirm_threshold {
+        println!("ATENCIÓN: --delete borrará {} archivos en el destino.", deletions);
+        println!("¿Confirma el borrado? [s/N]: ");
+
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        if !input.trim().eq_ignore_ascii_case("s") {
+            return Err(AppError::Validation("Borrado cancelado por el usuario".to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+/// --dry-run-delete-only: lista lo que --delete borraría en el destino, sin
+/// transferir nada. Devuelve el número total de borrados.
+pub async fn audit_deletions(args: &Cli, config: &AppConfig) -> Result<usize> {
+    let deletions = preview_deletions(args, config).await?;
+    Ok(display_deletions(&deletions))
+}
+
+/// Argumentos de la pasada que calcula los borrados: siempre --delete y
+/// siempre --dry-run, sea cual sea el --delete de la ejecución real
+fn deletion_simulation(args: &Cli) -> Cli {
+    Cli {
+        delete: true,
+        dry_run: true,
+        ..args.clone()
+    }
+}
+
+/// Borrados que haría --delete en cada elemento, con un gestor propio en
+/// simulación (--preview-deletions y --dry-run-delete-only)
+pub async fn preview_deletions(args: &Cli, config: &AppConfig) -> Result<Vec<(String, Vec<String>)>> {
+    SyncManager::new(config.clone(), deletion_simulation(args))
+        .pending_deletions()
+        .await
+}
+
+/// Lista los borrados por elemento y devuelve el total
+pub fn display_deletions(deletions: &[(String, Vec<String>)]) -> usize {
+    let total = deletions.iter().map(|(_, paths)| paths.len()).sum();
+
+    println!("Archivos que --delete borraría en el destino:");
+    for (item, paths) in deletions.iter().filter(|(_, paths)| !paths.is_empty()) {
+        println!("  {} ({}):", item, paths.len());
+        for path in paths {
+            println!("      - {}", path);
+        }
+    }
+    println!("Total de borrados: {}", total);
+
+    total
+}
+
+/// --compare-checksums: siempre en el sentido local -> pCloud (lo que se
+/// crearía solo está en local, lo que se borraría solo en el remoto)
+pub async fn compare_checksums(args: &Cli, config: &AppConfig) -> Result<Vec<ItemDiff>> {
+    let comparison = Cli {
+        mode: Some(SyncMode::Upload),
+        dry_run: true,
+        ..args.clone()
+    };
+    let manager = SyncManager::new(config.clone(), comparison);
+    match &config.ssh {
+        Some(ssh) => verify_ssh_reachable(ssh).await?,
+        None => manager.verify_pcloud_mounted().await?,
+    }
+    manager.compare_items().await
+}
+
+pub fn list_only(args: &Cli, config: &AppConfig) -> Result<()> {
+    let manager = SyncManager::new(config.clone(), args.clone());
+    manager.list_items()
+}
+
+/// --repair-symlinks: recrea los enlaces del manifiesto de pCloud sin
+/// transferir archivos (en --dry-run solo informa)
+pub async fn repair_symlinks(args: &Cli, config: &AppConfig) -> Result<SyncStats> {
+    let manager = SyncManager::new(config.clone(), args.clone());
+    let mounted = manager.verify_pcloud_mounted().await;
+    manager.tolerate_in_dry_run(mounted)?;
+
+    let mut stats = SyncStats::new();
+    manager.restore_symbolic_links(&mut stats).await?;
+    Ok(stats)
+}
+
+/// --watch-fs: vigila los orígenes locales de los elementos resueltos,
+/// ignorando las exclusiones del host y de --exclude
+pub fn watch_local_changes(args: &Cli, config: &AppConfig) -> Result<FsWatcher> {
+    let manager = SyncManager::new(config.clone(), args.clone());
+    let local_dir = config.local_dir();
+    let targets: Vec<PathBuf> = manager.resolve_items()?.iter().map(|item| local_dir.join(item)).collect();
+
+    let mut exclusions = config.get_current_host_config()?.exclusions.clone();
+    exclusions.extend(args.exclude.iter().cloned());
+    FsWatcher::new(&targets, ChangeFilter::new(&local_dir, &config.general.partial_dir, &exclusions))
+}
+
+/// --plan-out: guarda el plan resuelto sin ejecutar nada
+pub fn write_plan(args: &Cli, config: &AppConfig, path: &Path) -> Result<()> {
+    let mut manager = SyncManager::new(config.clone(), args.clone());
+    manager.snapshot = manager.plan_snapshot()?;
+    let plan = manager.build_plan()?;
+    plan.save(path)?;
+    println!("Plan guardado en {} ({} elementos)", path.display(), plan.items.len());
+    Ok(())
+}
+
+/// --plan-in: como `perform_sync`, pero con los comandos de un plan guardado
+pub async fn execute_plan(args: &Cli, config: &AppConfig, plan: &ExecutionPlan) -> (SyncStats, Result<()>) {
+    let mut stats = SyncStats { dry_run: args.dry_run, ..SyncStats::new() };
+    let manager = SyncManager::new(config.clone(), args.clone());
+    log::info!(
+        "Ejecutando plan ({}, host {}, {} elementos)",
+        plan.mode,
+        plan.host,
+        plan.items.len()
+    );
+    let result = manager.run_plan(plan, &mut stats).await;
+    (stats, result)
+}
+
+/// Ejecuta la sincronización completa. Las estadísticas se devuelven
+/// siempre, también si falla, para poder mostrar el resumen
+pub async fn perform_sync(args: &Cli, config: &AppConfig) -> (SyncStats, Result<()>) {
+    let mut stats = SyncStats { dry_run: args.dry_run, ..SyncStats::new() };
+    let mut manager = SyncManager::new(config.clone(), args.clone());
+
+    let result = match manager.plan_snapshot() {
+        Ok(snapshot) => {
+            manager.snapshot = snapshot;
+            manager.perform_sync(&mut stats).await
+        }
+        Err(e) => Err(e),
+    };
+
+    (stats, result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{parse_size, DeletePolicy};
+    use crate::test_support::test_config;
+    use std::ffi::OsStr;
+    use tempfile::TempDir;
+
+    fn rsync_args(config: AppConfig, args: Cli) -> Vec<String> {
+        let manager = SyncManager::new(config, args);
+        let command = manager
+            .build_rsync_command("Documentos", Path::new("/origen"), Path::new("/destino"), None)
+            .unwrap();
+        command
+            .get_args()
+            .map(OsStr::to_string_lossy)
+            .map(|arg| arg.to_string())
+            .collect()
+    }
+
+    fn follows(args: &[String], flag: &str, value: &str) -> bool {
+        args.windows(2).any(|pair| pair[0] == flag && pair[1] == value)
+    }
+
+    #[test]
+    fn test_delete_excluded_is_only_passed_with_delete() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(temp_dir.path());
+
+        let rsync = rsync_args(
+            config.clone(),
+            Cli { subir: true, delete: true, delete_excluded: true, ..Default::default() },
+        );
+        assert!(rsync.contains(&"--delete-delay".to_string()));
+        assert!(rsync.contains(&"--delete-excluded".to_string()));
+
+        let rsync = rsync_args(config, Cli { subir: true, delete: true, ..Default::default() });
+        assert!(!rsync.contains(&"--delete-excluded".to_string()));
+    }
+
+    #[test]
+    fn test_delete_policy_cli_overrides_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+        config.general.delete_policy = DeletePolicy::After;
+
+        let rsync = rsync_args(config.clone(), Cli { subir: true, delete: true, ..Default::default() });
+        assert!(rsync.contains(&"--delete-after".to_string()));
+        assert!(!rsync.contains(&"--delete-delay".to_string()));
+
+        let args = Cli { subir: true, delete: true, delete_policy: Some(DeletePolicy::Before), ..Default::default() };
+        assert!(rsync_args(config.clone(), args).contains(&"--delete-before".to_string()));
+
+        // Sin --delete no se borra, diga lo que diga la política
+        let rsync = rsync_args(config, Cli { subir: true, ..Default::default() });
+        assert!(!rsync.iter().any(|arg| arg.starts_with("--delete")));
+    }
+
+    #[test]
+    fn test_items_cannot_escape_their_roots() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = SyncManager::new(test_config(temp_dir.path()), Cli { subir: true, ..Default::default() });
+
+        for item in ["../escape", "Documentos/../../escape", "/abs/path", ""] {
+            assert!(
+                matches!(manager.get_sync_paths(item), Err(AppError::PathTraversal(_))),
+                "{} debería rechazarse",
+                item
+            );
+        }
+
+        let (source, destination) = manager.get_sync_paths("Documentos/personal").unwrap();
+        assert_eq!(source, temp_dir.path().join("Documentos/personal"));
+        assert!(destination.ends_with("Backup_Comun/Documentos/personal"));
+
+        let mut config = test_config(temp_dir.path());
+        config.hosts.get_mut("default").unwrap().sync_items = vec!["../fuera".to_string()];
+        let manager = SyncManager::new(config, Cli { subir: true, ..Default::default() });
+        assert!(matches!(manager.resolve_items(), Err(AppError::PathTraversal(_))));
+    }
+
+    #[tokio::test]
+    async fn test_repair_symlinks_without_transfers() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+        // Un punto de montaje real: un directorio temporal no lo es
+        config.general.pcloud_mount_point = PathBuf::from("/");
+        let backup = &config.general.pcloud_backup_comun;
+        std::fs::create_dir_all(backup).unwrap();
+        std::fs::write(
+            backup.join(&config.general.symlinks_file),
+            "Documentos/actual\t$HOME/Documentos/2026\n",
+        )
+        .unwrap();
+
+        let link = temp_dir.path().join("Documentos/actual");
+        let args = Cli { dry_run: true, repair_symlinks: true, ..Default::default() };
+        let stats = repair_symlinks(&args, &config).await.unwrap();
+        assert_eq!(stats.symbolic_links_created, 1);
+        assert!(std::fs::symlink_metadata(&link).is_err());
+
+        let args = Cli { repair_symlinks: true, ..Default::default() };
+        let stats = repair_symlinks(&args, &config).await.unwrap();
+        assert_eq!(stats.symbolic_links_created, 1);
+        assert!(std::fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+    }
+
+    #[tokio::test]
+    async fn test_compare_reports_items_only_on_the_remote() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(temp_dir.path());
+        let remote = config.general.pcloud_backup_comun.join("Documentos");
+        std::fs::create_dir_all(&remote).unwrap();
+        std::fs::write(remote.join("solo remoto.txt"), "x").unwrap();
+
+        let args = Cli { mode: Some(SyncMode::Upload), ..Default::default() };
+        let diffs = SyncManager::new(config, args).compare_items().await.unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].only_remote, ["solo remoto.txt"]);
+        assert_eq!(diffs[0].total(), 1);
+    }
+
+    #[test]
+    fn test_compare_dest_points_at_the_item_in_the_reference() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(temp_dir.path());
+        let manager = SyncManager::new(
+            config,
+            Cli {
+                bajar: true,
+                compare_dest: Some(PathBuf::from("/respaldo")),
+                ..Default::default()
+            },
+        );
+        let args_for = |item: &str, source: &Path| -> Vec<String> {
+            manager
+                .build_rsync_command(item, source, Path::new("/destino"), None)
+                .unwrap()
+                .get_args()
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect()
+        };
+
+        let dir_args = args_for("Documentos", temp_dir.path());
+        assert!(dir_args.contains(&"--compare-dest=/respaldo/Documentos".to_string()));
+        assert!(dir_args.contains(&"--stats".to_string()));
+        // Un archivo suelto se busca en el directorio que lo contiene
+        let file_args = args_for("Documentos/nota.txt", &temp_dir.path().join("nota.txt"));
+        assert!(file_args.contains(&"--compare-dest=/respaldo/Documentos".to_string()));
+
+        let output = "Number of files: 10 (reg: 8, dir: 2)\nNumber of regular files transferred: 3\n";
+        assert_eq!(parse_rsync_untransferred_files(output), Some(5));
+    }
+
+    #[test]
+    fn test_item_options_are_appended_for_their_item() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+        config
+            .hosts
+            .get_mut("default")
+            .unwrap()
+            .item_options
+            .insert("Documentos".to_string(), vec!["--perms".to_string(), "--size-only".to_string()]);
+
+        let args = rsync_args(config.clone(), Cli { subir: true, ..Default::default() });
+        assert!(args.contains(&"--perms".to_string()) && args.contains(&"--size-only".to_string()));
+
+        let manager = SyncManager::new(config, Cli { subir: true, ..Default::default() });
+        let other: Vec<String> = manager
+            .build_rsync_command("Fotos", Path::new("/origen"), Path::new("/destino"), None)
+            .unwrap()
+            .get_args()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect();
+        assert!(!other.contains(&"--size-only".to_string()));
+    }
+
+    #[test]
+    fn test_available_space_on_tmpdir() {
+        let temp_dir = TempDir::new().unwrap();
+        let available = get_available_space_mb(temp_dir.path()).unwrap();
+        assert!(available > 0);
+        // Lo libre nunca supera el tamaño del volumen
+        assert!(available <= fs2::total_space(temp_dir.path()).unwrap() / (1024 * 1024));
+    }
+
+    #[test]
+    fn test_exclude_from_uses_expanded_absolute_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+        let config_filter = temp_dir.path().join("config.filter");
+        config.general.exclude_from = Some(config_filter.clone());
+
+        let args = Cli {
+            subir: true,
+            exclude_from: Some(PathBuf::from("~/cli.filter")),
+            ..Default::default()
+        };
+        let rsync = rsync_args(config, args);
+
+        let home = dirs::home_dir().unwrap();
+        assert!(follows(&rsync, "--exclude-from", &config_filter.to_string_lossy()));
+        assert!(follows(&rsync, "--exclude-from", &home.join("cli.filter").to_string_lossy()));
+        // Las exclusiones en línea se siguen pasando
+        assert!(follows(&rsync, "--exclude", "*.tmp"));
+    }
+
+    #[test]
+    fn test_skip_item_removes_entries_from_default_set() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+        config.hosts.get_mut("default").unwrap().sync_items =
+            vec!["Documentos".to_string(), "Videos/".to_string(), ".bashrc".to_string()];
+
+        let args = Cli {
+            subir: true,
+            skip_item: vec!["Videos".to_string(), "NoExiste".to_string()],
+            ..Default::default()
+        };
+        let manager = SyncManager::new(config, args);
+
+        assert_eq!(manager.resolve_items().unwrap(), vec!["Documentos", ".bashrc"]);
+        assert!(manager.log_skipped_items().is_ok());
+    }
+
+    #[test]
+    fn test_latest_snapshot_and_hardlink_count() {
+        let temp_dir = TempDir::new().unwrap();
+        for name in ["2025-01-01_120000", "2025-03-01_080000", "no-es-instantanea"] {
+            std::fs::create_dir(temp_dir.path().join(name)).unwrap();
+        }
+        assert_eq!(
+            latest_snapshot(temp_dir.path()),
+            Some(temp_dir.path().join("2025-03-01_080000"))
+        );
+
+        let output = "Number of files: 1,250 (reg: 1,200, dir: 50)\n\
+                      Number of created files: 1,250 (reg: 1,200, dir: 50)\n\
+                      Number of regular files transferred: 15\n";
+        assert_eq!(parse_rsync_untransferred_files(output), Some(1_185));
+    }
+
+    #[test]
+    fn test_item_timeout_precedence() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+        config
+            .hosts
+            .get_mut("default")
+            .unwrap()
+            .item_timeouts
+            .insert("Fotos".to_string(), 120);
+
+        let args = Cli {
+            subir: true,
+            timeout: Some(5),
+            ..Default::default()
+        };
+        let manager = SyncManager::new(config.clone(), args);
+        assert_eq!(manager.item_timeout_minutes("Fotos").unwrap(), 120);
+        assert_eq!(manager.item_timeout_minutes("Documentos").unwrap(), 5);
+
+        let manager = SyncManager::new(config, Cli { subir: true, ..Default::default() });
+        assert_eq!(manager.item_timeout_minutes("Documentos").unwrap(), 30);
+    }
+
+    #[test]
+    fn test_directory_paths_get_trailing_slash() {
+        let temp_dir = TempDir::new().unwrap();
+        let destination = temp_dir.path().join("destino");
+
+        let [source_arg, destination_arg] = rsync_paths(temp_dir.path(), &destination);
+        assert!(source_arg.to_string_lossy().ends_with('/'));
+        assert!(destination_arg.to_string_lossy().ends_with("destino/"));
+
+        let file = temp_dir.path().join("archivo.txt");
+        std::fs::write(&file, "x").unwrap();
+        let [source_arg, _] = rsync_paths(&file, &destination);
+        assert_eq!(source_arg, file.as_os_str());
+    }
+
+    #[test]
+    fn test_prune_emptied_dirs_only_removes_what_rsync_emptied() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("origen");
+        let root = temp_dir.path().join("destino");
+        std::fs::create_dir_all(root.join("a/b/c")).unwrap();
+        std::fs::create_dir_all(root.join("oculto")).unwrap();
+        std::fs::write(root.join("oculto/.keep"), "").unwrap();
+        std::fs::create_dir_all(root.join("vacio_previo")).unwrap();
+        std::fs::create_dir_all(source.join("vacio_en_origen")).unwrap();
+        std::fs::create_dir_all(root.join("vacio_en_origen")).unwrap();
+
+        let deleted_from = BTreeSet::from(["a/b/c".into(), "oculto".into(), "vacio_en_origen".into(), "../fuera".into()]);
+        assert_eq!(prune_emptied_dirs(&source, &root, &deleted_from).unwrap(), 3);
+        assert!(!root.join("a").exists());
+        assert!(root.join("oculto/.keep").exists());
+        assert!(root.join("vacio_previo").is_dir());
+        assert!(root.join("vacio_en_origen").is_dir());
+        assert!(root.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_prune_emptied_dirs_on_download() {
+        use std::os::unix::process::ExitStatusExt;
+
+        // Con --bajar el destino es local_dir: sus directorios vacíos de antes
+        // de la ejecución no son de rsync y se conservan
+        let temp_dir = TempDir::new().unwrap();
+        let args = Cli { bajar: true, prune_empty_dirs: true, ..Default::default() };
+        let manager = SyncManager::new(test_config(temp_dir.path()), args);
+        let (source, destination) = manager.get_sync_paths("Documentos").unwrap();
+        assert!(destination.starts_with(temp_dir.path()) && !destination.starts_with(&source));
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::create_dir_all(destination.join("proyecto/viejo")).unwrap();
+        std::fs::create_dir_all(destination.join("mio_vacio")).unwrap();
+
+        let output = std::process::Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: b"*deleting   proyecto/viejo/nota.txt
+".to_vec(),
+            stderr: Vec::new(),
+        };
+        let deleted_from = manager
+            .process_rsync_output("Documentos", &output, None, &mut ItemStats::default())
+            .unwrap();
+        assert_eq!(deleted_from, BTreeSet::from([PathBuf::from("proyecto/viejo")]));
+
+        assert_eq!(prune_emptied_dirs(&source, &destination, &deleted_from).unwrap(), 2);
+        assert!(!destination.join("proyecto").exists());
+        assert!(destination.join("mio_vacio").is_dir());
+    }
+
+    #[tokio::test]
+    async fn test_strict_items_rejects_missing_items() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+        let args = Cli { subir: true, ..Default::default() };
+
+        let lenient = SyncManager::new(config.clone(), args.clone());
+        assert!(lenient.verify_config_items().await.is_ok());
+        assert!(lenient.is_missing_source("Documentos"));
+
+        config.general.strict_items = true;
+        let strict = SyncManager::new(config.clone(), args);
+        assert!(matches!(strict.verify_config_items().await, Err(AppError::Validation(_))));
+
+        // Al bajar el origen es pCloud: lo que haya en local no cuenta
+        std::fs::create_dir_all(temp_dir.path().join("Documentos")).unwrap();
+        let download = SyncManager::new(config.clone(), Cli { bajar: true, ..Default::default() });
+        assert!(matches!(download.verify_config_items().await, Err(AppError::Validation(_))));
+        std::fs::create_dir_all(config.general.pcloud_backup_comun.join("Documentos")).unwrap();
+        assert!(download.verify_config_items().await.is_ok());
+    }
+
+    #[test]
+    fn test_filesize_limits_map_to_rsync_options() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+        config.hosts.get_mut("default").unwrap().max_filesize = Some(parse_size("2G").unwrap());
+
+        let host_only = rsync_args(config.clone(), Cli { subir: true, ..Default::default() });
+        assert!(host_only.contains(&"--max-size=2147483648".to_string()));
+        assert!(host_only.contains(&"--info=skip1".to_string()));
+
+        let args = Cli {
+            subir: true,
+            max_filesize: Some(parse_size("100M").unwrap()),
+            min_filesize: Some(parse_size("1K").unwrap()),
+            ..Default::default()
+        };
+        let with_cli = rsync_args(config, args);
+        assert!(with_cli.contains(&"--max-size=104857600".to_string()));
+        assert!(with_cli.contains(&"--min-size=1024".to_string()));
+
+        let output = "video.mkv is over max-size\nvacio.txt is under min-size\n>f+++++++++ a.txt\n";
+        assert_eq!(count_size_skips(output), 2);
+        assert_eq!(parse_oversized_files(output), ["video.mkv"]);
+    }
+
+    #[tokio::test]
+    async fn test_crypto_only_skips_items() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("Documentos")).unwrap();
+        let config = test_config(temp_dir.path());
+
+        // Sin el archivo de verificación, el montaje de Crypto sigue bloqueando
+        let args = Cli { subir: true, crypto_only: true, ..Default::default() };
+        let (_, result) = perform_sync(&args, &config).await;
+        assert!(matches!(result, Err(AppError::Crypto(_))));
+
+        let args = Cli { no_crypto_mount_check: true, ..args };
+        let (stats, result) = perform_sync(&args, &config).await;
+        assert!(result.is_ok());
+        assert_eq!(stats.items_processed, 0);
+        assert_eq!(stats.symbolic_links_detected, 0);
+    }
+
+    #[tokio::test]
+    async fn test_deletion_guard_fails_closed() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("Documentos")).unwrap();
+        let mut config = test_config(temp_dir.path());
+        // rsync rechaza la opción (o no está instalado): no hay recuento
+        config.general.rsync_base_options = vec!["--opcion-inexistente".to_string()];
+        let args = Cli { subir: true, delete: true, yes: true, ..Default::default() };
+
+        assert!(matches!(guard_deletions(&args, &config).await, Err(AppError::Sync(_))));
+    }
+
+    #[tokio::test]
+    async fn test_plan_deletion_guard() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(temp_dir.path());
+        let args = Cli { plan_in: Some(temp_dir.path().join("plan.json")), yes: true, ..Default::default() };
+        let plan_with = |command: &[&str]| ExecutionPlan {
+            version: plan::PLAN_VERSION,
+            mode: "Upload".to_string(),
+            host: "default".to_string(),
+            remote: None,
+            items: vec![PlannedItem {
+                item: "Documentos".to_string(),
+                source: temp_dir.path().join("Documentos"),
+                destination: temp_dir.path().join("destino"),
+                timeout_minutes: 1,
+                create_destination_parent: false,
+                exclusions: Vec::new(),
+                commands: vec![command.iter().map(|arg| arg.to_string()).collect()],
+            }],
+        };
+
+        // Sin --delete en el plan no hay pasada previa
+        let plan = plan_with(&["rsync", "-a", "/a/", "/b/"]);
+        assert!(guard_plan_deletions(&args, &config, &plan).await.is_ok());
+
+        // Con --delete el recuento es obligatorio: si rsync falla, se aborta
+        let plan = plan_with(&["rsync", "--opcion-inexistente", "--delete", "/a/", "/b/"]);
+        assert!(matches!(guard_plan_deletions(&args, &config, &plan).await, Err(AppError::Sync(_))));
+    }
+
+    #[test]
+    fn test_deletion_preview_is_always_a_simulation() {
+        let temp_dir = TempDir::new().unwrap();
+        let args = Cli { subir: true, preview_deletions: true, ..Default::default() };
+        let simulation = deletion_simulation(&args);
+        assert!(simulation.delete && simulation.dry_run);
+        // La ejecución real conserva su --delete
+        assert!(!args.delete && !args.dry_run);
+
+        let preview = rsync_args(test_config(temp_dir.path()), simulation);
+        assert!(preview.contains(&"--dry-run".to_string()));
+        assert!(preview.iter().any(|arg| arg.starts_with("--delete")));
+        let real = rsync_args(test_config(temp_dir.path()), args);
+        assert!(!real.iter().any(|arg| arg.starts_with("--delete") || arg == "--dry-run"));
+    }
+
+    #[test]
+    fn test_checksum_choice() {
+        let version = "\
+rsync  version 3.2.7  protocol version 31
+Capabilities:
+    64-bit files, 64-bit inums, 64-bit timestamps, 64-bit long ints,
+Checksum list:
+    xxh128 xxh3 xxh64 (xxhash) md5 md4 sha1 none
+Compress list:
+    zstd lz4 zlibx zlib none
+";
+        let available = parse_rsync_checksum_list(version);
+        assert_eq!(available, ["xxh128", "xxh3", "xxh64", "md5", "md4", "sha1", "none"]);
+        assert_eq!(supported_checksum_choice("xxh64", Some(&available)), Some("xxh64"));
+        assert_eq!(supported_checksum_choice("auto,md5", Some(&available)), Some("auto,md5"));
+        assert_eq!(supported_checksum_choice("blake3", Some(&available)), None);
+        // rsync anterior a 3.2: sin la sección, no hay --checksum-choice
+        let old = parse_rsync_checksum_list("rsync  version 3.1.3  protocol version 31\n");
+        assert_eq!(supported_checksum_choice("xxh64", Some(&old)), None);
+        assert_eq!(supported_checksum_choice("xxh64", None), Some("xxh64"));
+
+        // Solo con --checksum; la línea de comandos prevalece sobre la configuración
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+        config.general.checksum_choice = Some("md5".to_string());
+        let plain = rsync_args(config.clone(), Cli { subir: true, ..Default::default() });
+        assert!(!plain.iter().any(|arg| arg.starts_with("--checksum-choice")));
+        if RSYNC_CHECKSUMS.as_ref().is_none_or(|list| list.iter().any(|a| a == "xxh64")) {
+            let args = Cli { subir: true, checksum: true, checksum_choice: Some("xxh64".to_string()), ..Default::default() };
+            assert!(rsync_args(config, args).contains(&"--checksum-choice=xxh64".to_string()));
+        }
+    }
+
+    #[test]
+    fn test_exclude_larger_than_free_caps_max_size() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("pCloudDrive")).unwrap();
+        let mut config = test_config(temp_dir.path());
+        config.general.min_free_space_mb = 0;
+        let args = Cli { subir: true, exclude_larger_than_free: true, ..Default::default() };
+
+        let mut manager = SyncManager::new(config.clone(), args.clone());
+        let cap = manager.free_space_cap().unwrap().unwrap();
+        assert_eq!(cap, get_available_space_mb(temp_dir.path()).unwrap() * 1024 * 1024);
+
+        // El menor de los dos topes
+        manager.args.max_filesize = Some(parse_size("1G").unwrap());
+        manager.free_space_cap = Some(parse_size("10M").unwrap());
+        let command = manager
+            .build_rsync_command("Documentos", Path::new("/origen"), Path::new("/destino"), None)
+            .unwrap();
+        let command_args: Vec<_> = command.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect();
+        assert!(command_args.contains(&"--max-size=10485760".to_string()));
+        assert!(command_args.contains(&"--info=skip1".to_string()));
+
+        // Sin sitio por encima de la reserva no cabe nada
+        config.general.min_free_space_mb = u64::MAX;
+        assert!(matches!(
+            SyncManager::new(config, args).free_space_cap(),
+            Err(AppError::InsufficientSpace(_))
+        ));
+    }
+
+    #[test]
+    fn test_rsync_base_options() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+
+        let default = rsync_args(config.clone(), Cli { subir: true, ..Default::default() });
+        assert_eq!(default[..3], ["-av", "--progress", "--itemize-changes"]);
+        assert!(!default.contains(&"--links".to_string()));
+
+        // Para FUSE sin permisos ni propietarios: --itemize-changes y --links se añaden
+        config.general.rsync_base_options = vec!["-rtv".to_string(), "--progress".to_string()];
+        let fuse = rsync_args(config.clone(), Cli { subir: true, ..Default::default() });
+        assert_eq!(fuse[..4], ["-rtv", "--progress", "--itemize-changes", "--links"]);
+
+        config.general.rsync_base_options = vec!["-rtvi".to_string()];
+        let itemized = rsync_args(config, Cli { subir: true, ..Default::default() });
+        assert!(!itemized.contains(&"--itemize-changes".to_string()));
+        assert!(has_rsync_option(&["-rtvi".to_string()], "--itemize-changes", 'i'));
+        assert!(!has_rsync_option(&["--info=progress2".to_string()], "--itemize-changes", 'i'));
+    }
+
+    #[test]
+    fn test_symlink_policy_cli_overrides_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+
+        let preserve = rsync_args(config.clone(), Cli { subir: true, ..Default::default() });
+        assert!(!preserve.iter().any(|arg| arg == "--copy-links" || arg == "--no-links"));
+
+        config.general.symlink_policy = SymlinkPolicy::Skip;
+        let skip = rsync_args(config.clone(), Cli { subir: true, ..Default::default() });
+        assert!(skip.contains(&"--no-links".to_string()));
+
+        let args = Cli {
+            subir: true,
+            symlink_policy: Some(SymlinkPolicy::Follow),
+            ..Default::default()
+        };
+        let follow = rsync_args(config.clone(), args);
+        assert!(follow.contains(&"--copy-links".to_string()));
+        assert!(!follow.contains(&"--no-links".to_string()));
+
+        let safe = rsync_args(config.clone(), Cli { subir: true, safe_links: true, ..Default::default() });
+        assert!(safe.contains(&"--safe-links".to_string()));
+        let copy_unsafe = rsync_args(config, Cli { subir: true, copy_unsafe_links: true, ..Default::default() });
+        assert!(copy_unsafe.contains(&"--copy-unsafe-links".to_string()));
+        assert!(!copy_unsafe.contains(&"--safe-links".to_string()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unchanged_files_are_counted() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let args = Cli { subir: true, dry_run: true, ..Default::default() };
+        assert!(rsync_args(test_config(temp_dir.path()), args.clone()).contains(&"--stats".to_string()));
+
+        let manager = SyncManager::new(test_config(temp_dir.path()), args);
+        let output = std::process::Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: b">f+++++++++ nuevo.txt\n>f.st...... cambiado.txt\n\n\
+                      Number of files: 10,002 (reg: 10,000, dir: 2)\n\
+                      Number of regular files transferred: 2\n"
+                .to_vec(),
+            stderr: Vec::new(),
+        };
+        let mut item_stats = ItemStats::default();
+        manager.process_rsync_output("Documentos", &output, None, &mut item_stats).unwrap();
+        assert_eq!((item_stats.files_transferred, item_stats.files_unchanged), (2, 9_998));
+
+        let mut stats = SyncStats { dry_run: true, ..SyncStats::default() };
+        stats.merge(item_stats);
+        assert_eq!(stats.to_json()["files_unchanged"], 9_998);
+        assert_eq!(stats.to_json()["dry_run"], true);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_vanished_files_are_not_errors() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let manager = SyncManager::new(test_config(temp_dir.path()), Cli { subir: true, ..Default::default() });
+        let output = std::process::Output {
+            status: std::process::ExitStatus::from_raw(24 << 8),
+            stdout: b">f+++++++++ nuevo.txt\n".to_vec(),
+            stderr: b"file has vanished: \"/origen/a.tmp\"\n\
+                      rsync: [sender] file has vanished: \"/origen/cache/b.lock\"\n\
+                      rsync warning: some files vanished before they could be transferred (code 24)\n"
+                .to_vec(),
+        };
+
+        let mut item_stats = ItemStats::default();
+        manager.process_rsync_output("Documentos", &output, None, &mut item_stats).unwrap();
+        assert_eq!(item_stats.files_transferred, 1);
+
+        let mut stats = SyncStats::default();
+        stats.merge(item_stats);
+        assert_eq!(stats.sync_errors, 0);
+        assert_eq!(stats.files_vanished, 2);
+        assert_eq!(stats.item_reports[0].vanished, ["/origen/a.tmp", "/origen/cache/b.lock"]);
+        assert_eq!(stats.to_json()["files_vanished"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_streamed_stdout_drops_itemized_lines() {
+        let stdout = "sending incremental file list\n\
+                      >f+++++++++ a.txt\n\
+                      \u{20}         1,024 100%    1.00MB/s    0:00:00 (xfr#1, to-chk=1/3)\n\
+                      >f.st...... b.txt\n\
+                      sent 1,234 bytes  received 56 bytes  2,580.00 bytes/sec";
+
+        let (kept, itemized) = read_stdout(Some(stdout.as_bytes()), None, None).await;
+        assert_eq!(kept, stdout.as_bytes());
+        assert!(itemized.is_none());
+
+        let (kept, itemized) = read_stdout(Some(stdout.as_bytes()), None, Some(ItemizeParser::new("Documentos", Some(1)))).await;
+        let kept = String::from_utf8(kept).unwrap();
+        assert_eq!(kept, "sending incremental file list\nsent 1,234 bytes  received 56 bytes  2,580.00 bytes/sec");
+
+        let itemized = itemized.unwrap();
+        assert_eq!(itemized.files_transferred, 2);
+        let report = itemized.finish();
+        assert_eq!((report.created_count(), report.updated_count()), (1, 1));
+        assert_eq!(parse_rsync_transferred_bytes(&kept, false), Some(1234));
+        assert_eq!(parse_rsync_transferred_bytes(&kept, true), Some(56));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_rsync_exit_code_is_kept_per_item() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let manager = SyncManager::new(test_config(temp_dir.path()), Cli { subir: true, ..Default::default() });
+        let output = std::process::Output {
+            status: std::process::ExitStatus::from_raw(23 << 8),
+            stdout: Vec::new(),
+            stderr: b"rsync: [sender] send_files failed to open \"/origen/a\": Permission denied (13)\n\
+                      rsync error: some files/attrs were not transferred (code 23)\n"
+                .to_vec(),
+        };
+
+        let error = manager
+            .process_rsync_output("Documentos", &output, None, &mut ItemStats::default())
+            .unwrap_err();
+        assert_eq!(error.rsync_exit_code(), Some(23));
+        assert_eq!(error.exit_code(), 14);
+
+        let mut stats = SyncStats::default();
+        stats.record_failed_item("Documentos", &error);
+        assert_eq!(stats.sync_errors, 1);
+        assert_eq!(stats.failed_items[0].exit_code, Some(23));
+        assert_eq!(stats.to_json()["failed_items"][0]["item"], "Documentos");
+    }
+
+    #[test]
+    fn test_force_checksum_passes() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+        config.general.force_checksum_on = vec!["*.kdbx".to_string()];
+        let args = Cli {
+            subir: true,
+            force_checksum_on: vec!["*.sqlite".to_string()],
+            ..Default::default()
+        };
+        let manager = SyncManager::new(config, args);
+        assert_eq!(manager.forced_checksum_patterns(temp_dir.path()).len(), 2);
+        assert!(manager.forced_checksum_patterns(&temp_dir.path().join("no_es_dir")).is_empty());
+
+        let args_of = |pass| -> Vec<String> {
+            manager
+                .with_checksum_pass(pass)
+                .build_rsync_command("Documentos", Path::new("/origen"), Path::new("/destino"), None)
+                .unwrap()
+                .get_args()
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect()
+        };
+
+        let fast = args_of(ChecksumPass::Fast);
+        assert!(follows(&fast, "--exclude", "*.kdbx") && follows(&fast, "--exclude", "*.sqlite"));
+        assert!(!fast.contains(&"--checksum".to_string()));
+
+        let strong = args_of(ChecksumPass::Strong);
+        assert!(strong.contains(&"--checksum".to_string()));
+        let position = |flag: &str, value: &str| strong.windows(2).position(|p| p[0] == flag && p[1] == value).unwrap();
+        // Las exclusiones del host siguen mandando sobre los patrones incluidos
+        assert!(position("--exclude", "*.tmp") < position("--include", "*.kdbx"));
+        assert!(position("--include", "*.sqlite") < position("--exclude", "*"));
+    }
+
+    #[test]
+    fn test_progress_peak_rate_uses_sampling_windows() {
+        let progress = ProgressTotal::new(100 * 1024 * 1024);
+        // Ventana de 4 s con 8 MiB hechos: 2 MiB/s
+        *progress.last_sample.lock().unwrap() =
+            (std::time::Instant::now() - Duration::from_secs(4), 0);
+        progress.file_done(8 * 1024 * 1024);
+        progress.sample(0);
+        let peak = progress.peak_rate();
+        assert!((1_900_000..=2_200_000).contains(&peak), "{}", peak);
+
+        // Una ventana que aún no ha terminado no cuenta
+        progress.file_done(50 * 1024 * 1024);
+        progress.sample(0);
+        assert_eq!(progress.peak_rate(), peak);
+    }
+
+    #[test]
+    fn test_hidden_rules_come_after_explicit_includes() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(temp_dir.path());
+        let position = |args: &[String], flag: &str, value: &str| {
+            args.windows(2).position(|p| p[0] == flag && p[1] == value).unwrap()
+        };
+
+        let no_hidden = rsync_args(
+            config.clone(),
+            Cli {
+                subir: true,
+                no_hidden: true,
+                include: vec![".config".to_string()],
+                exclude: vec![".config/cache".to_string()],
+                ..Default::default()
+            },
+        );
+        assert!(position(&no_hidden, "--include", ".config") < position(&no_hidden, "--exclude", ".*"));
+        assert!(position(&no_hidden, "--exclude", "*.tmp") < position(&no_hidden, "--exclude", ".*"));
+        // El directorio de transferencias parciales se excluye siempre antes
+        assert!(position(&no_hidden, "--exclude", ".rsync-partial/") < position(&no_hidden, "--include", ".config"));
+
+        let only_hidden = rsync_args(config, Cli { subir: true, only_hidden: true, ..Default::default() });
+        assert!(position(&only_hidden, "--exclude", "*.tmp") < position(&only_hidden, "--include", ".*"));
+        assert!(position(&only_hidden, "--include", "*/") < position(&only_hidden, "--exclude", "*"));
+        assert!(only_hidden.contains(&"--prune-empty-dirs".to_string()));
+    }
+
+    #[test]
+    fn test_exclude_vcs() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(temp_dir.path());
+
+        let args = rsync_args(config.clone(), Cli { subir: true, ..Default::default() });
+        assert!(!follows(&args, "--exclude", ".git"));
+
+        let args = rsync_args(
+            config,
+            Cli { subir: true, exclude_vcs: true, include: vec![".git".to_string()], ..Default::default() },
+        );
+        for exclusion in VCS_EXCLUSIONS {
+            assert!(follows(&args, "--exclude", exclusion));
+        }
+        assert!(!VCS_EXCLUSIONS.contains(&".gitignore"));
+        // --include sigue pudiendo rescatar un repositorio concreto
+        let include = args.iter().position(|a| a == ".git").unwrap();
+        let exclude = args.windows(2).position(|p| p[0] == "--exclude" && p[1] == ".git").unwrap();
+        assert!(include < exclude);
+    }
+
+    #[test]
+    fn test_bwlimit_is_split_between_parallel_items() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(temp_dir.path());
+
+        let rsync = rsync_args(config.clone(), Cli { subir: true, bwlimit: Some(900), ..Default::default() });
+        assert!(follows(&rsync, "--bwlimit", "900"));
+        let rsync = rsync_args(config.clone(), Cli { subir: true, bwlimit: Some(900), parallel: 3, ..Default::default() });
+        assert!(follows(&rsync, "--bwlimit", "300"));
+        let rsync = rsync_args(config, Cli { subir: true, ..Default::default() });
+        assert!(!rsync.iter().any(|arg| arg == "--bwlimit"));
+    }
+
+    #[test]
+    fn test_filter_rules_go_first_in_order() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+        config.hosts.get_mut("default").unwrap().filters = vec!["- *.bak".to_string()];
+
+        let args = Cli {
+            subir: true,
+            filter: vec!["+ /conservar.tmp".to_string()],
+            include: vec!["*.log".to_string()],
+            ..Default::default()
+        };
+        let rsync = rsync_args(config, args);
+        let position = |arg: &str| rsync.iter().position(|a| a == arg).unwrap();
+        assert!(position("--filter=+ /conservar.tmp") < position("--filter=- *.bak"));
+        assert!(position("--filter=- *.bak") < position("*.log"));
+        assert!(position("*.log") < position("*.tmp"));
+    }
+
+    #[test]
+    fn test_compress_options() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+        config.general.skip_compress = Some("jpg/mp4".to_string());
+
+        // Sin compresión, --skip-compress de la configuración no se emite
+        let plain = rsync_args(config.clone(), Cli { subir: true, ..Default::default() });
+        assert!(!plain.iter().any(|arg| arg.contains("compress")));
+
+        let args = Cli {
+            subir: true,
+            compress_level: Some(3),
+            skip_compress: Some("zip".to_string()),
+            ..Default::default()
+        };
+        let compressed = rsync_args(config.clone(), args);
+        assert!(compressed.contains(&"--compress".to_string()));
+        assert!(compressed.contains(&"--compress-level=3".to_string()));
+        assert!(compressed.contains(&"--skip-compress=zip".to_string()));
+
+        config.general.compress = true;
+        let from_config = rsync_args(config, Cli { subir: true, ..Default::default() });
+        assert!(from_config.contains(&"--compress".to_string()));
+        assert!(from_config.contains(&"--skip-compress=jpg/mp4".to_string()));
+        assert!(!from_config.iter().any(|arg| arg.starts_with("--compress-level")));
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_tolerates_missing_mount() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(temp_dir.path());
+        std::fs::create_dir_all(config.local_dir()).unwrap();
+        assert!(!config.general.pcloud_mount_point.exists());
+
+        let args = Cli {
+            subir: true,
+            skip_connectivity_check: true,
+            ..Default::default()
+        };
+        let real = SyncManager::new(config.clone(), args.clone());
+        assert!(matches!(real.verify_preconditions().await, Err(AppError::PCloudNotMounted(_))));
+
+        let simulation = SyncManager::new(config, Cli { dry_run: true, ..args });
+        assert!(simulation.verify_preconditions().await.is_ok());
+    }
+
+    #[test]
+    fn test_verify_respects_update() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(temp_dir.path());
+        let verify_args = |args: Cli| -> Vec<String> {
+            let manager = SyncManager::new(config.clone(), args);
+            let command = manager.build_verify_command(Path::new("/origen"), Path::new("/destino")).unwrap();
+            command.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect()
+        };
+
+        let update = verify_args(Cli { subir: true, verify: true, ..Default::default() });
+        assert!(update.contains(&"--checksum".to_string()) && update.contains(&"--dry-run".to_string()));
+        assert!(update.contains(&"--update".to_string()));
+
+        let overwrite = verify_args(Cli { subir: true, verify: true, overwrite: true, ..Default::default() });
+        assert!(!overwrite.contains(&"--update".to_string()));
+    }
+
+    #[test]
+    fn test_verify_pcloud_writable() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(temp_dir.path());
+        let args = Cli {
+            subir: true,
+            verify_mount_writable: true,
+            ..Default::default()
+        };
+
+        // Sin nada existente dentro del punto de montaje no se prueba fuera de él
+        let manager = SyncManager::new(config.clone(), args.clone());
+        assert!(matches!(manager.verify_pcloud_writable(), Err(AppError::PCloudNotMounted(_))));
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+
+        // El directorio de backup aún no existe: se prueba en el ancestro existente
+        let mount_point = &config.general.pcloud_mount_point;
+        std::fs::create_dir_all(mount_point).unwrap();
+        assert!(manager.verify_pcloud_writable().is_ok());
+        assert_eq!(std::fs::read_dir(mount_point).unwrap().count(), 0);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let backup = &config.general.pcloud_backup_comun;
+            std::fs::create_dir_all(backup).unwrap();
+            std::fs::set_permissions(backup, std::fs::Permissions::from_mode(0o555)).unwrap();
+            // root escribe igualmente: la prueba solo tiene sentido sin privilegios
+            if std::fs::write(backup.join("sonda"), b"").is_err() {
+                assert!(matches!(manager.verify_pcloud_writable(), Err(AppError::NotWritable(_))));
+            }
+            let dry_run = SyncManager::new(config.clone(), Cli { dry_run: true, ..args });
+            assert!(dry_run.verify_pcloud_writable().is_ok());
+            std::fs::set_permissions(backup, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_prepare_resume_skips_completed_items() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+        config.general.resume_state_file = temp_dir.path().join("resume_state.json");
+        let items = vec!["Documentos".to_string(), "Imagenes".to_string()];
+
+        let manager = SyncManager::new(config.clone(), Cli { subir: true, ..Default::default() });
+        let mut stats = SyncStats::default();
+        let (state, pending) = manager.prepare_resume(items.clone(), &mut stats);
+        assert_eq!(pending, items);
+        state.unwrap().record("Documentos").unwrap();
+
+        // Sin --resume-items se repite todo
+        let (_, pending) = manager.prepare_resume(items.clone(), &mut stats);
+        assert_eq!(pending, items);
+
+        let resuming = SyncManager::new(config.clone(), Cli { subir: true, resume_items: true, ..Default::default() });
+        let (_, pending) = resuming.prepare_resume(items.clone(), &mut stats);
+        assert_eq!(pending, vec!["Imagenes".to_string()]);
+        assert_eq!(stats.items_resumed, 1);
+
+        // Otros parámetros no reanudan, y una simulación no anota nada
+        let download = SyncManager::new(config.clone(), Cli { bajar: true, resume_items: true, ..Default::default() });
+        assert_eq!(download.prepare_resume(items.clone(), &mut stats).1, items);
+        let dry_run = SyncManager::new(config, Cli { subir: true, resume_items: true, dry_run: true, ..Default::default() });
+        assert!(dry_run.prepare_resume(items, &mut stats).0.is_none());
+    }
+
+    #[test]
+    fn test_mountinfo_mount_points() {
+        let mountinfo = "\
+28 1 0:25 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+52 28 0:47 / /home/yo/pCloud\\040Drive rw,nosuid,nodev,relatime shared:30 - fuse.pCloud pCloud rw
+";
+        assert!(mountinfo_has_mount_point(mountinfo, Path::new("/")));
+        assert!(mountinfo_has_mount_point(mountinfo, Path::new("/home/yo/pCloud Drive")));
+        assert!(!mountinfo_has_mount_point(mountinfo, Path::new("/home/yo")));
+
+        #[cfg(target_os = "linux")]
+        {
+            let temp_dir = TempDir::new().unwrap();
+            assert_eq!(os_mount_state(Path::new("/")), Some(true));
+            assert_eq!(os_mount_state(temp_dir.path()), Some(false));
+            // Vacío pero montado: manda el sistema
+            assert!(!is_directory_mounted(temp_dir.path()));
+        }
+    }
+
+    #[test]
+    fn test_ssh_remote_rsync_args() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+        config.ssh = Some(SshTarget { host: "nas".to_string(), user: Some("copias".to_string()), port: Some(2222) });
+
+        let upload = rsync_args(config.clone(), Cli { subir: true, ..Default::default() });
+        assert!(follows(&upload, "-e", "ssh -p 2222"));
+        assert_eq!(upload[upload.len() - 2..], ["/origen", "copias@nas:/destino"]);
+        // Un plan guardado con este remoto se puede volver a ejecutar
+        let argv: Vec<String> = std::iter::once("rsync".to_string()).chain(upload.iter().cloned()).collect();
+        assert!(plan::argv_command(&argv, Some("ssh -p 2222")).is_ok());
+
+        let download = rsync_args(config.clone(), Cli { bajar: true, ..Default::default() });
+        assert_eq!(download[download.len() - 2..], ["copias@nas:/origen", "/destino"]);
+
+        // Destino local ya existente: barra final en los dos lados
+        let ssh = config.ssh.as_ref().unwrap();
+        let local = temp_dir.path().join("Documentos");
+        std::fs::create_dir(&local).unwrap();
+        let download_args = ssh_rsync_paths(ssh, SyncMode::Download, Path::new("/srv/copias/Documentos"), &local);
+        assert_eq!(
+            download_args,
+            ["copias@nas:/srv/copias/Documentos/".into(), format!("{}/", local.display()).into()] as [std::ffi::OsString; 2]
+        );
+        let file_args = ssh_rsync_paths(ssh, SyncMode::Download, Path::new("/srv/copias/a.ini"), &temp_dir.path().join("a.ini"));
+        assert_eq!(file_args[0], "copias@nas:/srv/copias/a.ini");
+
+        assert_eq!(
+            ssh_probe_args(config.ssh.as_ref().unwrap()),
+            ["-o", "BatchMode=yes", "-o", "ConnectTimeout=10", "-p", "2222", "copias@nas", "true"]
+        );
+    }
+
+    #[test]
+    fn test_required_dependencies() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+        assert_eq!(required_dependencies(&config), vec!["rsync"]);
+
+        config.notifications.backend = NotificationBackend::Webhook;
+        assert_eq!(required_dependencies(&config), vec!["rsync", "curl"]);
+        config.ssh = Some(SshTarget { host: "nas".to_string(), user: None, port: None });
+        assert_eq!(required_dependencies(&config), vec!["rsync", "ssh", "curl"]);
+        assert!(install_hint("curl").contains("curl"));
+        assert_eq!(AppError::DependencyMissing("rsync".to_string()).exit_code(), 15);
+        assert_eq!(AppError::NotWritable(temp_dir.path().to_path_buf()).exit_code(), 16);
+    }
+
+    #[test]
+    fn test_parse_item_answer() {
+        assert_eq!(parse_item_answer("s\n"), Some(ItemAnswer::Sync));
+        assert_eq!(parse_item_answer(" Sí "), Some(ItemAnswer::Sync));
+        assert_eq!(parse_item_answer("o"), Some(ItemAnswer::Skip));
+        assert_eq!(parse_item_answer("A"), Some(ItemAnswer::Abort));
+        assert_eq!(parse_item_answer(""), None);
+        assert_eq!(parse_item_answer("quizá"), None);
+    }
+
+    #[tokio::test]
+    async fn test_disk_space_checks_every_receiving_side() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(temp_dir.path());
+        let sides = |args: Cli| {
+            let manager = SyncManager::new(config.clone(), args);
+            async move {
+                let incoming = manager.estimate_incoming_bytes().await.unwrap();
+                incoming.into_iter().map(|(path, _)| path).collect::<Vec<_>>()
+            }
+        };
+
+        let upload = sides(Cli { subir: true, ..Default::default() }).await;
+        assert_eq!(upload, vec![config.general.pcloud_mount_point.clone()]);
+        // La estimación se hace por defecto; con `estimate_transfer_size = false`
+        // no hay pasada previa
+        assert!(config.general.estimate_transfer_size);
+        let mut without_estimate = config.clone();
+        without_estimate.general.estimate_transfer_size = false;
+        let manager = SyncManager::new(without_estimate, Cli { subir: true, ..Default::default() });
+        assert_eq!(manager.estimate_incoming_bytes().await.unwrap()[0].1, 0);
+
+        let download = sides(Cli { bajar: true, ..Default::default() }).await;
+        assert_eq!(download, vec![config.local_dir()]);
+
+        let two_way = sides(Cli { two_way: true, ..Default::default() }).await;
+        assert_eq!(two_way, vec![config.general.pcloud_mount_point.clone(), config.local_dir()]);
+    }
+
+    #[tokio::test]
+    async fn test_overlapping_paths_fail_before_rsync() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("Documentos")).unwrap();
+        let args = Cli { subir: true, ..Default::default() };
+
+        // Mismo directorio en ambos lados
+        let mut same = test_config(temp_dir.path());
+        same.general.pcloud_backup_comun = temp_dir.path().join(".");
+        let manager = SyncManager::new(same, args.clone());
+        let (_, result) = manager.sync_item("Documentos").await;
+        assert!(matches!(result, Err(AppError::Validation(_))));
+
+        // Destino dentro del origen (aún no existe)
+        let mut nested = test_config(temp_dir.path());
+        nested.general.pcloud_backup_comun = temp_dir.path().join("Documentos/backup");
+        let manager = SyncManager::new(nested, args);
+        let (_, result) = manager.sync_item("Documentos").await;
+        assert!(matches!(result, Err(AppError::Validation(_))));
+    }
+
+    #[test]
+    fn test_progress_parsing() {
+        assert_eq!(parse_progress_bytes("      1,234,567  45%   10.50MB/s    0:00:03"), Some(1_234_567));
+        assert_eq!(
+            parse_progress_bytes("     32,768 100%   31.25MB/s    0:00:00 (xfr#1, to-chk=2/4)"),
+            Some(32_768)
+        );
+        assert_eq!(parse_progress_bytes(">f+++++++++ docs/a.txt"), None);
+
+        let stats = "Total file size: 9,000 bytes\nTotal transferred file size: 4,096 bytes\n";
+        assert_eq!(parse_rsync_total_size(stats), Some(4_096));
+    }
+
+    #[test]
+    fn test_itemized_change_detection() {
+        assert!(is_itemized_change(">f.st...... docs/a.txt"));
+        assert!(is_itemized_change("cd+++++++++ docs/nuevo/"));
+        assert!(is_itemized_change("*deleting   docs/viejo.txt"));
+        assert!(!is_itemized_change(".d..t...... docs/"));
+        assert!(!is_itemized_change("sending incremental file list"));
+    }
 }
@@ -0,0 +1,36 @@
+use crate::config::AppConfig;
+use std::path::Path;
+
+/// Configuración de prueba con todas las rutas bajo `root` y un host
+/// `default` con el elemento "Documentos"
+pub fn test_config(root: &Path) -> AppConfig {
+    test_config_with(root, "[hosts.default]\nsync_items = [\"Documentos\"]\nexclusions = [\"*.tmp\"]")
+}
+
+/// Como `test_config`, con `extra` (hosts, remotos, perfiles...) en lugar
+/// del host por defecto
+pub fn test_config_with(root: &Path, extra: &str) -> AppConfig {
+    let root = root.display();
+    toml::from_str(&format!(r#"
+        [general]
+        local_dir = "{root}"
+        pcloud_mount_point = "{root}/pCloudDrive"
+        pcloud_backup_comun = "{root}/pCloudDrive/Backups/Backup_Comun"
+        pcloud_backup_readonly = "{root}/pCloudDrive/pCloud Backup/test"
+        log_file = "{root}/syncb.log"
+        lock_file = "{root}/syncb.lock"
+        lock_timeout_seconds = 3600
+        default_timeout_minutes = 30
+
+        [general.crypto]
+        local_crypto_dir = "{root}/Crypto"
+        remote_crypto_dir = "{root}/pCloudDrive/Crypto Folder"
+        cloud_mount_check_file = "mount.check"
+        local_keepass_dir = "{root}/Crypto/Keepass2Android"
+        remote_keepass_dir = "{root}/pCloudDrive/Applications/Keepass2Android"
+        local_crypto_hostname_rtva_dir = "{root}/Crypto/ficheros_sensibles"
+        remote_crypto_hostname_rtva_dir = "{root}/pCloudDrive/Crypto Folder/ficheros_sensibles"
+
+        {extra}
+    "#)).unwrap()
+}
@@ -0,0 +1,233 @@
+use crate::cli::ConflictStrategy;
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use walkdir::WalkDir;
+
+/// Tamaño y fecha (en segundos: pCloud no conserva los nanosegundos) de un archivo
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FileState {
+    pub size: u64,
+    pub mtime: i64,
+}
+
+/// Archivos de un árbol por ruta relativa
+pub type TreeState = BTreeMap<String, FileState>;
+
+/// Qué copiar en cada sentido para fusionar los dos árboles
+#[derive(Debug, Default, PartialEq)]
+pub struct TwoWayPlan {
+    pub to_remote: Vec<String>,
+    pub to_local: Vec<String>,
+    /// Cambiados en ambos lados que la estrategia deja sin resolver
+    pub conflicts: Vec<String>,
+}
+
+/// Estado tras la última sincronización bidireccional de cada elemento,
+/// para distinguir qué lado cambió desde entonces
+#[derive(Debug, Default)]
+pub struct Baselines {
+    file: PathBuf,
+    items: HashMap<String, TreeState>,
+}
+
+impl Baselines {
+    pub fn load(file: &Path) -> Self {
+        let items = std::fs::read_to_string(file)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+
+        Self {
+            file: file.to_path_buf(),
+            items,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&TreeState> {
+        self.items.get(key)
+    }
+
+    pub fn set(&mut self, key: String, state: TreeState) {
+        self.items.insert(key, state);
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.file.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let temp = self.file.with_extension("json.tmp");
+        std::fs::write(&temp, serde_json::to_vec(&self.items).map_err(std::io::Error::from)?)?;
+        std::fs::rename(&temp, &self.file)?;
+        Ok(())
+    }
+}
+
+/// Archivos regulares bajo `root` (vacío si no existe); no sigue enlaces
+pub fn scan_tree(root: &Path) -> Result<TreeState> {
+    let mut state = TreeState::new();
+    if !root.is_dir() {
+        return Ok(state);
+    }
+
+    for entry in WalkDir::new(root).follow_links(false) {
+        let entry = entry.map_err(std::io::Error::from)?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let metadata = entry.metadata().map_err(std::io::Error::from)?;
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        let relative = entry.path().strip_prefix(root).unwrap_or(entry.path());
+
+        state.insert(
+            relative.to_string_lossy().into_owned(),
+            FileState {
+                size: metadata.len(),
+                mtime,
+            },
+        );
+    }
+
+    Ok(state)
+}
+
+/// Decide el sentido de cada archivo. Gana el lado que cambió desde la línea
+/// base; si cambiaron ambos (o no hay base y no se puede decidir por fecha)
+/// se aplica `strategy`. Los borrados no se propagan: se fusionan los árboles.
+pub fn plan(
+    local: &TreeState,
+    remote: &TreeState,
+    baseline: Option<&TreeState>,
+    strategy: ConflictStrategy,
+) -> TwoWayPlan {
+    let mut plan = TwoWayPlan::default();
+
+    for (path, local_state) in local {
+        let Some(remote_state) = remote.get(path) else {
+            plan.to_remote.push(path.clone());
+            continue;
+        };
+        if local_state == remote_state {
+            continue;
+        }
+
+        let base = baseline.and_then(|baseline| baseline.get(path));
+        let local_changed = base != Some(local_state);
+        let remote_changed = base != Some(remote_state);
+
+        let conflict = match base {
+            Some(_) => local_changed && remote_changed,
+            None => local_state.mtime == remote_state.mtime,
+        };
+
+        if !conflict {
+            let local_wins = match base {
+                Some(_) => local_changed,
+                None => local_state.mtime > remote_state.mtime,
+            };
+            push_direction(&mut plan, path, local_wins);
+            continue;
+        }
+
+        match strategy {
+            ConflictStrategy::Local => push_direction(&mut plan, path, true),
+            ConflictStrategy::Remote => push_direction(&mut plan, path, false),
+            ConflictStrategy::Newer if local_state.mtime != remote_state.mtime => {
+                push_direction(&mut plan, path, local_state.mtime > remote_state.mtime)
+            }
+            _ => plan.conflicts.push(path.clone()),
+        }
+    }
+
+    for path in remote.keys() {
+        if !local.contains_key(path) {
+            plan.to_local.push(path.clone());
+        }
+    }
+
+    plan
+}
+
+/// Nueva línea base: los archivos que quedaron iguales en ambos lados, más
+/// la base anterior de los conflictos sin resolver (seguirán siéndolo)
+pub fn next_baseline(
+    local: &TreeState,
+    remote: &TreeState,
+    previous: Option<&TreeState>,
+    conflicts: &[String],
+) -> TreeState {
+    let mut baseline: TreeState = local
+        .iter()
+        .filter(|(path, state)| remote.get(*path) == Some(*state))
+        .map(|(path, state)| (path.clone(), *state))
+        .collect();
+
+    for path in conflicts {
+        if let Some(state) = previous.and_then(|previous| previous.get(path)) {
+            baseline.insert(path.clone(), *state);
+        }
+    }
+
+    baseline
+}
+
+fn push_direction(plan: &mut TwoWayPlan, path: &str, local_wins: bool) {
+    if local_wins {
+        plan.to_remote.push(path.to_string());
+    } else {
+        plan.to_local.push(path.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(files: &[(&str, u64, i64)]) -> TreeState {
+        files
+            .iter()
+            .map(|(path, size, mtime)| (path.to_string(), FileState { size: *size, mtime: *mtime }))
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_without_baseline_uses_newer_side() {
+        let local = tree(&[("solo_local", 1, 10), ("igual", 1, 10), ("local_nuevo", 2, 20), ("remoto_nuevo", 1, 10)]);
+        let remote = tree(&[("solo_remoto", 1, 10), ("igual", 1, 10), ("local_nuevo", 1, 10), ("remoto_nuevo", 3, 30)]);
+
+        let plan = plan(&local, &remote, None, ConflictStrategy::Skip);
+
+        assert_eq!(plan.to_remote, vec!["local_nuevo", "solo_local"]);
+        assert_eq!(plan.to_local, vec!["remoto_nuevo", "solo_remoto"]);
+        assert!(plan.conflicts.is_empty());
+    }
+
+    #[test]
+    fn test_plan_with_baseline_detects_conflicts() {
+        let baseline = tree(&[("doc", 1, 10), ("solo_remoto_cambia", 1, 10)]);
+        let local = tree(&[("doc", 2, 20), ("solo_remoto_cambia", 1, 10)]);
+        // El remoto es más antiguo que el local pero es el único que cambió
+        let remote = tree(&[("doc", 3, 15), ("solo_remoto_cambia", 4, 5)]);
+
+        let skip = plan(&local, &remote, Some(&baseline), ConflictStrategy::Skip);
+        assert_eq!(skip.to_local, vec!["solo_remoto_cambia"]);
+        assert_eq!(skip.conflicts, vec!["doc"]);
+
+        let newer = plan(&local, &remote, Some(&baseline), ConflictStrategy::Newer);
+        assert_eq!(newer.to_remote, vec!["doc"]);
+
+        let remote_wins = plan(&local, &remote, Some(&baseline), ConflictStrategy::Remote);
+        assert_eq!(remote_wins.to_local, vec!["doc", "solo_remoto_cambia"]);
+
+        let next = next_baseline(&local, &remote, Some(&baseline), &skip.conflicts);
+        assert_eq!(next.get("doc"), baseline.get("doc"));
+    }
+}
@@ -0,0 +1,161 @@
+use std::path::{Path, PathBuf};
+
+/// Expande `~` al principio de la ruta al directorio home del usuario
+pub fn expand_tilde(path: &Path) -> PathBuf {
+    if let Some(path_str) = path.to_str() {
+        // Solo "~" y "~/...": "~otro" sería el home de otro usuario
+        if path_str == "~" || path_str.starts_with("~/") {
+            if let Some(home_dir) = dirs::home_dir() {
+                let expanded = path_str.replacen('~', &home_dir.to_string_lossy(), 1);
+                return PathBuf::from(expanded);
+            }
+        }
+    }
+
+    path.to_path_buf()
+}
+
+/// Sustituye `$VAR` y `${VAR}` por su valor; las variables que no están
+/// definidas se dejan tal cual para que el error se vea en la ruta
+pub fn expand_env_vars(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+
+        let (name, consumed) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            },
+            None => {
+                let end = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                (&after[..end], end)
+            }
+        };
+
+        match std::env::var(name) {
+            Ok(expanded) if !name.is_empty() => result.push_str(&expanded),
+            _ => result.push_str(&rest[start..start + 1 + consumed]),
+        }
+        rest = &after[consumed..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// `~` y variables de entorno; no toca el sistema de archivos
+pub fn expand_path(path: &Path) -> PathBuf {
+    let path = expand_tilde(path);
+    match path.to_str() {
+        Some(path_str) if path_str.contains('$') => PathBuf::from(expand_env_vars(path_str)),
+        _ => path,
+    }
+}
+
+/// Expande la ruta (`expand_path`) y, si existe, la canoniza; si no existe
+/// se devuelve expandida tal cual
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let expanded = expand_path(path);
+    expanded.canonicalize().unwrap_or(expanded)
+}
+
+/// Convierte una ruta relativa en absoluta respecto al directorio actual
+pub fn absolute_path(path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+
+    std::env::current_dir()
+        .map(|cwd| cwd.join(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Argumento entre comillas simples si la shell lo interpretaría
+/// (espacios, comodines, `$`, comillas...); tal cual si no hace falta
+pub fn shell_quote(arg: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c);
+    if !arg.is_empty() && arg.chars().all(safe) {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// Programa y argumentos de `command` listos para pegar en una shell. Hoy
+/// ningún argumento lleva secretos (con SSH solo van host, usuario y
+/// puerto); si alguno llegara a llevarlos, habría que ocultarlos aquí
+pub fn command_line(command: &std::process::Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| shell_quote(&arg.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_line_roundtrips_through_sh() {
+        let mut command = std::process::Command::new("rsync");
+        command.args(["-av", "--exclude", "*.tmp", "/origen/Mis Documentos/", "it's $HOME", "", "a\nb", "--filter=- *~"]);
+        let line = command_line(&command);
+        assert!(line.starts_with("rsync -av --exclude '*.tmp' '/origen/Mis Documentos/'"));
+
+        // La shell separa la línea en los mismos argumentos
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("printf '%s\\0' {}", line))
+            .output()
+            .unwrap();
+        let words: Vec<String> = String::from_utf8(output.stdout)
+            .unwrap()
+            .split_terminator('\0')
+            .map(String::from)
+            .collect();
+        let expected: Vec<String> = std::iter::once(command.get_program())
+            .chain(command.get_args())
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(words, expected);
+    }
+
+    #[test]
+    fn test_normalize_path_expands_tilde() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(normalize_path(Path::new("~/syncb-no-existe")), home.join("syncb-no-existe"));
+        assert_eq!(expand_tilde(Path::new("~otro/x")), PathBuf::from("~otro/x"));
+    }
+
+    #[test]
+    fn test_normalize_path_expands_env_vars() {
+        let home = std::env::var("HOME").unwrap();
+        assert_eq!(
+            normalize_path(Path::new("$HOME/syncb-no-existe")),
+            PathBuf::from(format!("{}/syncb-no-existe", home))
+        );
+        assert_eq!(
+            expand_env_vars("${HOME}/a/$SYNCB_VARIABLE_INEXISTENTE/b"),
+            format!("{}/a/$SYNCB_VARIABLE_INEXISTENTE/b", home)
+        );
+        assert_eq!(expand_env_vars("precio$ y ${sin_cerrar"), "precio$ y ${sin_cerrar");
+    }
+
+    #[test]
+    fn test_normalize_path_canonicalizes_only_existing_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let real = dir.path().canonicalize().unwrap();
+        std::fs::create_dir(real.join("sub")).unwrap();
+
+        assert_eq!(normalize_path(&real.join("sub/../sub")), real.join("sub"));
+        let missing = real.join("falta/../x");
+        assert_eq!(normalize_path(&missing), missing);
+    }
+}
@@ -0,0 +1,226 @@
+use crate::error::Result;
+use glob::Pattern;
+use nix::errno::Errno;
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, InotifyEvent, WatchDescriptor};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use walkdir::WalkDir;
+
+/// Cada cuánto se consulta inotify (no bloqueante), como `shutdown::requested`
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Cambios locales relevantes para --watch-fs
+fn watch_flags() -> AddWatchFlags {
+    AddWatchFlags::IN_CLOSE_WRITE
+        | AddWatchFlags::IN_CREATE
+        | AddWatchFlags::IN_DELETE
+        | AddWatchFlags::IN_MOVED_FROM
+        | AddWatchFlags::IN_MOVED_TO
+        | AddWatchFlags::IN_ATTRIB
+}
+
+/// Qué cambios no disparan una sincronización: el directorio de
+/// transferencias parciales, archivos propios de syncb y las exclusiones.
+/// Es una aproximación a las reglas de rsync: si algo se cuela, solo provoca
+/// una sincronización sin cambios.
+pub struct ChangeFilter {
+    root: PathBuf,
+    partial_dir: String,
+    exclusions: Vec<Pattern>,
+}
+
+impl ChangeFilter {
+    pub fn new(root: &Path, partial_dir: &str, exclusions: &[String]) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            partial_dir: partial_dir.trim_matches('/').to_string(),
+            exclusions: exclusions
+                .iter()
+                .filter_map(|pattern| Pattern::new(pattern.trim_matches('/')).ok())
+                .collect(),
+        }
+    }
+
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        let relative_str = relative.to_string_lossy();
+
+        relative.components().any(|component| {
+            let name = component.as_os_str().to_string_lossy();
+            name == self.partial_dir
+                || name.starts_with(".syncb_")
+                || self.exclusions.iter().any(|pattern| pattern.matches(&name))
+        }) || self.exclusions.iter().any(|pattern| pattern.matches(&relative_str))
+    }
+}
+
+/// Vigila recursivamente los orígenes locales con inotify
+pub struct FsWatcher {
+    inotify: Inotify,
+    watches: HashMap<WatchDescriptor, PathBuf>,
+    filter: ChangeFilter,
+}
+
+impl FsWatcher {
+    /// `targets` son las rutas locales de los elementos; de un archivo suelto
+    /// se vigila su directorio
+    pub fn new(targets: &[PathBuf], filter: ChangeFilter) -> Result<Self> {
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC).map_err(std::io::Error::from)?;
+        let mut watcher = Self {
+            inotify,
+            watches: HashMap::new(),
+            filter,
+        };
+
+        for target in targets {
+            if target.is_dir() {
+                watcher.watch_tree(target);
+            } else if let Some(parent) = target.parent().filter(|parent| parent.is_dir()) {
+                watcher.watch_dir(parent);
+            } else {
+                log::warn!("--watch-fs: no se puede vigilar {:?} (no existe)", target);
+            }
+        }
+
+        log::info!("--watch-fs: vigilando {} directorios", watcher.watches.len());
+        Ok(watcher)
+    }
+
+    fn watch_tree(&mut self, root: &Path) {
+        let dirs: Vec<PathBuf> = WalkDir::new(root)
+            .follow_links(false)
+            .into_iter()
+            .filter_entry(|entry| !self.filter.is_ignored(entry.path()))
+            .flatten()
+            .filter(|entry| entry.file_type().is_dir())
+            .map(|entry| entry.into_path())
+            .collect();
+
+        for dir in dirs {
+            self.watch_dir(&dir);
+        }
+    }
+
+    fn watch_dir(&mut self, dir: &Path) {
+        match self.inotify.add_watch(dir, watch_flags()) {
+            Ok(wd) => {
+                self.watches.insert(wd, dir.to_path_buf());
+            }
+            // Sin más watches (fs.inotify.max_user_watches) se sigue con los que haya
+            Err(e) => log::warn!("--watch-fs: no se puede vigilar {:?}: {}", dir, e),
+        }
+    }
+
+    /// Rutas de los eventos pendientes que no se ignoran. Los directorios
+    /// nuevos pasan a vigilarse.
+    fn read_changes(&mut self) -> Result<Vec<PathBuf>> {
+        let events = match self.inotify.read_events() {
+            Ok(events) => events,
+            Err(Errno::EAGAIN) => return Ok(Vec::new()),
+            Err(e) => return Err(std::io::Error::from(e).into()),
+        };
+
+        let mut changes = Vec::new();
+        for event in events {
+            let Some(path) = self.event_path(&event) else {
+                continue;
+            };
+            if self.filter.is_ignored(&path) {
+                continue;
+            }
+            if event.mask.contains(AddWatchFlags::IN_ISDIR)
+                && event.mask.intersects(AddWatchFlags::IN_CREATE | AddWatchFlags::IN_MOVED_TO)
+            {
+                self.watch_tree(&path);
+            }
+            changes.push(path);
+        }
+        Ok(changes)
+    }
+
+    fn event_path(&self, event: &InotifyEvent) -> Option<PathBuf> {
+        let dir = self.watches.get(&event.wd)?;
+        Some(match &event.name {
+            Some(name) => dir.join(name),
+            None => dir.clone(),
+        })
+    }
+
+    /// Espera a que haya cambios y a que pasen `debounce` sin ninguno nuevo,
+    /// de modo que una ráfaga de ediciones produzca una sola sincronización.
+    /// Devuelve `None` si se pide la parada.
+    pub async fn wait_for_changes(&mut self, debounce: Duration) -> Result<Option<Vec<PathBuf>>> {
+        let mut changes = Vec::new();
+        let mut quiet = Duration::ZERO;
+
+        loop {
+            if crate::shutdown::is_requested() {
+                return Ok(None);
+            }
+
+            let new = self.read_changes()?;
+            if new.is_empty() {
+                quiet += POLL_INTERVAL;
+            } else {
+                quiet = Duration::ZERO;
+                changes.extend(new);
+            }
+
+            if !changes.is_empty() && quiet >= debounce {
+                changes.sort();
+                changes.dedup();
+                return Ok(Some(changes));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_change_filter() {
+        let root = Path::new("/home/u");
+        let filter = ChangeFilter::new(root, ".rsync-partial", &["*.tmp".to_string(), "node_modules/".to_string()]);
+
+        assert!(!filter.is_ignored(&root.join("Documentos/informe.odt")));
+        assert!(filter.is_ignored(&root.join("Documentos/.rsync-partial/informe.odt")));
+        assert!(filter.is_ignored(&root.join("Documentos/borrador.tmp")));
+        assert!(filter.is_ignored(&root.join("proyecto/node_modules/x/index.js")));
+        assert!(filter.is_ignored(&root.join(".syncb_write_test.1")));
+    }
+
+    #[tokio::test]
+    async fn test_burst_of_changes_is_debounced() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("Documentos");
+        std::fs::create_dir_all(&root).unwrap();
+        let filter = ChangeFilter::new(temp_dir.path(), ".rsync-partial", &["*.tmp".to_string()]);
+        let mut watcher = FsWatcher::new(std::slice::from_ref(&root), filter).unwrap();
+
+        std::fs::create_dir(root.join("nuevo")).unwrap();
+        std::fs::write(root.join("a.txt"), "a").unwrap();
+        std::fs::write(root.join("ignorado.tmp"), "x").unwrap();
+
+        let changes = watcher
+            .wait_for_changes(Duration::from_millis(400))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(changes.contains(&root.join("a.txt")));
+        assert!(!changes.contains(&root.join("ignorado.tmp")));
+
+        // El directorio creado también se vigila
+        std::fs::write(root.join("nuevo/b.txt"), "b").unwrap();
+        let changes = watcher
+            .wait_for_changes(Duration::from_millis(400))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(changes, vec![root.join("nuevo/b.txt")]);
+    }
+}
@@ -1,8 +1,46 @@
 #[cfg(test)]
 mod tests {
-    use super::*;
     use tempfile::TempDir;
     use std::fs;
+    use std::path::Path;
+    use std::process::Command;
+
+    /// Escribe una configuración mínima con rutas dentro de `root`
+    fn write_config(path: &Path, root: &Path, sync_item: &str) {
+        let root = root.display();
+        fs::write(path, format!(r#"
+            [general]
+            local_dir = "{root}"
+            pcloud_mount_point = "{root}/pCloudDrive"
+            pcloud_backup_comun = "{root}/pCloudDrive/Backups/Backup_Comun"
+            pcloud_backup_readonly = "{root}/pCloudDrive/pCloud Backup/test"
+            log_file = "{root}/syncb.log"
+            lock_file = "{root}/syncb.lock"
+            lock_timeout_seconds = 3600
+            default_timeout_minutes = 30
+
+            [general.crypto]
+            local_crypto_dir = "{root}/Crypto"
+            remote_crypto_dir = "{root}/pCloudDrive/Crypto Folder"
+            cloud_mount_check_file = "mount.check"
+            local_keepass_dir = "{root}/Crypto/Keepass2Android"
+            remote_keepass_dir = "{root}/pCloudDrive/Applications/Keepass2Android"
+            local_crypto_hostname_rtva_dir = "{root}/Crypto/ficheros_sensibles"
+            remote_crypto_hostname_rtva_dir = "{root}/pCloudDrive/Crypto Folder/ficheros_sensibles"
+
+            [hosts.default]
+            sync_items = ["{sync_item}"]
+            exclusions = ["*.tmp"]
+        "#)).unwrap();
+    }
+
+    fn syncb(current_dir: &Path, args: &[&str]) -> std::process::Output {
+        Command::new(env!("CARGO_BIN_EXE_syncb"))
+            .current_dir(current_dir)
+            .args(args)
+            .output()
+            .unwrap()
+    }
 
     #[test]
     fn test_config_loading() {
@@ -49,4 +87,70 @@ mod tests {
         // Test de creación del gestor de sincronización
         // (Implementar según sea necesario)
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_explicit_config_takes_precedence() {
+        let temp_dir = TempDir::new().unwrap();
+        write_config(&temp_dir.path().join("config.toml"), temp_dir.path(), "desde_cwd");
+
+        let explicit = temp_dir.path().join("otra.toml");
+        write_config(&explicit, temp_dir.path(), "desde_explicito");
+
+        let output = syncb(
+            temp_dir.path(),
+            &["--subir", "--list-only", "--config", explicit.to_str().unwrap()],
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        assert!(output.status.success());
+        assert!(stdout.contains("desde_explicito"));
+        assert!(!stdout.contains("desde_cwd"));
+    }
+
+    #[test]
+    fn test_missing_explicit_config_fails() {
+        let temp_dir = TempDir::new().unwrap();
+        write_config(&temp_dir.path().join("config.toml"), temp_dir.path(), "desde_cwd");
+
+        let output = syncb(temp_dir.path(), &["--subir", "--list-only", "--config", "no_existe.toml"]);
+
+        // Código de salida de error de configuración
+        assert_eq!(output.status.code(), Some(2));
+    }
+
+    #[test]
+    fn test_cli_overrides_without_host_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+        write_config(&config_path, temp_dir.path(), "del_host");
+        let contents = fs::read_to_string(&config_path).unwrap();
+        fs::write(&config_path, contents.replace("[hosts.default]", "[hosts.otro_equipo]")).unwrap();
+
+        // --item crea una configuración transitoria para el host actual
+        let with_item = syncb(
+            temp_dir.path(),
+            &["--subir", "--list-only", "--item", "solo_este", "--exclude", "*.bak"],
+        );
+        let stdout = String::from_utf8_lossy(&with_item.stdout);
+        assert!(with_item.status.success());
+        assert!(stdout.contains("solo_este"));
+        assert!(!stdout.contains("del_host"));
+
+        // --exclude sin --item no se ignora en silencio
+        let exclude_only = syncb(temp_dir.path(), &["--subir", "--list-only", "--exclude", "*.bak"]);
+        assert_eq!(exclude_only.status.code(), Some(2));
+    }
+
+    #[test]
+    fn test_missing_local_dir_requires_lenient() {
+        let temp_dir = TempDir::new().unwrap();
+        let missing_root = temp_dir.path().join("no_existe");
+        write_config(&temp_dir.path().join("config.toml"), &missing_root, "item");
+
+        let strict = syncb(temp_dir.path(), &["--subir", "--list-only"]);
+        assert!(!strict.status.success());
+
+        let lenient = syncb(temp_dir.path(), &["--subir", "--list-only", "--lenient"]);
+        assert!(lenient.status.success());
+    }
+}